@@ -1,2 +1,12 @@
+pub mod arena;
+pub mod contacts;
 pub mod core;
+pub mod forces;
+pub mod inertia;
+pub mod links;
+pub mod materials;
+pub mod noise;
 pub mod particle;
+pub mod rigid_body;
+pub mod soft_body;
+pub mod world;
@@ -0,0 +1,370 @@
+//! Holds the generational arena used to store objects behind stable handles.
+//!
+//! Values are kept packed in a single vector so they can be iterated over (and
+//! handed out as a slice) quickly, while each handle goes through a slot that
+//! remembers where its value currently lives. Every slot carries a generation
+//! counter which is bumped on removal, so a handle to a removed value never
+//! resolves again, even after its slot has been reused.
+//!
+//! Removed slots are kept on a free list, so adding and removing values within the
+//! capacity of the arena, like an emitter spawning and despawning particles all the
+//! time, never allocates.
+
+/// Identifies a value stored in an `Arena`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Index {
+    /// Holds the slot the value was stored in.
+    slot: usize,
+
+    /// Holds the generation of the slot when the value was stored.
+    generation: u32,
+}
+
+impl Index {
+    /// Returns the slot the value was stored in.
+    /// Slots are reused after removal, so this alone does not identify a value.
+    pub fn slot(&self) -> usize {
+        self.slot
+    }
+
+    /// Returns the generation of the slot when the value was stored.
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+/// Where the value of a slot currently lives.
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Slot {
+    /// Holds the current generation of the slot.
+    generation: u32,
+
+    /// Holds the position of the value in the packed vector, or `None` if the slot is free.
+    dense: Option<usize>,
+}
+
+/// A collection of values addressed by generational indices.
+///
+/// With the `serde` feature, loading an arena checks that its parts agree, so corrupt
+/// data is rejected instead of panicking later on.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Arena<T> {
+    /// Holds the values, packed together in no particular order.
+    values: Vec<T>,
+
+    /// Holds the slot of each value in `values`.
+    owners: Vec<usize>,
+
+    /// Holds every slot ever allocated.
+    slots: Vec<Slot>,
+
+    /// Holds the slots available for reuse.
+    free: Vec<usize>,
+}
+
+/// The fields of an `Arena`, loaded as is before they are checked.
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct ArenaState<T> {
+    values: Vec<T>,
+    owners: Vec<usize>,
+    slots: Vec<Slot>,
+    free: Vec<usize>,
+}
+
+#[cfg(feature = "serde")]
+impl<'de, T: serde::Deserialize<'de>> serde::Deserialize<'de> for Arena<T> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let state = ArenaState::<T>::deserialize(deserializer)?;
+        if state.values.len() != state.owners.len() {
+            return Err(D::Error::custom(
+                "the values and their owners differ in number",
+            ));
+        }
+        // Every value and its slot point at each other.
+        for (dense, &owner) in state.owners.iter().enumerate() {
+            match state.slots.get(owner) {
+                Some(slot) if slot.dense == Some(dense) => {}
+                _ => return Err(D::Error::custom("a value is not owned by its slot")),
+            }
+        }
+        for (index, slot) in state.slots.iter().enumerate() {
+            if let Some(dense) = slot.dense {
+                if state.owners.get(dense) != Some(&index) {
+                    return Err(D::Error::custom("a slot points at a value it doesn't own"));
+                }
+            }
+        }
+        // The free list only holds free slots, each once.
+        let mut listed = vec![false; state.slots.len()];
+        for &slot in &state.free {
+            match state.slots.get(slot) {
+                Some(free) if free.dense.is_none() && !listed[slot] => listed[slot] = true,
+                _ => {
+                    return Err(D::Error::custom(
+                        "the free list holds a slot that isn't free",
+                    ))
+                }
+            }
+        }
+
+        Ok(Self {
+            values: state.values,
+            owners: state.owners,
+            slots: state.slots,
+            free: state.free,
+        })
+    }
+}
+
+impl<T> Default for Arena<T> {
+    fn default() -> Self {
+        Self {
+            values: Vec::new(),
+            owners: Vec::new(),
+            slots: Vec::new(),
+            free: Vec::new(),
+        }
+    }
+}
+
+impl<T> Arena<T> {
+    /// Constructor
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Constructs an arena with room for the given number of values, so storing up to
+    /// that many never reallocates.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            values: Vec::with_capacity(capacity),
+            owners: Vec::with_capacity(capacity),
+            slots: Vec::with_capacity(capacity),
+            free: Vec::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the number of values the arena can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.values.capacity()
+    }
+
+    /// Makes room for at least the given number of values on top of the ones stored.
+    pub fn reserve(&mut self, additional: usize) {
+        self.values.reserve(additional);
+        self.owners.reserve(additional);
+        let slots = (self.values.len() + additional).saturating_sub(self.slots.len());
+        self.slots.reserve(slots);
+        self.free
+            .reserve(self.slots.len() + slots - self.free.len());
+    }
+
+    /// Stores the given value and returns its index.
+    /// The slot of a removed value is reused before allocating a new one.
+    pub fn insert(&mut self, value: T) -> Index {
+        let dense = self.values.len();
+        let slot = match self.free.pop() {
+            Some(slot) => {
+                self.slots[slot].dense = Some(dense);
+                slot
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    dense: Some(dense),
+                });
+                self.slots.len() - 1
+            }
+        };
+        self.values.push(value);
+        self.owners.push(slot);
+        Index {
+            slot,
+            generation: self.slots[slot].generation,
+        }
+    }
+
+    /// Removes the given value and returns it, or `None` if the index is stale.
+    /// The last value of `values` is moved into the freed position.
+    pub fn remove(&mut self, index: Index) -> Option<T> {
+        let dense = self.dense_index(index)?;
+
+        let slot = &mut self.slots[index.slot];
+        slot.dense = None;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(index.slot);
+
+        let value = self.values.swap_remove(dense);
+        self.owners.swap_remove(dense);
+        if let Some(&moved) = self.owners.get(dense) {
+            self.slots[moved].dense = Some(dense);
+        }
+        Some(value)
+    }
+
+    /// Returns the position of the given value in `values`, or `None` if the index is stale.
+    /// Positions change when other values are removed.
+    pub fn dense_index(&self, index: Index) -> Option<usize> {
+        self.slots
+            .get(index.slot)
+            .filter(|slot| slot.generation == index.generation)
+            .and_then(|slot| slot.dense)
+    }
+
+    /// Returns the index of the value at the given position in `values`.
+    pub fn index_at(&self, dense: usize) -> Index {
+        let slot = self.owners[dense];
+        Index {
+            slot,
+            generation: self.slots[slot].generation,
+        }
+    }
+
+    /// Returns true if the index refers to a stored value.
+    pub fn contains(&self, index: Index) -> bool {
+        self.dense_index(index).is_some()
+    }
+
+    /// Returns the given value, or `None` if the index is stale.
+    pub fn get(&self, index: Index) -> Option<&T> {
+        self.dense_index(index).map(|dense| &self.values[dense])
+    }
+
+    /// Returns the given value for modification, or `None` if the index is stale.
+    pub fn get_mut(&mut self, index: Index) -> Option<&mut T> {
+        self.dense_index(index)
+            .map(move |dense| &mut self.values[dense])
+    }
+
+    /// Returns the number of values stored.
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Returns true if no values are stored.
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+
+    /// Removes every value. Every index given so far becomes stale.
+    pub fn clear(&mut self) {
+        for &slot in &self.owners {
+            self.slots[slot].dense = None;
+            self.slots[slot].generation = self.slots[slot].generation.wrapping_add(1);
+            self.free.push(slot);
+        }
+        self.values.clear();
+        self.owners.clear();
+    }
+
+    /// Returns the values, packed together in no particular order.
+    pub fn values(&self) -> &[T] {
+        &self.values
+    }
+
+    /// Returns the values for modification, packed together in no particular order.
+    pub fn values_mut(&mut self) -> &mut [T] {
+        &mut self.values
+    }
+
+    /// Iterates over the indices and values stored.
+    pub fn iter(&self) -> impl Iterator<Item = (Index, &T)> + '_ {
+        (0..self.values.len()).map(move |dense| (self.index_at(dense), &self.values[dense]))
+    }
+
+    /// Iterates over the indices and values stored, for modification.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (Index, &mut T)> + '_ {
+        let slots = &self.slots;
+        self.owners
+            .iter()
+            .zip(self.values.iter_mut())
+            .map(move |(&slot, value)| {
+                (
+                    Index {
+                        slot,
+                        generation: slots[slot].generation,
+                    },
+                    value,
+                )
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removing_keeps_the_other_indices_valid() {
+        let mut arena = Arena::new();
+        let a = arena.insert("a");
+        let b = arena.insert("b");
+        let c = arena.insert("c");
+
+        assert_eq!(arena.remove(b), Some("b"));
+        assert_eq!(arena.get(a), Some(&"a"));
+        assert_eq!(arena.get(c), Some(&"c"));
+        assert_eq!(arena.get(b), None);
+        assert_eq!(arena.remove(b), None);
+        assert_eq!(arena.len(), 2);
+
+        // The freed slot is reused under a new generation.
+        let d = arena.insert("d");
+        assert_eq!(d.slot(), b.slot());
+        assert_ne!(d.generation(), b.generation());
+        assert_eq!(arena.get(b), None);
+        let mut values: Vec<_> = arena.iter().map(|(index, &value)| (value, index)).collect();
+        values.sort();
+        assert_eq!(values, [("a", a), ("c", c), ("d", d)]);
+    }
+
+    #[test]
+    fn clear_makes_every_index_stale() {
+        let mut arena = Arena::with_capacity(4);
+        let indices: Vec<_> = (0..4).map(|i| arena.insert(i)).collect();
+        arena.clear();
+        assert!(arena.is_empty());
+        assert!(indices.iter().all(|&index| !arena.contains(index)));
+        let index = arena.insert(7);
+        assert_eq!(arena.get(index), Some(&7));
+        assert_eq!(arena.capacity(), 4);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn loading_rejects_parts_that_disagree() {
+        let mut arena = Arena::new();
+        let a = arena.insert(1);
+        let b = arena.insert(2);
+        arena.insert(3);
+        arena.remove(b);
+        let json = serde_json::to_string(&arena).unwrap();
+        let loaded: Arena<i32> = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.get(a), Some(&1));
+        assert_eq!(loaded.get(b), None);
+
+        let corrupt = |change: fn(&mut serde_json::Value)| {
+            let mut state: serde_json::Value = serde_json::from_str(&json).unwrap();
+            change(&mut state);
+            serde_json::from_value::<Arena<i32>>(state)
+        };
+        // A value without an owner.
+        assert!(corrupt(|state| state["values"] = serde_json::json!([1, 3, 4])).is_err());
+        // An owner past the slots.
+        assert!(corrupt(|state| state["owners"][0] = serde_json::json!(9)).is_err());
+        // Two values owned by the same slot.
+        assert!(corrupt(|state| state["owners"][1] = serde_json::json!(0)).is_err());
+        // A slot pointing past the values.
+        assert!(corrupt(|state| state["slots"][0]["dense"] = serde_json::json!(5)).is_err());
+        // A free slot past the slots, a live one, and the same one twice.
+        assert!(corrupt(|state| state["free"] = serde_json::json!([7])).is_err());
+        assert!(corrupt(|state| state["free"] = serde_json::json!([1, 0])).is_err());
+        assert!(corrupt(|state| state["free"] = serde_json::json!([1, 1])).is_err());
+    }
+}
@@ -0,0 +1,1760 @@
+//! Holds the contacts between particles and the means to resolve them, and in `rigid`
+//! the contacts between rigid bodies.
+
+pub mod rigid;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+use crate::kellenth::core::*;
+use crate::kellenth::materials::{MaterialId, MaterialTable};
+use crate::kellenth::world::{ParticleHandle, ParticleSet};
+
+/// Represents two particles in contact, or a particle in contact with the scenery.
+/// Resolving a contact removes the interpenetration and applies enough impulse
+/// to keep the particles apart.
+///
+/// Particles are referred to by handle; resolving a contact whose particles are gone panics.
+#[derive(Debug, Clone, Copy)]
+pub struct ParticleContact {
+    /// Holds the particles involved in the contact.
+    /// The second one is `None` for contacts with the scenery.
+    pub particles: (ParticleHandle, Option<ParticleHandle>),
+
+    /// Holds the normal restitution coefficient at the contact.
+    pub restitution: f64,
+
+    /// Holds the direction of the contact in world coordinates,
+    /// from the point of view of the first particle.
+    pub contact_normal: Vector3,
+
+    /// Holds the depth of the penetration at the contact.
+    pub penetration: f64,
+
+    /// Holds how much each particle was moved during the interpenetration resolution.
+    particle_movement: [Vector3; 2],
+
+    /// Holds the impulse applied along the normal by every velocity resolution so far.
+    impulse: f64,
+}
+
+impl ParticleContact {
+    /// Constructor
+    pub fn new(
+        particles: (ParticleHandle, Option<ParticleHandle>),
+        restitution: f64,
+        contact_normal: Vector3,
+        penetration: f64,
+    ) -> Self {
+        let zero = Vector3::new(0., 0., 0.);
+        Self {
+            particles,
+            restitution,
+            contact_normal,
+            penetration,
+            particle_movement: [zero, zero],
+            impulse: 0.,
+        }
+    }
+
+    /// Returns how much each particle was moved by the last interpenetration resolution.
+    pub fn get_particle_movement(&self) -> [Vector3; 2] {
+        self.particle_movement
+    }
+
+    /// Returns the impulse applied along the normal by every velocity resolution so far.
+    /// The first particle received this impulse, and the second one the opposite.
+    pub fn get_impulse(&self) -> f64 {
+        self.impulse
+    }
+
+    /// Resolves this contact, for both velocity and interpenetration.
+    pub fn resolve(&mut self, particles: &mut ParticleSet, duration: f64) {
+        self.resolve_velocity(particles, duration);
+        self.resolve_interpenetration(particles, duration);
+    }
+
+    /// Returns the separating velocity at this contact.
+    /// Negative values mean the particles are closing in on each other.
+    pub fn calculate_separating_velocity(&self, particles: &ParticleSet) -> f64 {
+        let mut relative_velocity = particles[self.particles.0].velocity;
+        if let Some(other) = self.particles.1 {
+            relative_velocity -= particles[other].velocity;
+        }
+        relative_velocity * self.contact_normal
+    }
+
+    /// Returns the inverse mass of the particles involved, added together.
+    fn total_inverse_mass(&self, particles: &ParticleSet) -> f64 {
+        let mut total = particles[self.particles.0].get_inverse_mass();
+        if let Some(other) = self.particles.1 {
+            total += particles[other].get_inverse_mass();
+        }
+        total
+    }
+
+    /// Applies the impulse needed to reach the separating velocity given by the restitution,
+    /// split between the particles by their inverse mass.
+    pub fn resolve_velocity(&mut self, particles: &mut ParticleSet, duration: f64) {
+        // Find the velocity in the direction of the contact.
+        let separating_velocity = self.calculate_separating_velocity(particles);
+
+        // Check if it needs to be resolved.
+        if separating_velocity > 0. {
+            // The contact is either separating, or stationary; no impulse is required.
+            return;
+        }
+
+        let delta_velocity =
+            self.target_separating_velocity(particles, duration) - separating_velocity;
+
+        // We apply the change in velocity to each object in proportion to
+        // their inverse mass (i.e. those with lower inverse mass [higher
+        // actual mass] get less change in velocity).
+        let total_inverse_mass = self.total_inverse_mass(particles);
+
+        // If all particles have infinite mass, then impulses have no effect.
+        if total_inverse_mass <= 0. {
+            return;
+        }
+
+        // Find the amount of impulse per unit of inverse mass.
+        self.apply_impulse(particles, delta_velocity / total_inverse_mass);
+    }
+
+    /// Returns the separating velocity the restitution asks for, given the current one.
+    fn target_separating_velocity(&self, particles: &ParticleSet, duration: f64) -> f64 {
+        let separating_velocity = self.calculate_separating_velocity(particles);
+        if separating_velocity > 0. {
+            return separating_velocity;
+        }
+
+        // Calculate the new separating velocity.
+        let mut new_separating_velocity = -separating_velocity * self.restitution;
+
+        // Check the velocity build-up due to acceleration only.
+        // Resting contacts gain this much closing velocity every frame,
+        // and bouncing it back would make them jitter.
+        let mut acceleration_caused_velocity =
+            particles[self.particles.0].get_last_frame_acceleration();
+        if let Some(other) = self.particles.1 {
+            acceleration_caused_velocity -= particles[other].get_last_frame_acceleration();
+        }
+        let acceleration_caused_separating_velocity =
+            acceleration_caused_velocity * self.contact_normal * duration;
+
+        // If we've got a closing velocity due to acceleration build-up,
+        // remove it from the new separating velocity.
+        if acceleration_caused_separating_velocity < 0. {
+            new_separating_velocity += self.restitution * acceleration_caused_separating_velocity;
+
+            // Make sure we haven't removed more than was there to remove.
+            if new_separating_velocity < 0. {
+                new_separating_velocity = 0.;
+            }
+        }
+        new_separating_velocity
+    }
+
+    /// Applies the given impulse along the normal, to the first particle,
+    /// and the opposite one to the second.
+    fn apply_impulse(&mut self, particles: &mut ParticleSet, impulse: f64) {
+        self.impulse += impulse;
+        let impulse_per_inverse_mass = self.contact_normal * impulse;
+
+        // Apply impulses: they are applied in the direction of the contact,
+        // and are proportional to the inverse mass.
+        let first = &mut particles[self.particles.0];
+        first.velocity += impulse_per_inverse_mass * first.get_inverse_mass();
+        if let Some(other) = self.particles.1 {
+            let second = &mut particles[other];
+            second.velocity += impulse_per_inverse_mass * -second.get_inverse_mass();
+        }
+    }
+
+    /// Moves the particles apart along the contact normal until they no longer interpenetrate,
+    /// each in proportion to its inverse mass.
+    pub fn resolve_interpenetration(&mut self, particles: &mut ParticleSet, _duration: f64) {
+        let zero = Vector3::new(0., 0., 0.);
+        self.particle_movement = [zero, zero];
+
+        // If we don't have any penetration, skip this step.
+        if self.penetration <= 0. {
+            return;
+        }
+
+        // The movement of each object is based on its inverse mass, so total that.
+        let total_inverse_mass = self.total_inverse_mass(particles);
+
+        // If all particles have infinite mass, then we do nothing.
+        if total_inverse_mass <= 0. {
+            return;
+        }
+
+        // Find the amount of penetration resolution per unit of inverse mass.
+        self.move_apart(particles, self.penetration / total_inverse_mass);
+    }
+
+    /// Moves the particles apart along the normal by the given amount per unit of inverse mass,
+    /// recording the movement of each.
+    fn move_apart(&mut self, particles: &mut ParticleSet, move_per_inverse_mass: f64) {
+        let move_per_inverse_mass = self.contact_normal * move_per_inverse_mass;
+
+        // Calculate the movement amounts and apply them.
+        match self.particles.1 {
+            Some(other) => {
+                let (first, second) = particles.pair_mut(self.particles.0, other);
+                self.particle_movement = [
+                    move_per_inverse_mass * first.get_inverse_mass(),
+                    move_per_inverse_mass * -second.get_inverse_mass(),
+                ];
+                first.position += self.particle_movement[0];
+                second.position += self.particle_movement[1];
+            }
+            None => {
+                let first = &mut particles[self.particles.0];
+                self.particle_movement[0] = move_per_inverse_mass * first.get_inverse_mass();
+                first.position += self.particle_movement[0];
+            }
+        }
+    }
+}
+
+/// The contact resolution routine for particle contacts.
+/// One resolver instance can be shared for the whole simulation.
+///
+/// Contacts are resolved one at a time, always picking the one with the most
+/// negative separating velocity, the deepest first among equals, and the penetration of the other contacts is
+/// updated as particles are moved, so later resolutions build on earlier ones.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ParticleContactResolver {
+    /// Holds the number of iterations allowed.
+    iterations: usize,
+
+    /// Holds the number of iterations actually used by the last resolution.
+    iterations_used: usize,
+}
+
+impl ParticleContactResolver {
+    /// Constructor
+    pub fn new(iterations: usize) -> Self {
+        Self {
+            iterations,
+            iterations_used: 0,
+        }
+    }
+
+    /// Sets the number of iterations that can be used.
+    pub fn set_iterations(&mut self, iterations: usize) {
+        self.iterations = iterations;
+    }
+
+    /// Returns the number of iterations that can be used.
+    pub fn get_iterations(&self) -> usize {
+        self.iterations
+    }
+
+    /// Returns the number of iterations used by the last call to `resolve_contacts`.
+    pub fn get_iterations_used(&self) -> usize {
+        self.iterations_used
+    }
+
+    /// Resolves a set of particle contacts for both penetration and velocity.
+    /// Stops early once no contact is closing or penetrating anymore.
+    pub fn resolve_contacts(
+        &mut self,
+        contacts: &mut [ParticleContact],
+        particles: &mut ParticleSet,
+        duration: f64,
+    ) {
+        self.iterations_used = 0;
+        while self.iterations_used < self.iterations {
+            // Find the contact with the largest closing velocity, or the deepest one among
+            // those closing equally fast, so resting contacts don't starve each other.
+            let mut max = f64::MAX;
+            let mut max_penetration = f64::NEG_INFINITY;
+            let mut max_index = None;
+            for (index, contact) in contacts.iter().enumerate() {
+                let separating_velocity = contact.calculate_separating_velocity(particles);
+                let closer = separating_velocity < max
+                    || (separating_velocity == max && contact.penetration > max_penetration);
+                if closer && (separating_velocity < 0. || contact.penetration > 0.) {
+                    max = separating_velocity;
+                    max_penetration = contact.penetration;
+                    max_index = Some(index);
+                }
+            }
+
+            // Do we have anything worth resolving?
+            let max_index = match max_index {
+                Some(max_index) => max_index,
+                None => break,
+            };
+
+            // Resolve this contact.
+            contacts[max_index].resolve(particles, duration);
+
+            // Update the interpenetrations for all the contacts sharing a particle.
+            let resolved = contacts[max_index].particles;
+            let movement = contacts[max_index].get_particle_movement();
+            let moved = |particle: ParticleHandle| {
+                if particle == resolved.0 {
+                    Some(movement[0])
+                } else if Some(particle) == resolved.1 {
+                    Some(movement[1])
+                } else {
+                    None
+                }
+            };
+            for contact in contacts.iter_mut() {
+                if let Some(movement) = moved(contact.particles.0) {
+                    contact.penetration -= movement * contact.contact_normal;
+                }
+                if let Some(movement) = contact.particles.1.and_then(moved) {
+                    contact.penetration += movement * contact.contact_normal;
+                }
+            }
+
+            self.iterations_used += 1;
+        }
+    }
+}
+
+/// A contact resolver that works on all the contacts together, instead of one at a time.
+///
+/// Velocities are resolved first, sweeping over every contact a few times and keeping the
+/// total impulse of each contact pushing, never pulling. The change in velocity also moves
+/// the particles, since `ParticleWorld` moves them before resolving the contacts. Then the
+/// contacts are swept over `position_iterations` times, each moved apart by a fraction,
+/// `correction_percent`, of its penetration beyond `slop`, so deep stacks climb back out
+/// smoothly instead of jittering as each contact is fixed completely at the expense of its
+/// neighbors.
+///
+/// The total impulse of every contact is remembered and applied again at the start of the
+/// next call, for the contact between the same particles, which lets resting stacks converge
+/// over several frames with only a few sweeps each.
+#[derive(Debug, Clone)]
+pub struct ContactBatch {
+    /// Holds the maximum number of velocity sweeps over the contacts.
+    iterations: usize,
+
+    /// Holds the number of velocity sweeps actually used by the last resolution.
+    iterations_used: usize,
+
+    /// Holds the number of penetration sweeps over the contacts.
+    position_iterations: usize,
+
+    /// Holds the penetration allowed to remain, which keeps resting contacts alive.
+    pub slop: f64,
+
+    /// Holds the fraction, between 0 and 1, of the remaining penetration corrected every call.
+    pub correction_percent: f64,
+
+    /// Holds the total impulse and normal of every contact resolved by the last call.
+    previous_impulses: HashMap<ContactKey, (f64, Vector3)>,
+}
+
+/// Identifies a contact across frames by its particles, and by its order among the
+/// contacts between the same particles, since a rod makes two of them.
+type ContactKey = (ParticleHandle, Option<ParticleHandle>, usize);
+
+impl Default for ContactBatch {
+    fn default() -> Self {
+        Self::new(10, 0.01, 0.2)
+    }
+}
+
+impl ContactBatch {
+    /// Constructor
+    /// The penetrations are swept over twice, until set otherwise.
+    pub fn new(iterations: usize, slop: f64, correction_percent: f64) -> Self {
+        Self {
+            iterations,
+            iterations_used: 0,
+            position_iterations: 2,
+            slop,
+            correction_percent,
+            previous_impulses: HashMap::new(),
+        }
+    }
+
+    /// Sets the maximum number of velocity sweeps over the contacts.
+    pub fn set_iterations(&mut self, iterations: usize) {
+        self.iterations = iterations;
+    }
+
+    /// Returns the maximum number of velocity sweeps over the contacts.
+    pub fn get_iterations(&self) -> usize {
+        self.iterations
+    }
+
+    /// Sets the number of penetration sweeps over the contacts.
+    /// More sweeps remove the penetration faster, but too many make chains of links,
+    /// whose normals change as they move, overshoot.
+    pub fn set_position_iterations(&mut self, position_iterations: usize) {
+        self.position_iterations = position_iterations;
+    }
+
+    /// Returns the number of penetration sweeps over the contacts.
+    pub fn get_position_iterations(&self) -> usize {
+        self.position_iterations
+    }
+
+    /// Returns the number of velocity sweeps used by the last call to `resolve_contacts`.
+    pub fn get_iterations_used(&self) -> usize {
+        self.iterations_used
+    }
+
+    /// Forgets the impulses remembered from the last call, e.g. after teleporting particles.
+    pub fn clear_impulses(&mut self) {
+        self.previous_impulses.clear();
+    }
+
+    /// Resolves a set of particle contacts for both velocity and penetration.
+    /// Stops sweeping early once a sweep applies no impulse.
+    pub fn resolve_contacts(
+        &mut self,
+        contacts: &mut [ParticleContact],
+        particles: &mut ParticleSet,
+        duration: f64,
+    ) {
+        // Every contact aims for the separating velocity its restitution asks for at the start.
+        let targets: Vec<f64> = contacts
+            .iter()
+            .map(|contact| {
+                let separating_velocity = contact.calculate_separating_velocity(particles);
+                if separating_velocity > 0. {
+                    0.
+                } else {
+                    contact.target_separating_velocity(particles, duration)
+                }
+            })
+            .collect();
+        let mut accumulated = vec![0.; contacts.len()];
+        let mut initial_velocities: HashMap<ParticleHandle, Vector3> = HashMap::new();
+        for contact in contacts.iter() {
+            let handles = std::iter::once(contact.particles.0).chain(contact.particles.1);
+            for handle in handles {
+                initial_velocities
+                    .entry(handle)
+                    .or_insert(particles[handle].velocity);
+            }
+        }
+
+        // Start from the impulses of the last call, as far as the normals still agree.
+        let mut occurrences: HashMap<(ParticleHandle, Option<ParticleHandle>), usize> =
+            HashMap::new();
+        let keys: Vec<ContactKey> = contacts
+            .iter()
+            .map(|contact| {
+                let occurrence = occurrences.entry(contact.particles).or_insert(0);
+                *occurrence += 1;
+                (contact.particles.0, contact.particles.1, *occurrence - 1)
+            })
+            .collect();
+        for (index, contact) in contacts.iter_mut().enumerate() {
+            if let Some(&(impulse, normal)) = self.previous_impulses.get(&keys[index]) {
+                let impulse = impulse * (normal * contact.contact_normal).max(0.);
+                if impulse > 0. && contact.total_inverse_mass(particles) > 0. {
+                    accumulated[index] = impulse;
+                    contact.apply_impulse(particles, impulse);
+                }
+            }
+        }
+
+        self.iterations_used = 0;
+        while self.iterations_used < self.iterations {
+            let mut applied_any = false;
+            for (index, contact) in contacts.iter_mut().enumerate() {
+                let total_inverse_mass = contact.total_inverse_mass(particles);
+                if total_inverse_mass <= 0. {
+                    continue;
+                }
+
+                // Clamp the total impulse rather than this one, so a contact can take back
+                // what it applied earlier in the sweep, but never ends up pulling.
+                let separating_velocity = contact.calculate_separating_velocity(particles);
+                let impulse = (targets[index] - separating_velocity) / total_inverse_mass;
+                let total = (accumulated[index] + impulse).max(0.);
+                let impulse = total - accumulated[index];
+                accumulated[index] = total;
+                if impulse != 0. {
+                    contact.apply_impulse(particles, impulse);
+                    applied_any = true;
+                }
+            }
+            self.iterations_used += 1;
+            if !applied_any {
+                break;
+            }
+        }
+
+        self.previous_impulses = keys
+            .into_iter()
+            .zip(contacts.iter())
+            .zip(&accumulated)
+            .map(|((key, contact), &impulse)| (key, (impulse, contact.contact_normal)))
+            .collect();
+
+        // The particles were moved with their velocities before the contacts were resolved,
+        // so carry the change in velocity into the positions, as if the impulses had come
+        // first. Otherwise resting contacts sink by a frame of gravity every frame.
+        let mut carried = initial_velocities;
+        for (&handle, movement) in carried.iter_mut() {
+            let particle = &mut particles[handle];
+            *movement = (particle.velocity - *movement) * duration;
+            particle.position += *movement;
+        }
+        for contact in contacts.iter_mut() {
+            contact.penetration -= carried[&contact.particles.0] * contact.contact_normal;
+            if let Some(other) = contact.particles.1 {
+                contact.penetration += carried[&other] * contact.contact_normal;
+            }
+        }
+
+        // Correct the penetrations left a fraction at a time, over a few sweeps, updating the
+        // penetration of the contacts sharing a particle as it moves.
+        let mut sharing: HashMap<ParticleHandle, Vec<usize>> = HashMap::new();
+        for (index, contact) in contacts.iter().enumerate() {
+            let handles = std::iter::once(contact.particles.0).chain(contact.particles.1);
+            for handle in handles {
+                sharing.entry(handle).or_default().push(index);
+            }
+        }
+        let zero = Vector3::new(0., 0., 0.);
+        let mut movement = vec![[zero, zero]; contacts.len()];
+        for _ in 0..self.position_iterations {
+            for index in 0..contacts.len() {
+                let contact = &mut contacts[index];
+                let total_inverse_mass = contact.total_inverse_mass(particles);
+                let depth = (contact.penetration - self.slop).max(0.);
+                if total_inverse_mass <= 0. || depth <= 0. {
+                    continue;
+                }
+                contact.move_apart(
+                    particles,
+                    depth * self.correction_percent / total_inverse_mass,
+                );
+                let moved = contact.particle_movement;
+                movement[index][0] += moved[0];
+                movement[index][1] += moved[1];
+
+                let resolved = contact.particles;
+                let handles = std::iter::once((resolved.0, moved[0]))
+                    .chain(resolved.1.map(|other| (other, moved[1])));
+                for (handle, moved) in handles {
+                    for &other in &sharing[&handle] {
+                        let other = &mut contacts[other];
+                        if other.particles.0 == handle {
+                            other.penetration -= moved * other.contact_normal;
+                        }
+                        if other.particles.1 == Some(handle) {
+                            other.penetration += moved * other.contact_normal;
+                        }
+                    }
+                }
+            }
+        }
+        for (contact, movement) in contacts.iter_mut().zip(movement) {
+            contact.particle_movement = movement;
+        }
+    }
+}
+
+/// Describes a contact resolved by a `ParticleWorld`, for the contact listener.
+#[derive(Debug, Clone, Copy)]
+pub struct ContactEvent {
+    /// Holds the particles involved in the contact.
+    /// The second one is `None` for contacts with the scenery.
+    pub handles: (ParticleHandle, Option<ParticleHandle>),
+
+    /// Holds the direction of the contact, from the point of view of the first particle.
+    pub normal: Vector3,
+
+    /// Holds the depth of the penetration when the contact was generated.
+    pub penetration: f64,
+
+    /// Holds the separating velocity before the contact was resolved.
+    /// Negative values mean the particles were closing in on each other.
+    pub separating_velocity_before: f64,
+
+    /// Holds the impulse applied along the normal to resolve the contact.
+    pub impulse_applied: f64,
+}
+
+/// Gets told about the contacts resolved by a `ParticleWorld`, to play sounds,
+/// spawn effects or keep score.
+pub trait ParticleContactListener {
+    /// Returns true if the listener wants to hear about the given contact.
+    /// Every event is accepted by default; filtering here keeps resting contacts,
+    /// which are resolved every frame, from flooding the listener.
+    fn accepts(&self, event: &ContactEvent) -> bool {
+        let _ = event;
+        true
+    }
+
+    /// Called after resolution for every contact accepted by `accepts`.
+    fn on_contact(&mut self, event: &ContactEvent);
+}
+
+/// Adapts any closure into a contact listener, only told about the contacts
+/// resolved with at least the given impulse.
+pub struct ParticleContactListenerClosure<F>
+where
+    F: FnMut(&ContactEvent),
+{
+    /// Holds the smallest impulse of the contacts passed to the closure.
+    pub min_impulse: f64,
+
+    /// Holds the closure called for every accepted contact.
+    closure: F,
+}
+
+impl<F> ParticleContactListenerClosure<F>
+where
+    F: FnMut(&ContactEvent),
+{
+    /// Constructor
+    /// Use a `min_impulse` of zero to hear about every contact.
+    pub fn new(min_impulse: f64, closure: F) -> Self {
+        Self {
+            min_impulse,
+            closure,
+        }
+    }
+}
+
+impl<F> ParticleContactListener for ParticleContactListenerClosure<F>
+where
+    F: FnMut(&ContactEvent),
+{
+    fn accepts(&self, event: &ContactEvent) -> bool {
+        event.impulse_applied.abs() >= self.min_impulse
+    }
+
+    fn on_contact(&mut self, event: &ContactEvent) {
+        (self.closure)(event);
+    }
+}
+
+/// This is the basic polymorphic interface for contact generators applying to particles.
+pub trait ParticleContactGenerator {
+    /// Fills the given contact list with the contacts generated from the given particles,
+    /// appending at most `limit` contacts. Returns the number of contacts added.
+    fn add_contacts(
+        &self,
+        particles: &ParticleSet,
+        contacts: &mut Vec<ParticleContact>,
+        limit: usize,
+    ) -> usize;
+}
+
+/// Adapts any closure into a contact generator.
+/// Contacts the closure adds past the limit are discarded.
+pub struct ParticleContactClosure<F>
+where
+    F: Fn(&ParticleSet, &mut Vec<ParticleContact>, usize) -> usize,
+{
+    /// Holds the closure called to generate contacts.
+    closure: F,
+}
+
+impl<F> ParticleContactClosure<F>
+where
+    F: Fn(&ParticleSet, &mut Vec<ParticleContact>, usize) -> usize,
+{
+    /// Constructor
+    pub fn new(closure: F) -> Self {
+        Self { closure }
+    }
+}
+
+impl<F> ParticleContactGenerator for ParticleContactClosure<F>
+where
+    F: Fn(&ParticleSet, &mut Vec<ParticleContact>, usize) -> usize,
+{
+    fn add_contacts(
+        &self,
+        particles: &ParticleSet,
+        contacts: &mut Vec<ParticleContact>,
+        limit: usize,
+    ) -> usize {
+        let start = contacts.len();
+        (self.closure)(particles, contacts, limit);
+        contacts.truncate(start.saturating_add(limit));
+        contacts.len() - start
+    }
+}
+
+/// Collects the contacts of every generator in turn, until `limit` contacts have been added.
+/// Returns the number of contacts added.
+pub fn generate_contacts(
+    generators: &[Box<dyn ParticleContactGenerator>],
+    particles: &ParticleSet,
+    contacts: &mut Vec<ParticleContact>,
+    limit: usize,
+) -> usize {
+    let start = contacts.len();
+    for generator in generators {
+        let remaining = limit - (contacts.len() - start);
+        if remaining == 0 {
+            break;
+        }
+        generator.add_contacts(particles, contacts, remaining);
+
+        // Don't trust the generator to respect the limit.
+        contacts.truncate(start.saturating_add(limit));
+    }
+    contacts.len() - start
+}
+
+/// A contact generator colliding particles with a plane, usually the ground.
+///
+/// Particles are treated as spheres of the same radius, and are kept on the side
+/// of the plane its normal points to. The plane can have any orientation.
+#[derive(Debug, Clone)]
+pub struct GroundContactGenerator {
+    /// Holds the plane the particles collide with.
+    pub plane: Plane,
+
+    /// Holds the restitution of the contacts, used when no material table is set.
+    pub restitution: f64,
+
+    /// Holds the radius of the particles.
+    pub particle_radius: f64,
+
+    /// Holds the material of the plane.
+    pub material: MaterialId,
+
+    /// Holds the table the restitution is looked up in, if any.
+    materials: Option<Rc<MaterialTable>>,
+
+    /// Holds the particles colliding with the plane.
+    particles: Vec<ParticleHandle>,
+}
+
+impl GroundContactGenerator {
+    /// Constructor
+    pub fn new(plane: Plane, restitution: f64, particle_radius: f64) -> Self {
+        Self {
+            plane,
+            restitution,
+            particle_radius,
+            material: MaterialId::DEFAULT,
+            materials: None,
+            particles: Vec::new(),
+        }
+    }
+
+    /// Makes the restitution of the contacts come from the given table, using the
+    /// materials of the plane and of each particle.
+    pub fn set_material_table(&mut self, materials: Rc<MaterialTable>) {
+        self.materials = Some(materials);
+    }
+
+    /// Registers the given particle to collide with the plane.
+    pub fn add_particle(&mut self, particle: ParticleHandle) {
+        self.particles.push(particle);
+    }
+
+    /// Returns the particles colliding with the plane.
+    pub fn get_particles(&self) -> &[ParticleHandle] {
+        &self.particles
+    }
+}
+
+impl ParticleContactGenerator for GroundContactGenerator {
+    fn add_contacts(
+        &self,
+        particles: &ParticleSet,
+        contacts: &mut Vec<ParticleContact>,
+        limit: usize,
+    ) -> usize {
+        let mut count = 0;
+        for &handle in &self.particles {
+            if count >= limit {
+                break;
+            }
+            // Particles removed since they were registered are skipped.
+            let particle = match particles.get(handle) {
+                Some(particle) => particle,
+                None => continue,
+            };
+            let distance = self.plane.signed_distance(particle.position);
+            if distance < self.particle_radius {
+                let restitution = match &self.materials {
+                    Some(materials) => materials.restitution(particle.material, self.material),
+                    None => self.restitution,
+                };
+                contacts.push(ParticleContact::new(
+                    (handle, None),
+                    restitution,
+                    self.plane.normal,
+                    self.particle_radius - distance,
+                ));
+                count += 1;
+            }
+        }
+        count
+    }
+}
+
+/// A particle registered for collisions, treated as a sphere of the given radius.
+#[derive(Debug, Clone, Copy)]
+pub struct CollidableParticle {
+    /// Holds the particle.
+    pub particle: ParticleHandle,
+
+    /// Holds the collision radius of the particle.
+    pub radius: f64,
+}
+
+/// A broad phase for sphere collisions, hashing every sphere into the cubic cells
+/// its bounding box overlaps.
+///
+/// Two spheres can only touch if they share a cell, so only those pairs are yielded
+/// as potential pairs. Spheres larger than a cell are inserted into every cell they
+/// overlap, up to `MAX_CELLS_PER_SPHERE` cells; bigger ones are kept aside and paired
+/// with every other sphere instead, so any cell size gives correct results, if slowly
+/// when it is far too small. A size close to the typical diameter is the fastest.
+///
+/// Spheres with a center or radius that isn't finite have no place in any cell, and
+/// are left out of every pair.
+#[derive(Debug, Clone)]
+pub struct SpatialHash {
+    /// Holds the side length of a single cubic cell.
+    cell_size: f64,
+
+    /// Holds the indices of the spheres overlapping each cell.
+    cells: HashMap<(i64, i64, i64), Vec<usize>>,
+
+    /// Holds the indices of the spheres overlapping too many cells to be inserted in them.
+    oversized: Vec<usize>,
+
+    /// Holds the pairs found by the last rebuild, sorted and without duplicates.
+    pairs: Vec<(usize, usize)>,
+}
+
+impl SpatialHash {
+    /// Constructor
+    /// The cell size must be strictly positive.
+    pub fn new(cell_size: f64) -> Self {
+        assert!(cell_size > 0.);
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+            oversized: Vec::new(),
+            pairs: Vec::new(),
+        }
+    }
+
+    /// The largest number of cells a sphere is inserted into. Bigger spheres are
+    /// paired with every other sphere instead.
+    pub const MAX_CELLS_PER_SPHERE: u64 = 64;
+
+    /// Returns the side length of a cell.
+    pub fn get_cell_size(&self) -> f64 {
+        self.cell_size
+    }
+
+    /// Returns the coordinates of the cell containing the given position.
+    fn cell_of(&self, position: Vector3) -> (i64, i64, i64) {
+        let cell = (position * (1. / self.cell_size)).floor();
+        (cell.x as i64, cell.y as i64, cell.z as i64)
+    }
+
+    /// Rehashes the spheres with the given centers and radii, and finds the potential pairs.
+    pub fn rebuild(&mut self, positions: &[Vector3], radii: &[f64]) {
+        assert_eq!(positions.len(), radii.len());
+        self.cells.clear();
+        self.oversized.clear();
+        self.pairs.clear();
+
+        let mut hashed = Vec::with_capacity(positions.len());
+        for (index, (&position, &radius)) in positions.iter().zip(radii).enumerate() {
+            // Non-finite spheres would saturate the cell coordinates and loop forever.
+            let finite = position.x.is_finite() && position.y.is_finite() && position.z.is_finite();
+            if !finite || !radius.is_finite() {
+                continue;
+            }
+            hashed.push(index);
+
+            let extent = Vector3::new(radius, radius, radius);
+            let min = self.cell_of(position - extent);
+            let max = self.cell_of(position + extent);
+
+            // Count the cells in wide integers, since the span of saturated
+            // coordinates doesn't fit in an i64.
+            let span = |min: i64, max: i64| (max as i128 - min as i128 + 1).max(0) as u128;
+            let cells = span(min.0, max.0)
+                .saturating_mul(span(min.1, max.1))
+                .saturating_mul(span(min.2, max.2));
+            if cells > Self::MAX_CELLS_PER_SPHERE as u128 {
+                self.oversized.push(index);
+                continue;
+            }
+
+            for x in min.0..=max.0 {
+                for y in min.1..=max.1 {
+                    for z in min.2..=max.2 {
+                        self.cells.entry((x, y, z)).or_default().push(index);
+                    }
+                }
+            }
+        }
+
+        for cell in self.cells.values() {
+            for (i, &first) in cell.iter().enumerate() {
+                for &second in &cell[i + 1..] {
+                    self.pairs.push((first.min(second), first.max(second)));
+                }
+            }
+        }
+        for &first in &self.oversized {
+            for &second in hashed.iter().filter(|&&second| second != first) {
+                self.pairs.push((first.min(second), first.max(second)));
+            }
+        }
+
+        // Spheres sharing several cells are found once per cell.
+        self.pairs.sort_unstable();
+        self.pairs.dedup();
+    }
+
+    /// Iterates over the pairs `(i, j)`, with `i < j`, of spheres sharing a cell.
+    pub fn potential_pairs(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.pairs.iter().copied()
+    }
+}
+
+/// A contact generator colliding particles with each other, treating them as spheres.
+/// Every pair of registered particles is checked, unless a broad phase is set.
+#[derive(Debug, Clone)]
+pub struct ParticleCollisionGenerator {
+    /// Holds the restitution of the contacts, used when no material table is set.
+    pub restitution: f64,
+
+    /// Holds the table the restitution is looked up in, if any.
+    materials: Option<Rc<MaterialTable>>,
+
+    /// Holds the particles that collide with each other.
+    particles: Vec<CollidableParticle>,
+
+    /// Holds the broad phase used to skip pairs that are far apart, if any.
+    broad_phase: Option<RefCell<SpatialHash>>,
+}
+
+impl ParticleCollisionGenerator {
+    /// The normal used when two particles are exactly on top of each other.
+    pub const COINCIDENT_NORMAL: Vector3 = Vector3::new(0., 1., 0.);
+
+    /// Constructor
+    pub fn new(restitution: f64) -> Self {
+        Self {
+            restitution,
+            materials: None,
+            particles: Vec::new(),
+            broad_phase: None,
+        }
+    }
+
+    /// Makes the generator only check the pairs found by a spatial hash with the given cell size.
+    pub fn set_broad_phase(&mut self, cell_size: f64) {
+        self.broad_phase = Some(RefCell::new(SpatialHash::new(cell_size)));
+    }
+
+    /// Makes the generator check every pair of particles again.
+    pub fn clear_broad_phase(&mut self) {
+        self.broad_phase = None;
+    }
+
+    /// Makes the restitution of the contacts come from the given table,
+    /// using the materials of the particles involved.
+    pub fn set_material_table(&mut self, materials: Rc<MaterialTable>) {
+        self.materials = Some(materials);
+    }
+
+    /// Registers the given particle with the given collision radius.
+    pub fn add_particle(&mut self, particle: ParticleHandle, radius: f64) {
+        self.particles.push(CollidableParticle { particle, radius });
+    }
+
+    /// Returns the particles that collide with each other.
+    pub fn get_particles(&self) -> &[CollidableParticle] {
+        &self.particles
+    }
+
+    /// Returns the contact between the two particles, if they overlap.
+    fn contact_between(
+        &self,
+        particles: &ParticleSet,
+        first: CollidableParticle,
+        second: CollidableParticle,
+    ) -> Option<ParticleContact> {
+        let offset = particles[first.particle].position - particles[second.particle].position;
+        let reach = first.radius + second.radius;
+        let distance_squared = offset.square_magnitude();
+        if distance_squared >= reach * reach {
+            return None;
+        }
+
+        // The normal points from the second particle to the first. Coincident
+        // centers have no direction, so fall back to a fixed one.
+        let distance = distance_squared.sqrt();
+        let normal = if distance > 0. {
+            offset * (1. / distance)
+        } else {
+            Self::COINCIDENT_NORMAL
+        };
+        let restitution = match &self.materials {
+            Some(materials) => materials.restitution(
+                particles[first.particle].material,
+                particles[second.particle].material,
+            ),
+            None => self.restitution,
+        };
+        Some(ParticleContact::new(
+            (first.particle, Some(second.particle)),
+            restitution,
+            normal,
+            reach - distance,
+        ))
+    }
+}
+
+impl ParticleContactGenerator for ParticleCollisionGenerator {
+    fn add_contacts(
+        &self,
+        particles: &ParticleSet,
+        contacts: &mut Vec<ParticleContact>,
+        limit: usize,
+    ) -> usize {
+        // Particles removed since they were registered are skipped.
+        let live: Vec<CollidableParticle> = self
+            .particles
+            .iter()
+            .copied()
+            .filter(|collidable| particles.contains(collidable.particle))
+            .collect();
+
+        let mut count = 0;
+        if let Some(broad_phase) = &self.broad_phase {
+            let positions: Vec<Vector3> = live
+                .iter()
+                .map(|collidable| particles[collidable.particle].position)
+                .collect();
+            let radii: Vec<f64> = live.iter().map(|collidable| collidable.radius).collect();
+
+            let mut broad_phase = broad_phase.borrow_mut();
+            broad_phase.rebuild(&positions, &radii);
+            for (i, j) in broad_phase.potential_pairs() {
+                if count >= limit {
+                    break;
+                }
+                let (first, second) = (live[i], live[j]);
+                if let Some(contact) = self.contact_between(particles, first, second) {
+                    contacts.push(contact);
+                    count += 1;
+                }
+            }
+            return count;
+        }
+
+        for (i, &first) in live.iter().enumerate() {
+            for &second in &live[i + 1..] {
+                if count >= limit {
+                    return count;
+                }
+                if let Some(contact) = self.contact_between(particles, first, second) {
+                    contacts.push(contact);
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+}
+
+/// A contact generator keeping particles inside a convex container made of planes,
+/// like a box. Each plane keeps particles on the side its normal points to.
+///
+/// A particle penetrating several planes at once (in a corner) gets one contact per
+/// plane, and the resolver sorts them out.
+#[derive(Debug, Clone)]
+pub struct ContainerContactGenerator {
+    /// Holds the walls of the container, with their normals pointing inwards.
+    pub planes: Vec<Plane>,
+
+    /// Holds the restitution of the contacts, used when no material table is set.
+    pub restitution: f64,
+
+    /// Holds the material of the walls.
+    pub material: MaterialId,
+
+    /// Holds the table the restitution is looked up in, if any.
+    materials: Option<Rc<MaterialTable>>,
+
+    /// Holds the particles kept inside the container.
+    particles: Vec<CollidableParticle>,
+}
+
+impl ContainerContactGenerator {
+    /// Constructor
+    pub fn new(planes: Vec<Plane>, restitution: f64) -> Self {
+        Self {
+            planes,
+            restitution,
+            material: MaterialId::DEFAULT,
+            materials: None,
+            particles: Vec::new(),
+        }
+    }
+
+    /// Constructs the axis-aligned box going from `min` to `max`.
+    pub fn aabb(min: Vector3, max: Vector3, restitution: f64) -> Self {
+        let planes = vec![
+            Plane::new(Vector3::new(1., 0., 0.), min.x),
+            Plane::new(Vector3::new(-1., 0., 0.), -max.x),
+            Plane::new(Vector3::new(0., 1., 0.), min.y),
+            Plane::new(Vector3::new(0., -1., 0.), -max.y),
+            Plane::new(Vector3::new(0., 0., 1.), min.z),
+            Plane::new(Vector3::new(0., 0., -1.), -max.z),
+        ];
+        Self::new(planes, restitution)
+    }
+
+    /// Makes the restitution of the contacts come from the given table, using the
+    /// materials of the walls and of each particle.
+    pub fn set_material_table(&mut self, materials: Rc<MaterialTable>) {
+        self.materials = Some(materials);
+    }
+
+    /// Registers the given particle with the given collision radius.
+    pub fn add_particle(&mut self, particle: ParticleHandle, radius: f64) {
+        self.particles.push(CollidableParticle { particle, radius });
+    }
+
+    /// Returns the particles kept inside the container.
+    pub fn get_particles(&self) -> &[CollidableParticle] {
+        &self.particles
+    }
+}
+
+impl ParticleContactGenerator for ContainerContactGenerator {
+    fn add_contacts(
+        &self,
+        particles: &ParticleSet,
+        contacts: &mut Vec<ParticleContact>,
+        limit: usize,
+    ) -> usize {
+        let mut count = 0;
+        for collidable in &self.particles {
+            // Particles removed since they were registered are skipped.
+            let particle = match particles.get(collidable.particle) {
+                Some(particle) => particle,
+                None => continue,
+            };
+            let restitution = match &self.materials {
+                Some(materials) => materials.restitution(particle.material, self.material),
+                None => self.restitution,
+            };
+            for plane in &self.planes {
+                if count >= limit {
+                    return count;
+                }
+                let distance = plane.signed_distance(particle.position);
+                if distance < collidable.radius {
+                    contacts.push(ParticleContact::new(
+                        (collidable.particle, None),
+                        restitution,
+                        plane.normal,
+                        collidable.radius - distance,
+                    ));
+                    count += 1;
+                }
+            }
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kellenth::materials::CombineRule;
+    use crate::kellenth::noise::Random;
+    use crate::kellenth::particle::Particle;
+
+    const ZERO: Vector3 = Vector3::new(0., 0., 0.);
+
+    /// Returns a particle with the given mass at rest at the given position.
+    fn particle_at(position: Vector3, mass: f64) -> Particle {
+        let mut particle = Particle::new(position, ZERO, ZERO, 1.);
+        particle.set_mass(mass);
+        particle
+    }
+
+    /// Returns the pairs of handles and penetrations of the contacts, sorted.
+    fn contact_summary(contacts: &[ParticleContact]) -> Vec<(ParticleHandle, ParticleHandle, f64)> {
+        let mut summary: Vec<_> = contacts
+            .iter()
+            .map(|contact| {
+                let (a, b) = (contact.particles.0, contact.particles.1.unwrap());
+                (a.min(b), a.max(b), contact.penetration)
+            })
+            .collect();
+        summary.sort_by_key(|&(a, b, _)| (a, b));
+        summary
+    }
+
+    #[test]
+    fn spatial_hash_finds_the_same_contacts_as_brute_force() {
+        let mut rng = Random::new(7);
+        for scene in 0..20 {
+            let mut particles = ParticleSet::new();
+            let mut brute = ParticleCollisionGenerator::new(0.5);
+            for i in 0..120 {
+                // Snap some particles onto cell boundaries, and make some much larger
+                // than a cell.
+                let mut position = rng.in_sphere(6.);
+                if i % 5 == 0 {
+                    position = position.round();
+                }
+                let radius = if i % 17 == 0 {
+                    rng.range(1.5, 4.)
+                } else {
+                    rng.range(0.1, 0.6)
+                };
+                let handle = particles.insert(particle_at(position, 1.));
+                brute.add_particle(handle, radius);
+            }
+            let mut hashed = brute.clone();
+            hashed.set_broad_phase(if scene % 2 == 0 { 1. } else { 0.3 });
+
+            let (mut expected, mut found) = (Vec::new(), Vec::new());
+            brute.add_contacts(&particles, &mut expected, usize::MAX);
+            hashed.add_contacts(&particles, &mut found, usize::MAX);
+            assert!(!expected.is_empty());
+            assert_eq!(contact_summary(&found), contact_summary(&expected));
+        }
+    }
+
+    #[test]
+    fn spatial_hash_skips_non_finite_spheres() {
+        let mut hash = SpatialHash::new(1.);
+        let positions = [
+            ZERO,
+            Vector3::new(0.5, 0., 0.),
+            Vector3::new(f64::NAN, 0., 0.),
+            Vector3::new(0., f64::INFINITY, 0.),
+            Vector3::new(0., 0., 0.2),
+        ];
+        let radii = [0.5, 0.5, 0.5, 0.5, f64::INFINITY];
+        hash.rebuild(&positions, &radii);
+        assert_eq!(hash.potential_pairs().collect::<Vec<_>>(), vec![(0, 1)]);
+    }
+
+    #[test]
+    fn spatial_hash_pairs_oversized_spheres_with_everything() {
+        let mut hash = SpatialHash::new(0.001);
+        let positions = [
+            ZERO,
+            Vector3::new(50., 0., 0.),
+            Vector3::new(-1e300, 0., 0.),
+        ];
+        let radii = [1e6, 0.0001, 1e300];
+        hash.rebuild(&positions, &radii);
+        assert_eq!(
+            hash.potential_pairs().collect::<Vec<_>>(),
+            vec![(0, 1), (0, 2), (1, 2)]
+        );
+    }
+
+    /// Returns a set holding two unit masses one apart on the x axis, closing in at unit speed,
+    /// and the contact between them.
+    fn head_on(restitution: f64) -> (ParticleSet, ParticleContact) {
+        let mut particles = ParticleSet::new();
+        let mut left = particle_at(ZERO, 1.);
+        left.velocity = Vector3::new(1., 0., 0.);
+        let mut right = particle_at(Vector3::new(0.9, 0., 0.), 1.);
+        right.velocity = Vector3::new(-1., 0., 0.);
+        let left = particles.insert(left);
+        let right = particles.insert(right);
+        let normal = Vector3::new(-1., 0., 0.);
+        let contact = ParticleContact::new((left, Some(right)), restitution, normal, 0.1);
+        (particles, contact)
+    }
+
+    #[test]
+    fn elastic_head_on_collision_swaps_velocities() {
+        let (mut particles, mut contact) = head_on(1.);
+        assert_eq!(contact.calculate_separating_velocity(&particles), -2.);
+        contact.resolve(&mut particles, 0.01);
+        let left = particles[contact.particles.0];
+        let right = particles[contact.particles.1.unwrap()];
+        assert_eq!(left.velocity.x, -1.);
+        assert_eq!(right.velocity.x, 1.);
+        assert_eq!(contact.get_impulse(), 2.);
+        // Each particle moved half of the penetration.
+        assert!((right.position.x - left.position.x - 1.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn inelastic_head_on_collision_stops_relative_motion() {
+        let (mut particles, mut contact) = head_on(0.);
+        contact.resolve(&mut particles, 0.01);
+        assert_eq!(contact.calculate_separating_velocity(&particles), 0.);
+        assert_eq!(particles[contact.particles.0].velocity.x, 0.);
+    }
+
+    #[test]
+    fn particle_resting_on_a_static_one_does_not_sink() {
+        let gravity = Vector3::new(0., -9.81, 0.);
+        let mut particles = ParticleSet::new();
+        let ground = particles.insert(Particle::new(ZERO, ZERO, ZERO, 1.));
+        let mut ball = particle_at(Vector3::new(0., 1., 0.), 1.);
+        ball.acceleration = gravity;
+        let ball = particles.insert(ball);
+
+        let duration = 0.01;
+        for _ in 0..1000 {
+            particles[ball].integrate(duration);
+            let distance = particles[ball].position.y;
+            if distance < 1. {
+                let normal = Vector3::new(0., 1., 0.);
+                let mut contact =
+                    ParticleContact::new((ball, Some(ground)), 0.8, normal, 1. - distance);
+                contact.resolve(&mut particles, duration);
+            }
+            assert!(particles[ball].position.y > 1. - 1e-3);
+            // Without the resting correction, the ball would bounce off every frame.
+            assert!(particles[ball].velocity.y.abs() < 1e-9);
+        }
+        assert_eq!(particles[ground].position.magnitude(), 0.);
+    }
+
+    /// Returns a set holding three unit masses stacked on an immovable one, each sunk
+    /// 0.1 into the one below, and the contacts between them, bottom first.
+    fn sunken_stack() -> (ParticleSet, Vec<ParticleHandle>, Vec<ParticleContact>) {
+        let mut particles = ParticleSet::new();
+        let mut handles = vec![particles.insert(Particle::new(ZERO, ZERO, ZERO, 1.))];
+        for level in 1..4 {
+            let position = Vector3::new(0., 0.9 * level as f64, 0.);
+            handles.push(particles.insert(particle_at(position, 1.)));
+        }
+        let up = Vector3::new(0., 1., 0.);
+        let contacts = handles
+            .windows(2)
+            .map(|pair| ParticleContact::new((pair[1], Some(pair[0])), 0., up, 0.1))
+            .collect();
+        (particles, handles, contacts)
+    }
+
+    #[test]
+    fn resolver_untangles_a_stack_over_several_iterations() {
+        let (mut particles, handles, mut contacts) = sunken_stack();
+        let mut resolver = ParticleContactResolver::new(100);
+        resolver.resolve_contacts(&mut contacts, &mut particles, 0.01);
+        assert!(resolver.get_iterations_used() > 1);
+        for pair in handles.windows(2) {
+            let gap = particles[pair[1]].position.y - particles[pair[0]].position.y;
+            assert!(gap > 1. - 1e-4);
+        }
+        assert!(contacts.iter().all(|contact| contact.penetration < 1e-4));
+
+        // Each resolution pushes into the neighbors, so one iteration is not enough.
+        let (mut particles, _, mut contacts) = sunken_stack();
+        resolver.set_iterations(1);
+        resolver.resolve_contacts(&mut contacts, &mut particles, 0.01);
+        assert_eq!(resolver.get_iterations_used(), 1);
+        assert!(contacts.iter().any(|contact| contact.penetration > 0.05));
+    }
+
+    /// A contact generator that always produces the given number of contacts on its particle,
+    /// ignoring the limit.
+    struct Burst(usize, ParticleHandle);
+
+    impl ParticleContactGenerator for Burst {
+        fn add_contacts(
+            &self,
+            _particles: &ParticleSet,
+            contacts: &mut Vec<ParticleContact>,
+            _limit: usize,
+        ) -> usize {
+            let up = Vector3::new(0., 1., 0.);
+            contacts.extend((0..self.0).map(|_| ParticleContact::new((self.1, None), 0., up, 0.)));
+            self.0
+        }
+    }
+
+    #[test]
+    fn contact_generation_respects_the_limit() {
+        let mut particles = ParticleSet::new();
+        let a = particles.insert(particle_at(ZERO, 1.));
+        let b = particles.insert(particle_at(ZERO, 1.));
+        let generators: Vec<Box<dyn ParticleContactGenerator>> = vec![
+            Box::new(Burst(3, a)),
+            Box::new(Burst(4, b)),
+            Box::new(ParticleContactClosure::new(
+                move |_, contacts: &mut Vec<_>, _| {
+                    Burst(5, a).add_contacts(&ParticleSet::new(), contacts, 0)
+                },
+            )),
+        ];
+
+        let mut contacts = Vec::new();
+        assert_eq!(
+            generate_contacts(&generators, &particles, &mut contacts, 100),
+            12
+        );
+        let from_b = contacts.iter().filter(|c| c.particles.0 == b).count();
+        assert_eq!(from_b, 4);
+
+        // Appending keeps the contacts already there, and the limit cuts into the second generator.
+        assert_eq!(
+            generate_contacts(&generators, &particles, &mut contacts, 5),
+            5
+        );
+        assert_eq!(contacts.len(), 17);
+        assert_eq!(
+            contacts[12..].iter().filter(|c| c.particles.0 == b).count(),
+            2
+        );
+        assert_eq!(
+            generate_contacts(&generators, &particles, &mut contacts, 0),
+            0
+        );
+
+        // The closure adapter enforces the limit itself.
+        let mut contacts = Vec::new();
+        assert_eq!(generators[2].add_contacts(&particles, &mut contacts, 2), 2);
+        assert_eq!(contacts.len(), 2);
+    }
+
+    /// Integrates the particles one step, then generates and resolves the contacts
+    /// of the given generator.
+    fn contact_step(
+        generator: &dyn ParticleContactGenerator,
+        particles: &mut ParticleSet,
+        resolver: &mut ParticleContactResolver,
+        duration: f64,
+    ) {
+        for particle in particles.as_mut_slice() {
+            particle.integrate(duration);
+        }
+        let mut contacts = Vec::new();
+        generator.add_contacts(particles, &mut contacts, usize::MAX);
+        resolver.set_iterations(contacts.len() * 2);
+        resolver.resolve_contacts(&mut contacts, particles, duration);
+    }
+
+    #[test]
+    fn particles_dropped_on_a_tilted_plane_slide_down_it() {
+        let plane = Plane::new(Vector3::new(1., 2., 0.5), 1.);
+        let mut ground = GroundContactGenerator::new(plane, 0., 0.25);
+        let mut particles = ParticleSet::new();
+        let gravity = Vector3::new(0., -9.81, 0.);
+        for i in 0..3 {
+            for j in 0..3 {
+                let offset = Vector3::new(i as f64 - 1., 0., j as f64 - 1.);
+                let mut particle = particle_at(offset + plane.normal * (2. + i as f64), 1.);
+                particle.acceleration = gravity;
+                ground.add_particle(particles.insert(particle));
+            }
+        }
+        let start: Vec<Vector3> = particles.as_slice().iter().map(|p| p.position).collect();
+
+        let mut resolver = ParticleContactResolver::new(0);
+        for _ in 0..200 {
+            contact_step(&ground, &mut particles, &mut resolver, 0.01);
+            for particle in particles.as_slice() {
+                assert!(plane.signed_distance(particle.position) > 0.25 - 0.01);
+            }
+        }
+
+        // Frictionless contacts only take away the velocity into the plane, so every
+        // particle picks up the part of gravity along the slope, and nothing else.
+        let downhill = gravity - plane.normal * (gravity * plane.normal);
+        for (particle, start) in particles.as_slice().iter().zip(start) {
+            assert!((plane.signed_distance(particle.position) - 0.25).abs() < 1e-9);
+            assert!((particle.velocity * plane.normal).abs() < 1e-9);
+            assert!((particle.velocity - downhill * 2.).chebyshev_distance(&ZERO) < 1e-9);
+
+            let slid = particle.position - start;
+            let along = slid - plane.normal * (slid * plane.normal);
+            assert!(along * downhill.get_normalized() > 0.99 * along.magnitude());
+            assert!(along.magnitude() > 5.);
+        }
+    }
+
+    #[test]
+    fn ground_contacts_use_the_plane_normal_and_overlap() {
+        let plane = Plane::from_point_normal(Vector3::new(0., 1., 0.), Vector3::new(0., 3., 4.));
+        let mut ground = GroundContactGenerator::new(plane, 0.5, 1.);
+        let mut particles = ParticleSet::new();
+        let touching = particles.insert(particle_at(Vector3::new(7., 1.3, 0.4), 1.));
+        let clear = particles.insert(particle_at(Vector3::new(0., 4., 4.), 1.));
+        ground.add_particle(touching);
+        ground.add_particle(clear);
+
+        let mut contacts = Vec::new();
+        assert_eq!(ground.add_contacts(&particles, &mut contacts, 16), 1);
+        let contact = contacts[0];
+        assert_eq!(contact.particles, (touching, None));
+        assert!(
+            contact
+                .contact_normal
+                .chebyshev_distance(&Vector3::new(0., 0.6, 0.8))
+                < 1e-15
+        );
+        // The center is 0.6·0.3 + 0.8·0.4 = 0.5 above the plane.
+        assert!((contact.penetration - 0.5).abs() < 1e-12);
+        assert_eq!(contact.restitution, 0.5);
+    }
+
+    #[test]
+    fn colliding_particles_bounce_off_each_other() {
+        let mut particles = ParticleSet::new();
+        let mut left = particle_at(Vector3::new(-1., 0., 0.), 1.);
+        left.velocity = Vector3::new(2., 0., 0.);
+        let mut right = particle_at(Vector3::new(1., 0.1, 0.), 1.);
+        right.velocity = Vector3::new(-2., 0., 0.);
+        let left = particles.insert(left);
+        let right = particles.insert(right);
+        let mut collisions = ParticleCollisionGenerator::new(0.8);
+        collisions.add_particle(left, 0.5);
+        collisions.add_particle(right, 0.5);
+
+        let mut resolver = ParticleContactResolver::new(0);
+        let mut bounced = 0;
+        for _ in 0..100 {
+            let before = particles[left].velocity.x;
+            contact_step(&collisions, &mut particles, &mut resolver, 0.01);
+            if particles[left].velocity.x < 0. && before > 0. {
+                bounced += 1;
+            }
+        }
+        assert_eq!(bounced, 1);
+        let momentum = particles[left].velocity + particles[right].velocity;
+        assert!(momentum.magnitude() < 1e-12);
+        // The glancing blow sends them apart a little slower than they came in.
+        let speed = particles[left].speed();
+        assert!(speed < 2. && speed > 1.5, "{speed}");
+        let gap = (particles[right].position - particles[left].position).magnitude();
+        assert!(gap > 1.);
+    }
+
+    #[test]
+    fn coincident_particles_get_a_fixed_normal() {
+        let mut particles = ParticleSet::new();
+        let a = particles.insert(particle_at(ZERO, 1.));
+        let b = particles.insert(particle_at(ZERO, 1.));
+        let mut collisions = ParticleCollisionGenerator::new(0.);
+        collisions.add_particle(a, 0.5);
+        collisions.add_particle(b, 0.25);
+        let mut contacts = Vec::new();
+        assert_eq!(collisions.add_contacts(&particles, &mut contacts, 16), 1);
+        let contact = contacts[0];
+        let normal = ParticleCollisionGenerator::COINCIDENT_NORMAL;
+        assert_eq!(contact.contact_normal.manhattan_distance(&normal), 0.);
+        assert_eq!(contact.penetration, 0.75);
+    }
+
+    #[test]
+    fn cluster_dropped_in_a_box_piles_up_without_overlapping() {
+        let radius = 0.25;
+        let mut particles = ParticleSet::new();
+        let mut collisions = ParticleCollisionGenerator::new(0.2);
+        let mut walls: Vec<GroundContactGenerator> = [
+            Plane::new(Vector3::new(0., 1., 0.), 0.),
+            Plane::new(Vector3::new(1., 0., 0.), -0.6),
+            Plane::new(Vector3::new(-1., 0., 0.), -0.6),
+            Plane::new(Vector3::new(0., 0., 1.), -0.6),
+            Plane::new(Vector3::new(0., 0., -1.), -0.6),
+        ]
+        .into_iter()
+        .map(|plane| GroundContactGenerator::new(plane, 0.2, radius))
+        .collect();
+        let mut rng = Random::new(11);
+        for i in 0..8 {
+            let position = Vector3::new(
+                rng.range(-0.3, 0.3),
+                1. + 0.6 * i as f64,
+                rng.range(-0.3, 0.3),
+            );
+            let mut particle = particle_at(position, 1.);
+            particle.acceleration = Vector3::new(0., -9.81, 0.);
+            particle.damping = 0.5;
+            let handle = particles.insert(particle);
+            collisions.add_particle(handle, radius);
+            walls.iter_mut().for_each(|wall| wall.add_particle(handle));
+        }
+        let mut generators: Vec<Box<dyn ParticleContactGenerator>> = vec![Box::new(collisions)];
+        generators.extend(walls.into_iter().map(|wall| Box::new(wall) as Box<_>));
+
+        let mut resolver = ParticleContactResolver::new(0);
+        for _ in 0..1000 {
+            for particle in particles.as_mut_slice() {
+                particle.integrate(0.01);
+            }
+            let mut contacts = Vec::new();
+            generate_contacts(&generators, &particles, &mut contacts, 256);
+            resolver.set_iterations(contacts.len() * 2);
+            resolver.resolve_contacts(&mut contacts, &mut particles, 0.01);
+        }
+
+        let settled = particles.as_slice();
+        for (i, a) in settled.iter().enumerate() {
+            assert!(a.position.y > radius - 0.01);
+            assert!(a.position.x.abs() < 0.6 - radius + 0.01);
+            // Stacked particles keep jostling a little, but nothing bounces around.
+            assert!(a.speed() < 0.5);
+            for b in &settled[i + 1..] {
+                let distance = (a.position - b.position).magnitude();
+                assert!(distance > 2. * radius - 0.02, "{distance}");
+            }
+        }
+    }
+
+    #[test]
+    fn generators_take_restitution_from_the_material_table() {
+        let (ball, floor) = (MaterialId(1), MaterialId(2));
+        let mut table = MaterialTable::new(0.1, CombineRule::Average);
+        table.set_pair_restitution(ball, floor, 0.9);
+        table.set_pair_restitution(ball, ball, 0.6);
+        let table = Rc::new(table);
+
+        let mut particles = ParticleSet::new();
+        let mut a = particle_at(ZERO, 1.);
+        a.material = ball;
+        let mut b = a;
+        b.position = Vector3::new(0.5, 0., 0.);
+        let (a, b) = (particles.insert(a), particles.insert(b));
+
+        let mut ground =
+            GroundContactGenerator::new(Plane::new(Vector3::new(0., 1., 0.), 0.), 0., 1.);
+        ground.material = floor;
+        ground.set_material_table(table.clone());
+        ground.add_particle(a);
+        let mut collisions = ParticleCollisionGenerator::new(0.);
+        collisions.set_material_table(table.clone());
+        collisions.add_particle(a, 0.5);
+        collisions.add_particle(b, 0.5);
+        let mut container =
+            ContainerContactGenerator::new(vec![Plane::new(Vector3::new(-1., 0., 0.), -0.75)], 0.);
+        container.material = floor;
+        container.set_material_table(table);
+        container.add_particle(b, 0.5);
+
+        let mut contacts = Vec::new();
+        ground.add_contacts(&particles, &mut contacts, 16);
+        collisions.add_contacts(&particles, &mut contacts, 16);
+        container.add_contacts(&particles, &mut contacts, 16);
+        let restitutions: Vec<f64> = contacts.iter().map(|c| c.restitution).collect();
+        assert_eq!(restitutions, [0.9, 0.6, 0.9]);
+
+        // Walls of a material without a registered pair fall back to the table default.
+        container.material = MaterialId(3);
+        let mut contacts = Vec::new();
+        container.add_contacts(&particles, &mut contacts, 16);
+        assert_eq!(contacts[0].restitution, 0.1);
+    }
+
+    #[test]
+    fn container_corner_gets_a_contact_per_plane() {
+        let mut container = ContainerContactGenerator::aabb(ZERO, Vector3::new(2., 2., 2.), 0.5);
+        assert_eq!(container.planes.len(), 6);
+        let mut particles = ParticleSet::new();
+        let corner = particles.insert(particle_at(Vector3::new(0.1, 1.95, 0.2), 1.));
+        let middle = particles.insert(particle_at(Vector3::new(1., 1., 1.), 1.));
+        container.add_particle(corner, 0.25);
+        container.add_particle(middle, 0.25);
+
+        let mut contacts = Vec::new();
+        assert_eq!(container.add_contacts(&particles, &mut contacts, 16), 3);
+        let mut found: Vec<(f64, f64)> = contacts
+            .iter()
+            .map(|c| (c.contact_normal.component_sum(), c.penetration))
+            .collect();
+        found.sort_by(|a, b| a.1.total_cmp(&b.1));
+        let expected = [(1., 0.05), (1., 0.15), (-1., 0.2)];
+        for ((normal, penetration), (expected_normal, expected_penetration)) in
+            found.into_iter().zip(expected)
+        {
+            assert_eq!(normal, expected_normal);
+            assert!((penetration - expected_penetration).abs() < 1e-12);
+        }
+        assert!(contacts.iter().all(|c| c.particles == (corner, None)));
+    }
+
+    #[test]
+    fn bouncing_particles_never_escape_the_container() {
+        let (min, max) = (Vector3::new(-1., 0., -1.), Vector3::new(1., 2., 1.));
+        let mut container = ContainerContactGenerator::aabb(min, max, 1.);
+        let mut particles = ParticleSet::new();
+        let mut rng = Random::new(5);
+        for _ in 0..50 {
+            let mut particle = particle_at(Vector3::new(0., 1., 0.) + rng.in_sphere(0.5), 1.);
+            particle.velocity = rng.unit_vector() * rng.range(5., 30.);
+            particle.acceleration = Vector3::new(0., -9.81, 0.);
+            container.add_particle(particles.insert(particle), 0.1);
+        }
+
+        let mut resolver = ParticleContactResolver::new(0);
+        for _ in 0..3000 {
+            contact_step(&container, &mut particles, &mut resolver, 0.005);
+            for particle in particles.as_slice() {
+                let position = particle.position;
+                assert!(position.x > -1. && position.y > 0. && position.z > -1.);
+                assert!(position.x < 1. && position.y < 2. && position.z < 1.);
+            }
+        }
+    }
+
+    #[test]
+    fn batch_settles_a_tall_stack_without_sinking() {
+        let mut particles = ParticleSet::new();
+        let mut collisions = ParticleCollisionGenerator::new(0.);
+        let base = particles.insert(Particle::new(ZERO, ZERO, ZERO, 1.));
+        collisions.add_particle(base, 0.5);
+        let mut stack = Vec::new();
+        for level in 1..=8 {
+            let mut particle = particle_at(Vector3::new(0., level as f64, 0.), 1.);
+            particle.acceleration = Vector3::new(0., -9.81, 0.);
+            let handle = particles.insert(particle);
+            collisions.add_particle(handle, 0.5);
+            stack.push(handle);
+        }
+
+        let mut batch = ContactBatch::default();
+        let mut lowest_top = f64::INFINITY;
+        for step in 0..600 {
+            for particle in particles.as_mut_slice() {
+                particle.integrate(0.01);
+            }
+            let mut contacts = Vec::new();
+            collisions.add_contacts(&particles, &mut contacts, usize::MAX);
+            batch.resolve_contacts(&mut contacts, &mut particles, 0.01);
+            let top = particles[stack[7]].position.y;
+            lowest_top = lowest_top.min(top);
+            if step >= 300 {
+                // Settled: the stack barely moves from one step to the next.
+                assert!(particles
+                    .as_slice()
+                    .iter()
+                    .all(|particle| particle.speed() < 0.05));
+            }
+        }
+
+        // Every gap keeps within the slop, so the top never sinks into the ones below.
+        assert!(lowest_top > 8. - 8. * batch.slop - 0.05, "{lowest_top}");
+        let mut below = particles[base].position.y;
+        for &handle in &stack {
+            let y = particles[handle].position.y;
+            assert!(y - below > 1. - batch.slop - 1e-3);
+            assert!(particles[handle].position.x.abs() < 1e-9);
+            below = y;
+        }
+    }
+}
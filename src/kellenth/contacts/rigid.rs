@@ -0,0 +1,495 @@
+//! Holds the contacts between rigid bodies, and the means to resolve them with friction.
+
+use crate::kellenth::core::*;
+use crate::kellenth::rigid_body::{BodyHandle, RigidBodySet};
+
+/// Represents two rigid bodies touching at a point, or a body touching the scenery.
+///
+/// Resolution works with the velocities of the two surfaces at the contact point, spin
+/// included, as given by `velocity_at_point`. A body resting on a moving kinematic body,
+/// like a crate on a platform, is dragged along by the friction until it moves with it.
+///
+/// Bodies are referred to by handle; resolving a contact whose bodies are gone panics.
+#[derive(Debug, Clone, Copy)]
+pub struct Contact {
+    /// Holds the bodies involved in the contact.
+    /// The second one is `None` for contacts with the scenery.
+    pub bodies: (BodyHandle, Option<BodyHandle>),
+
+    /// Holds the point of contact, in world space.
+    pub contact_point: Vector3,
+
+    /// Holds the direction of the contact in world space,
+    /// from the point of view of the first body.
+    pub contact_normal: Vector3,
+
+    /// Holds the depth of the penetration at the contact.
+    pub penetration: f64,
+
+    /// Holds the normal restitution coefficient at the contact.
+    pub restitution: f64,
+
+    /// Holds the Coulomb friction coefficient at the contact.
+    pub friction: f64,
+
+    /// Holds the impulse applied along the normal by the last resolution.
+    normal_impulse: f64,
+
+    /// Holds the friction impulse, across the normal, applied by the last resolution.
+    friction_impulse: Vector3,
+}
+
+impl Contact {
+    /// Constructor
+    pub fn new(
+        bodies: (BodyHandle, Option<BodyHandle>),
+        contact_point: Vector3,
+        contact_normal: Vector3,
+        penetration: f64,
+        restitution: f64,
+        friction: f64,
+    ) -> Self {
+        Self {
+            bodies,
+            contact_point,
+            contact_normal,
+            penetration,
+            restitution,
+            friction,
+            normal_impulse: 0.,
+            friction_impulse: Vector3::new(0., 0., 0.),
+        }
+    }
+
+    /// Returns the impulse applied along the normal by the last resolution.
+    /// The first body received this impulse, and the second one the opposite.
+    pub fn get_normal_impulse(&self) -> f64 {
+        self.normal_impulse
+    }
+
+    /// Returns the friction impulse applied to the first body by the last resolution.
+    pub fn get_friction_impulse(&self) -> Vector3 {
+        self.friction_impulse
+    }
+
+    /// Returns the velocity of the surface of the first body at the contact point,
+    /// relative to the surface of the second one.
+    pub fn relative_velocity(&self, bodies: &RigidBodySet) -> Vector3 {
+        let mut relative_velocity = bodies[self.bodies.0].velocity_at_point(self.contact_point);
+        if let Some(other) = self.bodies.1 {
+            relative_velocity -= bodies[other].velocity_at_point(self.contact_point);
+        }
+        relative_velocity
+    }
+
+    /// Returns the separating velocity at this contact.
+    /// Negative values mean the bodies are closing in on each other.
+    pub fn calculate_separating_velocity(&self, bodies: &RigidBodySet) -> f64 {
+        self.relative_velocity(bodies) * self.contact_normal
+    }
+
+    /// Returns the change in relative velocity at the contact point, along the given
+    /// direction, caused by a unit impulse along it: the inverse of the effective mass.
+    fn inverse_effective_mass(&self, bodies: &RigidBodySet, direction: Vector3) -> f64 {
+        let handles = std::iter::once(self.bodies.0).chain(self.bodies.1);
+        handles
+            .map(|handle| {
+                let body = &bodies[handle];
+                if !body.has_finite_mass() {
+                    return 0.;
+                }
+                let arm = self.contact_point - body.get_center_of_mass_world();
+                let spin = body.get_inverse_inertia_tensor_world() * (arm % direction);
+                body.get_inverse_mass() + (spin % arm) * direction
+            })
+            .sum()
+    }
+
+    /// Returns the separating velocity the restitution asks for, given the current one.
+    /// Like for particles, the velocity built up by the acceleration over the last step
+    /// isn't bounced back, so resting contacts don't jitter.
+    fn target_separating_velocity(&self, bodies: &RigidBodySet, duration: f64) -> f64 {
+        let separating_velocity = self.calculate_separating_velocity(bodies);
+        if separating_velocity > 0. {
+            return 0.;
+        }
+
+        let mut acceleration_caused_velocity = bodies[self.bodies.0].get_last_frame_acceleration();
+        if let Some(other) = self.bodies.1 {
+            acceleration_caused_velocity -= bodies[other].get_last_frame_acceleration();
+        }
+        let acceleration_caused_separating_velocity =
+            (acceleration_caused_velocity * self.contact_normal * duration).min(0.);
+
+        let target = -separating_velocity * self.restitution
+            + self.restitution * acceleration_caused_separating_velocity;
+        target.max(0.)
+    }
+
+    /// Applies the given impulse at the contact point to the first body,
+    /// and the opposite one to the second.
+    fn apply_impulse(&self, bodies: &mut RigidBodySet, impulse: Vector3) {
+        bodies[self.bodies.0].apply_impulse_at_point(impulse, self.contact_point);
+        if let Some(other) = self.bodies.1 {
+            bodies[other].apply_impulse_at_point(impulse * -1., self.contact_point);
+        }
+    }
+
+    /// Applies the impulse along the normal that brings the separating velocity to the
+    /// target, keeping the total impulse pushing. Returns true if any impulse was applied.
+    fn resolve_normal(&mut self, bodies: &mut RigidBodySet, target: f64) -> bool {
+        let inverse_effective_mass = self.inverse_effective_mass(bodies, self.contact_normal);
+        if inverse_effective_mass <= 0. {
+            return false;
+        }
+
+        // Clamp the total impulse rather than this one, so a contact can take back
+        // what it applied earlier, but never ends up pulling.
+        let separating_velocity = self.calculate_separating_velocity(bodies);
+        let impulse = (target - separating_velocity) / inverse_effective_mass;
+        let total = (self.normal_impulse + impulse).max(0.);
+        let impulse = total - self.normal_impulse;
+        self.normal_impulse = total;
+        if impulse == 0. {
+            return false;
+        }
+        self.apply_impulse(bodies, self.contact_normal * impulse);
+        true
+    }
+
+    /// Applies the friction impulse that stops the surfaces sliding over each other,
+    /// keeping the total within the friction cone of the normal impulse.
+    /// Returns true if any impulse was applied.
+    fn resolve_friction(&mut self, bodies: &mut RigidBodySet) -> bool {
+        let relative_velocity = self.relative_velocity(bodies);
+        let sliding =
+            relative_velocity - self.contact_normal * (relative_velocity * self.contact_normal);
+        let speed = sliding.magnitude();
+        if speed == 0. {
+            return false;
+        }
+        let direction = sliding * (1. / speed);
+        let inverse_effective_mass = self.inverse_effective_mass(bodies, direction);
+        if inverse_effective_mass <= 0. {
+            return false;
+        }
+
+        let mut total = self.friction_impulse - direction * (speed / inverse_effective_mass);
+        let limit = self.friction * self.normal_impulse;
+        let magnitude = total.magnitude();
+        if magnitude > limit {
+            total *= limit / magnitude;
+        }
+        let impulse = total - self.friction_impulse;
+        self.friction_impulse = total;
+        if impulse.square_magnitude() == 0. {
+            return false;
+        }
+        self.apply_impulse(bodies, impulse);
+        true
+    }
+
+    /// Returns the inverse mass of the bodies involved, added together.
+    fn total_inverse_mass(&self, bodies: &RigidBodySet) -> f64 {
+        let mut total = bodies[self.bodies.0].get_inverse_mass();
+        if let Some(other) = self.bodies.1 {
+            total += bodies[other].get_inverse_mass();
+        }
+        total
+    }
+
+    /// Moves the bodies apart along the normal until they no longer interpenetrate, each
+    /// in proportion to its inverse mass, without turning them. Returns how much each moved.
+    fn move_apart(&self, bodies: &mut RigidBodySet) -> [Vector3; 2] {
+        let zero = Vector3::new(0., 0., 0.);
+        let total_inverse_mass = self.total_inverse_mass(bodies);
+        if self.penetration <= 0. || total_inverse_mass <= 0. {
+            return [zero, zero];
+        }
+
+        let move_per_inverse_mass = self.contact_normal * (self.penetration / total_inverse_mass);
+        let first = &mut bodies[self.bodies.0];
+        let mut movement = [move_per_inverse_mass * first.get_inverse_mass(), zero];
+        first.position += movement[0];
+        first.calculate_derived_data();
+        if let Some(other) = self.bodies.1 {
+            let second = &mut bodies[other];
+            movement[1] = move_per_inverse_mass * -second.get_inverse_mass();
+            second.position += movement[1];
+            second.calculate_derived_data();
+        }
+        movement
+    }
+}
+
+/// The contact resolution routine for rigid body contacts.
+/// One resolver instance can be shared for the whole simulation.
+///
+/// Velocities are resolved first, sweeping over every contact, normal then friction,
+/// up to `iterations` times, keeping the total normal impulse of each contact pushing
+/// and its total friction impulse within the friction cone. Then the deepest contact
+/// is moved apart, one at a time, updating the penetration of the contacts sharing a
+/// body as it moves, up to `position_iterations` times.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ContactResolver {
+    /// Holds the maximum number of velocity sweeps over the contacts.
+    iterations: usize,
+
+    /// Holds the number of velocity sweeps actually used by the last resolution.
+    iterations_used: usize,
+
+    /// Holds the maximum number of contacts moved apart.
+    position_iterations: usize,
+}
+
+impl ContactResolver {
+    /// Constructor
+    /// As many contacts can be moved apart as there are velocity sweeps, until set otherwise.
+    pub fn new(iterations: usize) -> Self {
+        Self {
+            iterations,
+            iterations_used: 0,
+            position_iterations: iterations,
+        }
+    }
+
+    /// Sets the maximum number of velocity sweeps over the contacts.
+    pub fn set_iterations(&mut self, iterations: usize) {
+        self.iterations = iterations;
+    }
+
+    /// Returns the maximum number of velocity sweeps over the contacts.
+    pub fn get_iterations(&self) -> usize {
+        self.iterations
+    }
+
+    /// Sets the maximum number of contacts moved apart.
+    pub fn set_position_iterations(&mut self, position_iterations: usize) {
+        self.position_iterations = position_iterations;
+    }
+
+    /// Returns the maximum number of contacts moved apart.
+    pub fn get_position_iterations(&self) -> usize {
+        self.position_iterations
+    }
+
+    /// Returns the number of velocity sweeps used by the last call to `resolve_contacts`.
+    pub fn get_iterations_used(&self) -> usize {
+        self.iterations_used
+    }
+
+    /// Resolves a set of rigid body contacts for both velocity and penetration.
+    /// Stops sweeping early once a sweep applies no impulse.
+    pub fn resolve_contacts(
+        &mut self,
+        contacts: &mut [Contact],
+        bodies: &mut RigidBodySet,
+        duration: f64,
+    ) {
+        // Every contact aims for the separating velocity its restitution asks for at the start.
+        let targets: Vec<f64> = contacts
+            .iter()
+            .map(|contact| contact.target_separating_velocity(bodies, duration))
+            .collect();
+        for contact in contacts.iter_mut() {
+            contact.normal_impulse = 0.;
+            contact.friction_impulse = Vector3::new(0., 0., 0.);
+        }
+
+        self.iterations_used = 0;
+        while self.iterations_used < self.iterations {
+            let mut applied_any = false;
+            for (contact, &target) in contacts.iter_mut().zip(&targets) {
+                applied_any |= contact.resolve_normal(bodies, target);
+                applied_any |= contact.resolve_friction(bodies);
+            }
+            self.iterations_used += 1;
+            if !applied_any {
+                break;
+            }
+        }
+
+        for _ in 0..self.position_iterations {
+            // Find the deepest contact that can be moved apart.
+            let mut max_penetration = 0.;
+            let mut max_index = None;
+            for (index, contact) in contacts.iter().enumerate() {
+                if contact.penetration > max_penetration && contact.total_inverse_mass(bodies) > 0.
+                {
+                    max_penetration = contact.penetration;
+                    max_index = Some(index);
+                }
+            }
+            let max_index = match max_index {
+                Some(max_index) => max_index,
+                None => break,
+            };
+
+            // Move it apart, and update the penetrations of all the contacts sharing a body.
+            let movement = contacts[max_index].move_apart(bodies);
+            let resolved = contacts[max_index].bodies;
+            let moved = |body: BodyHandle| {
+                if body == resolved.0 {
+                    Some(movement[0])
+                } else if Some(body) == resolved.1 {
+                    Some(movement[1])
+                } else {
+                    None
+                }
+            };
+            for contact in contacts.iter_mut() {
+                if let Some(movement) = moved(contact.bodies.0) {
+                    contact.penetration -= movement * contact.contact_normal;
+                }
+                if let Some(movement) = contact.bodies.1.and_then(moved) {
+                    contact.penetration += movement * contact.contact_normal;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kellenth::rigid_body::RigidBodyBuilder;
+
+    const ZERO: Vector3 = Vector3::new(0., 0., 0.);
+    const UP: Vector3 = Vector3::new(0., 1., 0.);
+
+    /// Returns the contacts of the bottom corners of a crate, a cube with sides of 1,
+    /// with the top of a platform, a slab whose top is 0.5 above its position.
+    fn crate_contacts(
+        bodies: &RigidBodySet,
+        crate_handle: BodyHandle,
+        platform: BodyHandle,
+        friction: f64,
+    ) -> Vec<Contact> {
+        let top = bodies[platform].position.y + 0.5;
+        let mut contacts = Vec::new();
+        for (x, z) in [(-0.5, -0.5), (-0.5, 0.5), (0.5, -0.5), (0.5, 0.5)] {
+            let corner = bodies[crate_handle].get_point_in_world_space(Vector3::new(x, -0.5, z));
+            if corner.y < top {
+                contacts.push(Contact::new(
+                    (crate_handle, Some(platform)),
+                    corner,
+                    UP,
+                    top - corner.y,
+                    0.,
+                    friction,
+                ));
+            }
+        }
+        contacts
+    }
+
+    /// Runs a crate resting on a kinematic platform moving at a constant velocity,
+    /// and returns the bodies, the crate and the platform.
+    fn ride_platform(friction: f64, steps: usize) -> (RigidBodySet, BodyHandle, BodyHandle) {
+        let mut bodies = RigidBodySet::new();
+        let platform = bodies.insert(
+            RigidBodyBuilder::new()
+                .with_velocity(Vector3::new(2., 0., 0.5))
+                .kinematic()
+                .build(),
+        );
+        let mut body = RigidBodyBuilder::new()
+            .with_position(Vector3::new(0., 1., 0.))
+            .with_mass(2.)
+            .with_box_shape(Vector3::new(0.5, 0.5, 0.5))
+            .with_linear_damping(1.)
+            .with_angular_damping(1.)
+            .build();
+        body.acceleration = Vector3::new(0., -9.81, 0.);
+        body.set_can_sleep(false);
+        let crate_handle = bodies.insert(body);
+
+        let mut resolver = ContactResolver::new(20);
+        for _ in 0..steps {
+            for body in bodies.as_mut_slice() {
+                body.integrate(0.01);
+            }
+            let mut contacts = crate_contacts(&bodies, crate_handle, platform, friction);
+            resolver.resolve_contacts(&mut contacts, &mut bodies, 0.01);
+        }
+        (bodies, crate_handle, platform)
+    }
+
+    #[test]
+    fn crate_on_a_moving_platform_is_carried_along() {
+        let (bodies, crate_handle, platform) = ride_platform(0.5, 200);
+        let (body, platform) = (&bodies[crate_handle], &bodies[platform]);
+        assert!(
+            (body.velocity - platform.velocity).magnitude() < 1e-3,
+            "{:?}",
+            body.velocity
+        );
+        assert!(body.rotation.magnitude() < 1e-3);
+        // It rests on the platform, neither sinking nor tipping over.
+        assert!((body.position.y - 1.).abs() < 0.01);
+        assert!(
+            body.get_direction_in_world_space(UP)
+                .angle_between(&UP)
+                .abs()
+                < 1e-3
+        );
+        // Kinematic bodies are never pushed back.
+        assert!((platform.velocity - Vector3::new(2., 0., 0.5)).magnitude() < 1e-12);
+    }
+
+    #[test]
+    fn frictionless_platforms_slide_away_under_the_crate() {
+        let (bodies, crate_handle, platform) = ride_platform(0., 50);
+        let body = &bodies[crate_handle];
+        assert!(body.velocity.magnitude() < 1e-9);
+        assert!((body.position.y - 1.).abs() < 0.01);
+        assert!((bodies[platform].position.x - 1.).abs() < 1e-9);
+    }
+
+    #[test]
+    fn contact_impulses_bounce_and_never_pull() {
+        let mut bodies = RigidBodySet::new();
+        let mut body = RigidBodyBuilder::new().with_mass(2.).build();
+        body.velocity = Vector3::new(1., -3., 0.);
+        let handle = bodies.insert(body);
+        let point = Vector3::new(0., -1., 0.);
+
+        // Frictionless, a sphere is only pushed along the normal through its center.
+        let mut contacts = [Contact::new((handle, None), point, UP, 0., 0.5, 0.)];
+        let mut resolver = ContactResolver::new(10);
+        resolver.resolve_contacts(&mut contacts, &mut bodies, 0.01);
+        let body = &bodies[handle];
+        assert!((body.velocity - Vector3::new(1., 1.5, 0.)).chebyshev_distance(&ZERO) < 1e-12);
+        assert!(body.rotation.magnitude() < 1e-12);
+        assert!((contacts[0].get_normal_impulse() - 9.).abs() < 1e-12);
+
+        // Separating contacts are left alone.
+        let before = bodies[handle].velocity;
+        resolver.resolve_contacts(&mut contacts, &mut bodies, 0.01);
+        assert_eq!(contacts[0].get_normal_impulse(), 0.);
+        assert_eq!(bodies[handle].velocity.manhattan_distance(&before), 0.);
+    }
+
+    #[test]
+    fn sliding_spheres_start_rolling() {
+        let mut bodies = RigidBodySet::new();
+        let mut body = RigidBodyBuilder::new()
+            .with_mass(1.)
+            .with_sphere_shape(1.)
+            .build();
+        body.velocity = Vector3::new(3., -0.1, 0.);
+        let handle = bodies.insert(body);
+        let mut resolver = ContactResolver::new(10);
+
+        // Enough friction to stop the sliding at once turns the sphere into a rolling one:
+        // the contact point stops, and 2/7 of the speed is lost as for a solid sphere.
+        let point = Vector3::new(0., -1., 0.);
+        let mut contacts = [Contact::new((handle, None), point, UP, 0., 0., 100.)];
+        resolver.resolve_contacts(&mut contacts, &mut bodies, 0.01);
+        let body = &bodies[handle];
+        assert!(body.velocity_at_point(point).magnitude() < 1e-12);
+        assert!((body.velocity.x - 3. * 5. / 7.).abs() < 1e-12);
+        assert!(body.velocity.y.abs() < 1e-12);
+        assert!((contacts[0].get_friction_impulse().x + 3. * 2. / 7.).abs() < 1e-12);
+    }
+}
@@ -1,8 +1,11 @@
 #[allow(unused, dead_code)]
 use std::ops;
 
+use std::cmp::Ordering;
+
 /// Three-dimensional vector used to describe position, movement, direction, etc. in space.
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vector3 {
     /// Length along the x coordinate
     pub x: f64,
@@ -38,9 +41,9 @@ impl ops::Mul<f64> for Vector3 {
 
     fn mul(self, _rhs: f64) -> Vector3 {
         Vector3 {
-            x: self.x * &_rhs,
-            y: self.y * &_rhs,
-            z: self.z * &_rhs,
+            x: self.x * _rhs,
+            y: self.y * _rhs,
+            z: self.z * _rhs,
         }
     }
 }
@@ -50,24 +53,24 @@ impl ops::Mul<i64> for Vector3 {
 
     fn mul(self, _rhs: i64) -> Vector3 {
         Vector3 {
-            x: self.x * &(_rhs as f64),
-            y: self.y * &(_rhs as f64),
-            z: self.z * &(_rhs as f64),
+            x: self.x * (_rhs as f64),
+            y: self.y * (_rhs as f64),
+            z: self.z * (_rhs as f64),
         }
     }
 }
 impl ops::MulAssign<f64> for Vector3 {
     fn mul_assign(&mut self, rhs: f64) {
-        self.x *= &rhs;
-        self.y *= &rhs;
-        self.z *= &rhs;
+        self.x *= rhs;
+        self.y *= rhs;
+        self.z *= rhs;
     }
 }
 impl ops::MulAssign<i64> for Vector3 {
     fn mul_assign(&mut self, rhs: i64) {
-        self.x *= &(rhs as f64);
-        self.y *= &(rhs as f64);
-        self.z *= &(rhs as f64);
+        self.x *= rhs as f64;
+        self.y *= rhs as f64;
+        self.z *= rhs as f64;
     }
 }
 
@@ -78,9 +81,9 @@ impl ops::Add<Vector3> for Vector3 {
 
     fn add(self, rhs: Vector3) -> Vector3 {
         Vector3 {
-            x: self.x + &rhs.x,
-            y: self.y + &rhs.y,
-            z: self.z + &rhs.z,
+            x: self.x + rhs.x,
+            y: self.y + rhs.y,
+            z: self.z + rhs.z,
         }
     }
 }
@@ -89,24 +92,34 @@ impl ops::Sub<Vector3> for Vector3 {
 
     fn sub(self, rhs: Vector3) -> Vector3 {
         Vector3 {
-            x: self.x - &rhs.x,
-            y: self.y - &rhs.y,
-            z: self.z - &rhs.z,
+            x: self.x - rhs.x,
+            y: self.y - rhs.y,
+            z: self.z - rhs.z,
         }
     }
 }
 impl ops::AddAssign<Vector3> for Vector3 {
     fn add_assign(&mut self, rhs: Vector3) {
-        self.x += &rhs.x;
-        self.y += &rhs.y;
-        self.z += &rhs.z;
+        self.x += rhs.x;
+        self.y += rhs.y;
+        self.z += rhs.z;
     }
 }
 impl ops::SubAssign<Vector3> for Vector3 {
     fn sub_assign(&mut self, rhs: Vector3) {
-        self.x -= &rhs.x;
-        self.y -= &rhs.y;
-        self.z -= &rhs.z;
+        self.x -= rhs.x;
+        self.y -= rhs.y;
+        self.z -= rhs.z;
+    }
+}
+impl ops::AddAssign<&Vector3> for Vector3 {
+    fn add_assign(&mut self, rhs: &Vector3) {
+        *self += *rhs;
+    }
+}
+impl ops::SubAssign<&Vector3> for Vector3 {
+    fn sub_assign(&mut self, rhs: &Vector3) {
+        *self -= *rhs;
     }
 }
 impl ops::Mul<Vector3> for Vector3 {
@@ -136,11 +149,16 @@ impl ops::RemAssign<Vector3> for Vector3 {
 }
 
 impl Vector3 {
+    /// Constructor
+    pub const fn new(x: f64, y: f64, z: f64) -> Self {
+        Self { x, y, z }
+    }
+
     /// Adds a vector scaled by a scalar to the current vector.
     pub fn add_scaled_vector(&mut self, vector: Vector3, scalar: f64) {
-        self.x += vector.x * &scalar;
-        self.y += vector.y * &scalar;
-        self.z += vector.z * &scalar;
+        self.x += vector.x * scalar;
+        self.y += vector.y * scalar;
+        self.z += vector.z * scalar;
     }
 
     /// Returns the component product of this vector and a given one.
@@ -152,6 +170,17 @@ impl Vector3 {
         }
     }
 
+    /// Returns the sum of the components, `x + y + z`.
+    pub fn component_sum(&self) -> f64 {
+        self.x + self.y + self.z
+    }
+
+    /// Returns the product of the components, `x * y * z`.
+    /// For a vector of box extents, this is the volume of the box.
+    pub fn component_product_scalar(&self) -> f64 {
+        self.x * self.y * self.z
+    }
+
     /// #### Equivalent to the `%` operator when used between two vectors.
     /// Returns the vector product of this vector and the given one.
     pub fn vector_product(self, vector: Vector3) -> Vector3 {
@@ -170,20 +199,26 @@ impl Vector3 {
 
     /// Inverts the vector along each axis.
     pub fn invert(&mut self) {
-        self.x *= -1 as f64;
-        self.y *= -1 as f64;
-        self.z *= -1 as f64;
+        self.x *= -1.;
+        self.y *= -1.;
+        self.z *= -1.;
+    }
+
+    /// Returns the squared magnitude of the vector.
+    /// Cheaper than `magnitude` when only comparing lengths.
+    pub fn square_magnitude(&self) -> f64 {
+        self.x * self.x + self.y * self.y + self.z * self.z
     }
 
     /// Returns the magnitude of the vector.
     pub fn magnitude(&self) -> f64 {
-        f64::sqrt(&self.x * &self.x + &self.y * &self.y + &self.z * &self.z)
+        f64::sqrt(self.x * self.x + self.y * self.y + self.z * self.z)
     }
 
     /// Normalizes the vector, making it a unit-length vector.
     pub fn normalize(&mut self) {
         let l = self.magnitude();
-        if l > 0 as f64 {
+        if l > 0. {
             *self *= 1. / l;
         }
     }
@@ -192,9 +227,1385 @@ impl Vector3 {
     pub fn get_normalized(self) -> Vector3 {
         let l = self.magnitude();
         let mut dist = self;
-        if l > 0 as f64 {
+        if l > 0. {
             dist *= 1. / l;
         }
         dist
     }
+
+    /// Returns the normalized version of the vector, or the given fallback if the vector
+    /// is too short to have a direction (see `approx_zero`) or isn't finite.
+    /// The fallback is returned as is, so it should be a unit vector.
+    pub fn normalize_or(&self, fallback: Vector3) -> Vector3 {
+        if self.approx_zero(f64::EPSILON) || !self.magnitude().is_finite() {
+            return fallback;
+        }
+        *self * (1. / self.magnitude())
+    }
+
+    /// Returns true if the magnitude of the vector is within the given tolerance of 1.
+    pub fn is_unit(&self, tolerance: f64) -> bool {
+        (self.magnitude() - 1.).abs() <= tolerance
+    }
+
+    /// Returns true if the magnitude of the vector is below the given epsilon,
+    /// so it is too short to have a meaningful direction.
+    /// The guards of `normalize_or`, `cos_angle`, `rotate_towards` and `project_onto_plane`
+    /// use it with `f64::EPSILON`.
+    pub fn approx_zero(&self, epsilon: f64) -> bool {
+        self.magnitude() < epsilon
+    }
+
+    /// Returns a vector with each component rounded down to the nearest integer.
+    /// `(-1.5).floor()` is `-2`.
+    pub fn floor(&self) -> Vector3 {
+        Vector3::new(self.x.floor(), self.y.floor(), self.z.floor())
+    }
+
+    /// Returns a vector with each component rounded up to the nearest integer.
+    /// `(-1.5).ceil()` is `-1`.
+    pub fn ceil(&self) -> Vector3 {
+        Vector3::new(self.x.ceil(), self.y.ceil(), self.z.ceil())
+    }
+
+    /// Returns a vector with each component rounded to the nearest integer.
+    /// Halfway cases are rounded away from zero, so `(-1.5).round()` is `-2`.
+    pub fn round(&self) -> Vector3 {
+        Vector3::new(self.x.round(), self.y.round(), self.z.round())
+    }
+
+    /// Returns a vector with the fractional part of each component removed.
+    /// `(-1.5).trunc()` is `-1`.
+    pub fn trunc(&self) -> Vector3 {
+        Vector3::new(self.x.trunc(), self.y.trunc(), self.z.trunc())
+    }
+
+    /// Returns the cosine of the angle between this vector and the given one,
+    /// clamped to `[-1, 1]` so rounding errors can never make `acos` return NaN.
+    /// If either vector is too short to have a direction, the angle is taken to be zero
+    /// and `1` is returned.
+    pub fn cos_angle(&self, other: &Vector3) -> f64 {
+        if self.approx_zero(f64::EPSILON) || other.approx_zero(f64::EPSILON) {
+            return 1.;
+        }
+        let lengths = self.magnitude() * other.magnitude();
+        (self.scalar_product(*other) / lengths).clamp(-1., 1.)
+    }
+
+    /// Returns the angle in radians between this vector and the given one, in `[0, PI]`.
+    pub fn angle_between(&self, other: &Vector3) -> f64 {
+        self.cos_angle(other).acos()
+    }
+
+    /// Returns the vector as 24 bytes: `x`, `y` and `z` in order, each in little-endian byte order.
+    /// The layout is the same on every host.
+    pub fn to_le_bytes(&self) -> [u8; 24] {
+        let mut bytes = [0; 24];
+        bytes[0..8].copy_from_slice(&self.x.to_le_bytes());
+        bytes[8..16].copy_from_slice(&self.y.to_le_bytes());
+        bytes[16..24].copy_from_slice(&self.z.to_le_bytes());
+        bytes
+    }
+
+    /// Reads a vector back from the layout produced by `to_le_bytes`.
+    pub fn from_le_bytes(bytes: &[u8; 24]) -> Vector3 {
+        let component = |offset: usize| {
+            let mut component = [0; 8];
+            component.copy_from_slice(&bytes[offset..offset + 8]);
+            f64::from_le_bytes(component)
+        };
+        Vector3::new(component(0), component(8), component(16))
+    }
+
+    /// Compares the magnitudes of this vector and the given one.
+    /// Meant for sorting by length, e.g. `velocities.sort_by(Vector3::cmp_magnitude)`.
+    /// Vectors are deliberately not `Ord`, since two different vectors can compare as equal here.
+    /// If either magnitude is NaN, the vectors compare as `Ordering::Equal`.
+    pub fn cmp_magnitude(&self, other: &Vector3) -> Ordering {
+        self.square_magnitude()
+            .partial_cmp(&other.square_magnitude())
+            .unwrap_or(Ordering::Equal)
+    }
+
+    /// Returns true if no component is infinite or NaN.
+    pub fn is_finite(&self) -> bool {
+        self.x.is_finite() && self.y.is_finite() && self.z.is_finite()
+    }
+
+    /// Adds the given vector to this one, returning `None` if any component of
+    /// the result overflows to infinity or becomes NaN.
+    pub fn checked_add(&self, other: &Vector3) -> Option<Vector3> {
+        let result = *self + *other;
+        if result.is_finite() {
+            Some(result)
+        } else {
+            None
+        }
+    }
+
+    /// Returns the point moved from this one towards the target by at most `max_delta`.
+    /// When the target is within `max_delta`, the target itself is returned, so it is never overshot.
+    pub fn move_towards(&self, target: &Vector3, max_delta: f64) -> Vector3 {
+        let offset = *target - *self;
+        let distance = offset.magnitude();
+        if distance <= max_delta || distance == 0. {
+            return *target;
+        }
+        *self + offset * (max_delta / distance)
+    }
+
+    /// Returns the vector rotated towards the direction of the target by at most `max_radians`,
+    /// keeping its own length. When the angle between them is within `max_radians`, the result
+    /// points along the target. Opposite vectors turn around an arbitrary perpendicular axis,
+    /// and the vector is returned as is if either one is too short to have a direction.
+    pub fn rotate_towards(&self, target: &Vector3, max_radians: f64) -> Vector3 {
+        if self.approx_zero(f64::EPSILON) || target.approx_zero(f64::EPSILON) {
+            return *self;
+        }
+        let length = self.magnitude();
+
+        let direction = self.get_normalized();
+        let angle = self.angle_between(target);
+        if angle <= max_radians {
+            return target.get_normalized() * length;
+        }
+
+        // Find the unit vector perpendicular to the direction, in the plane of the turn.
+        let mut perpendicular = target.project_onto_plane(&direction);
+        if perpendicular.square_magnitude() == 0. {
+            // The target is straight behind, so any perpendicular will do.
+            let axis = if direction.x.abs() < 0.9 {
+                Vector3::new(1., 0., 0.)
+            } else {
+                Vector3::new(0., 1., 0.)
+            };
+            perpendicular = (direction % axis).get_normalized();
+        } else {
+            perpendicular.normalize();
+        }
+
+        (direction * max_radians.cos() + perpendicular * max_radians.sin()) * length
+    }
+
+    /// Interpolates linearly towards the target, with `t` clamped to [0, 1],
+    /// so the result always lies between the two vectors.
+    pub fn lerp_clamped(&self, target: &Vector3, t: f64) -> Vector3 {
+        self.lerp_unclamped(target, t.clamp(0., 1.))
+    }
+
+    /// Interpolates linearly towards the target without clamping `t`,
+    /// so values outside [0, 1] extrapolate along the line through both vectors.
+    pub fn lerp_unclamped(&self, target: &Vector3, t: f64) -> Vector3 {
+        *self + (*target - *self) * t
+    }
+
+    /// Interpolates linearly towards the target.
+    /// This is the same as `lerp_clamped`; use `lerp_unclamped` to extrapolate.
+    pub fn lerp(&self, target: &Vector3, t: f64) -> Vector3 {
+        self.lerp_clamped(target, t)
+    }
+
+    /// Interpolates the direction towards the target at a constant angular speed, with
+    /// `t` clamped to [0, 1]. Both vectors should be unit-length, and so is the result.
+    /// Opposite vectors turn around an arbitrary perpendicular axis, like `rotate_towards`.
+    pub fn slerp(&self, target: &Vector3, t: f64) -> Vector3 {
+        let t = t.clamp(0., 1.);
+        self.rotate_towards(target, self.angle_between(target) * t)
+    }
+
+    /// Interpolates linearly towards the target, with `t` clamped to [0, 1], and normalizes
+    /// the result. Both vectors should be unit-length.
+    ///
+    /// It is cheaper than `slerp` and follows the same arc, but not at a constant speed:
+    /// it turns faster in the middle than at the ends. For small angles the two are
+    /// practically the same, while at 90 degrees they already differ by up to 4 degrees,
+    /// a quarter of the way from either end. Opposite vectors have no direction halfway, where
+    /// the result falls back to this vector.
+    pub fn nlerp(&self, target: &Vector3, t: f64) -> Vector3 {
+        self.lerp(target, t).normalize_or(*self)
+    }
+
+    /// Interpolates along the cubic Hermite curve from `p0` to `p1`, leaving `p0` along
+    /// the tangent `m0` and arriving at `p1` along the tangent `m1`, for `t` in [0, 1].
+    /// Useful for smooth camera paths; `t` isn't clamped.
+    pub fn hermite(p0: &Vector3, m0: &Vector3, p1: &Vector3, m1: &Vector3, t: f64) -> Vector3 {
+        let t2 = t * t;
+        let t3 = t2 * t;
+
+        // The Hermite basis functions.
+        let h00 = 2. * t3 - 3. * t2 + 1.;
+        let h10 = t3 - 2. * t2 + t;
+        let h01 = -2. * t3 + 3. * t2;
+        let h11 = t3 - t2;
+
+        *p0 * h00 + *m0 * h10 + *p1 * h01 + *m1 * h11
+    }
+
+    /// Evaluates the Catmull-Rom spline through the given points, which passes through
+    /// point `i` at `t = i`. `t` is clamped to [0, `points.len() - 1`], and the end points
+    /// are duplicated to work out the tangents there. There must be at least one point.
+    pub fn catmull_rom(points: &[Vector3], t: f64) -> Vector3 {
+        assert!(!points.is_empty());
+        let last = points.len() - 1;
+        if last == 0 {
+            return points[0];
+        }
+
+        // Find the segment, and how far along it we are.
+        let t = t.clamp(0., last as f64);
+        let segment = (t.floor() as usize).min(last - 1);
+        let local = t - segment as f64;
+
+        // Each tangent is half the difference between the neighbors.
+        let point = |i: usize| points[i.min(last)];
+        let p0 = point(segment);
+        let p1 = point(segment + 1);
+        let m0 = (p1 - point(segment.saturating_sub(1))) * 0.5;
+        let m1 = (point(segment + 2) - p0) * 0.5;
+
+        Vector3::hermite(&p0, &m0, &p1, &m1, local)
+    }
+
+    /// Returns the Manhattan (taxicab) distance to the given vector:
+    /// the sum of the absolute differences of the components.
+    pub fn manhattan_distance(&self, other: &Vector3) -> f64 {
+        (self.x - other.x).abs() + (self.y - other.y).abs() + (self.z - other.z).abs()
+    }
+
+    /// Returns the Chebyshev distance to the given vector:
+    /// the largest absolute difference of the components.
+    pub fn chebyshev_distance(&self, other: &Vector3) -> f64 {
+        (self.x - other.x)
+            .abs()
+            .max((self.y - other.y).abs())
+            .max((self.z - other.z).abs())
+    }
+
+    /// Returns the vector with its component along the normal removed, which is its
+    /// projection onto the plane through the origin with that normal.
+    /// The normal doesn't have to be normalized; a normal too short to have a direction
+    /// leaves the vector as is.
+    pub fn project_onto_plane(&self, normal: &Vector3) -> Vector3 {
+        if normal.approx_zero(f64::EPSILON) {
+            return *self;
+        }
+        *self - *normal * ((*self * *normal) / normal.square_magnitude())
+    }
+
+    /// Returns the vector mirrored off the surface with the given unit normal,
+    /// like a velocity bouncing without losing energy.
+    pub fn reflect(&self, normal: &Vector3) -> Vector3 {
+        self.reflect_with_restitution(normal, 1.)
+    }
+
+    /// Returns the vector mirrored off the surface with the given unit normal, with
+    /// the component along the normal scaled by the restitution and the rest kept,
+    /// like a velocity bouncing off the surface. A restitution of 0 slides along it.
+    pub fn reflect_with_restitution(&self, normal: &Vector3, restitution: f64) -> Vector3 {
+        debug_assert!(normal.is_unit(1e-6));
+
+        let normal_component = *normal * (*self * *normal);
+        *self - normal_component - normal_component * restitution
+    }
+
+    /// Returns the direction of the vector bent through a surface by Snell's law,
+    /// or `None` on total internal reflection.
+    /// Both the vector and the normal must be unit-length, with the normal facing
+    /// against the vector; `eta` is the ratio of the refractive indices, from over to under.
+    pub fn refract(&self, normal: &Vector3, eta: f64) -> Option<Vector3> {
+        debug_assert!(self.is_unit(1e-6) && normal.is_unit(1e-6));
+
+        let cos_incident = -(*self * *normal);
+        let k = 1. - eta * eta * (1. - cos_incident * cos_incident);
+        if k < 0. {
+            return None;
+        }
+        Some(*self * eta + *normal * (eta * cos_incident - k.sqrt()))
+    }
+
+    /// Returns the x and z components, dropping the height, for top-down views.
+    pub fn to_vector2_xz(&self) -> Vector2 {
+        Vector2::new(self.x, self.z)
+    }
+
+    /// Returns the x and y components, dropping the depth, for side views.
+    pub fn to_vector2_xy(&self) -> Vector2 {
+        Vector2::new(self.x, self.y)
+    }
+}
+
+/// Converts to the double precision vector of `glam`, enabled by the `glam` feature.
+#[cfg(feature = "glam")]
+impl From<Vector3> for glam::DVec3 {
+    fn from(vector: Vector3) -> Self {
+        glam::DVec3::new(vector.x, vector.y, vector.z)
+    }
+}
+
+/// Converts from the double precision vector of `glam`, enabled by the `glam` feature.
+#[cfg(feature = "glam")]
+impl From<glam::DVec3> for Vector3 {
+    fn from(vector: glam::DVec3) -> Self {
+        Vector3::new(vector.x, vector.y, vector.z)
+    }
+}
+
+/// Converts to the interoperability vector of `mint`, enabled by the `mint` feature.
+#[cfg(feature = "mint")]
+impl From<Vector3> for mint::Vector3<f64> {
+    fn from(vector: Vector3) -> Self {
+        mint::Vector3 {
+            x: vector.x,
+            y: vector.y,
+            z: vector.z,
+        }
+    }
+}
+
+/// Converts from the interoperability vector of `mint`, enabled by the `mint` feature.
+#[cfg(feature = "mint")]
+impl From<mint::Vector3<f64>> for Vector3 {
+    fn from(vector: mint::Vector3<f64>) -> Self {
+        Vector3::new(vector.x, vector.y, vector.z)
+    }
+}
+
+/// Two-dimensional vector, mostly used for top-down and side views of the simulation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Vector2 {
+    /// Length along the x coordinate
+    pub x: f64,
+
+    /// Length along the y coordinate
+    pub y: f64,
+}
+
+impl ops::Mul<f64> for Vector2 {
+    type Output = Vector2;
+
+    fn mul(self, _rhs: f64) -> Vector2 {
+        Vector2::new(self.x * _rhs, self.y * _rhs)
+    }
+}
+
+impl ops::Add<Vector2> for Vector2 {
+    type Output = Vector2;
+
+    fn add(self, _rhs: Vector2) -> Vector2 {
+        Vector2::new(self.x + _rhs.x, self.y + _rhs.y)
+    }
+}
+
+impl ops::Sub<Vector2> for Vector2 {
+    type Output = Vector2;
+
+    fn sub(self, _rhs: Vector2) -> Vector2 {
+        Vector2::new(self.x - _rhs.x, self.y - _rhs.y)
+    }
+}
+
+impl Vector2 {
+    /// Constructor
+    pub const fn new(x: f64, y: f64) -> Self {
+        Self { x, y }
+    }
+
+    /// Calculates the magnitude of the vector.
+    pub fn magnitude(&self) -> f64 {
+        self.square_magnitude().sqrt()
+    }
+
+    /// Calculates the squared magnitude of the vector.
+    pub fn square_magnitude(&self) -> f64 {
+        self.x * self.x + self.y * self.y
+    }
+
+    /// Returns the vector in the XZ plane, at the given height.
+    pub fn to_vector3_xz(&self, y: f64) -> Vector3 {
+        Vector3::new(self.x, y, self.y)
+    }
+}
+
+/// Holds a 3x3 row-major matrix representing a transformation in 3D space that
+/// doesn't include translation, such as a rotation or an inertia tensor.
+///
+/// # Conventions
+/// * `matrix * vector` treats the vector as a column: it computes `M v`.
+///   This is the convention used throughout the engine.
+/// * `vector * matrix` treats the vector as a row: it computes `vᵀ M`,
+///   which equals `Mᵀ v`. It is only provided for users thinking in row vectors.
+///
+/// The two only agree for symmetric matrices, so mixing them up silently transposes
+/// the transformation.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Matrix3 {
+    /// Holds the matrix data in row-major order: `data[row * 3 + column]`.
+    pub data: [f64; 9],
+}
+
+impl Matrix3 {
+    /// Constructor
+    /// The values are given in row-major order.
+    #[allow(clippy::too_many_arguments)]
+    pub const fn new(
+        c0: f64,
+        c1: f64,
+        c2: f64,
+        c3: f64,
+        c4: f64,
+        c5: f64,
+        c6: f64,
+        c7: f64,
+        c8: f64,
+    ) -> Self {
+        Self {
+            data: [c0, c1, c2, c3, c4, c5, c6, c7, c8],
+        }
+    }
+
+    /// Returns the identity matrix.
+    pub const fn identity() -> Self {
+        Self::new(1., 0., 0., 0., 1., 0., 0., 0., 1.)
+    }
+
+    /// Returns a diagonal matrix with the given values on the diagonal.
+    pub const fn diagonal(a: f64, b: f64, c: f64) -> Self {
+        Self::new(a, 0., 0., 0., b, 0., 0., 0., c)
+    }
+
+    /// Returns the skew-symmetric matrix of the given vector, which computes
+    /// the cross product with it: `S(a) b = a × b`.
+    pub const fn skew_symmetric(v: Vector3) -> Self {
+        Self::new(0., -v.z, v.y, v.z, 0., -v.x, -v.y, v.x, 0.)
+    }
+
+    /// Returns the transformed version of the given vector, `M v`.
+    pub fn transform(&self, vector: Vector3) -> Vector3 {
+        *self * vector
+    }
+
+    /// Returns the transpose of the matrix.
+    pub fn transpose(&self) -> Matrix3 {
+        let d = &self.data;
+        Matrix3::new(d[0], d[3], d[6], d[1], d[4], d[7], d[2], d[5], d[8])
+    }
+
+    /// Returns the determinant of the matrix.
+    pub fn determinant(&self) -> f64 {
+        let d = &self.data;
+        d[0] * (d[4] * d[8] - d[5] * d[7]) - d[1] * (d[3] * d[8] - d[5] * d[6])
+            + d[2] * (d[3] * d[7] - d[4] * d[6])
+    }
+
+    /// Returns the inverse of the matrix, or `None` if it is singular.
+    pub fn inverse(&self) -> Option<Matrix3> {
+        let det = self.determinant();
+        if det == 0. || !det.is_finite() {
+            return None;
+        }
+
+        // The inverse is the adjugate divided by the determinant.
+        let d = &self.data;
+        let inv = 1. / det;
+        Some(Matrix3::new(
+            (d[4] * d[8] - d[5] * d[7]) * inv,
+            (d[2] * d[7] - d[1] * d[8]) * inv,
+            (d[1] * d[5] - d[2] * d[4]) * inv,
+            (d[5] * d[6] - d[3] * d[8]) * inv,
+            (d[0] * d[8] - d[2] * d[6]) * inv,
+            (d[2] * d[3] - d[0] * d[5]) * inv,
+            (d[3] * d[7] - d[4] * d[6]) * inv,
+            (d[1] * d[6] - d[0] * d[7]) * inv,
+            (d[0] * d[4] - d[1] * d[3]) * inv,
+        ))
+    }
+
+    /// Returns the rotation matrix of the given orientation, which must be normalized.
+    pub fn from_orientation(q: Quaternion) -> Matrix3 {
+        Matrix3::new(
+            1. - 2. * (q.j * q.j + q.k * q.k),
+            2. * (q.i * q.j - q.k * q.r),
+            2. * (q.i * q.k + q.j * q.r),
+            2. * (q.i * q.j + q.k * q.r),
+            1. - 2. * (q.i * q.i + q.k * q.k),
+            2. * (q.j * q.k - q.i * q.r),
+            2. * (q.i * q.k - q.j * q.r),
+            2. * (q.j * q.k + q.i * q.r),
+            1. - 2. * (q.i * q.i + q.j * q.j),
+        )
+    }
+}
+
+/// Column-vector convention: computes `M v`.
+impl ops::Mul<Vector3> for Matrix3 {
+    type Output = Vector3;
+
+    fn mul(self, vector: Vector3) -> Vector3 {
+        let d = &self.data;
+        Vector3 {
+            x: vector.x * d[0] + vector.y * d[1] + vector.z * d[2],
+            y: vector.x * d[3] + vector.y * d[4] + vector.z * d[5],
+            z: vector.x * d[6] + vector.y * d[7] + vector.z * d[8],
+        }
+    }
+}
+
+/// Row-vector convention: computes `vᵀ M`, which is the same as `Mᵀ v`.
+impl ops::Mul<Matrix3> for Vector3 {
+    type Output = Vector3;
+
+    fn mul(self, matrix: Matrix3) -> Vector3 {
+        let d = &matrix.data;
+        Vector3 {
+            x: self.x * d[0] + self.y * d[3] + self.z * d[6],
+            y: self.x * d[1] + self.y * d[4] + self.z * d[7],
+            z: self.x * d[2] + self.y * d[5] + self.z * d[8],
+        }
+    }
+}
+
+impl ops::Mul<Matrix3> for Matrix3 {
+    type Output = Matrix3;
+
+    fn mul(self, o: Matrix3) -> Matrix3 {
+        let a = &self.data;
+        let b = &o.data;
+        let mut data = [0.; 9];
+        for row in 0..3 {
+            for column in 0..3 {
+                data[row * 3 + column] = a[row * 3] * b[column]
+                    + a[row * 3 + 1] * b[3 + column]
+                    + a[row * 3 + 2] * b[6 + column];
+            }
+        }
+        Matrix3 { data }
+    }
+}
+
+impl ops::Add<Matrix3> for Matrix3 {
+    type Output = Matrix3;
+
+    fn add(self, o: Matrix3) -> Matrix3 {
+        let mut data = self.data;
+        for (value, other) in data.iter_mut().zip(o.data) {
+            *value += other;
+        }
+        Matrix3 { data }
+    }
+}
+
+/// Transform matrix made of a rotation and a translation: a 3x4 matrix standing for a
+/// 4x4 one whose bottom row is always `(0, 0, 0, 1)`.
+/// Like `Matrix3`, it treats vectors as columns.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Matrix4 {
+    /// Holds the matrix data in row-major order: `data[row * 4 + column]`.
+    /// The last column is the translation.
+    pub data: [f64; 12],
+}
+
+impl Matrix4 {
+    /// Returns the identity transform.
+    pub const fn identity() -> Self {
+        Self {
+            data: [1., 0., 0., 0., 0., 1., 0., 0., 0., 0., 1., 0.],
+        }
+    }
+
+    /// Returns the transform rotating by the given orientation, which must be normalized,
+    /// and then translating by the given position.
+    pub fn from_orientation_and_position(orientation: Quaternion, position: Vector3) -> Self {
+        let r = Matrix3::from_orientation(orientation).data;
+        Self {
+            data: [
+                r[0], r[1], r[2], position.x, r[3], r[4], r[5], position.y, r[6], r[7], r[8],
+                position.z,
+            ],
+        }
+    }
+
+    /// Returns the rotation part of the transform.
+    pub fn get_rotation(&self) -> Matrix3 {
+        let d = &self.data;
+        Matrix3::new(d[0], d[1], d[2], d[4], d[5], d[6], d[8], d[9], d[10])
+    }
+
+    /// Returns the translation part of the transform.
+    pub fn get_translation(&self) -> Vector3 {
+        Vector3::new(self.data[3], self.data[7], self.data[11])
+    }
+
+    /// Returns the transformed version of the given point, rotated and then translated.
+    pub fn transform(&self, point: Vector3) -> Vector3 {
+        *self * point
+    }
+
+    /// Returns the transformed version of the given direction, which is only rotated.
+    pub fn transform_direction(&self, direction: Vector3) -> Vector3 {
+        self.get_rotation() * direction
+    }
+
+    /// Returns the given point transformed by the inverse of the transform.
+    /// The rotation must be a pure rotation, as it is for any rigid body.
+    pub fn transform_inverse(&self, point: Vector3) -> Vector3 {
+        self.transform_inverse_direction(point - self.get_translation())
+    }
+
+    /// Returns the given direction transformed by the inverse of the transform,
+    /// which only rotates it back. The rotation must be a pure rotation.
+    pub fn transform_inverse_direction(&self, direction: Vector3) -> Vector3 {
+        // The inverse of a rotation is its transpose.
+        direction * self.get_rotation()
+    }
+}
+
+/// Transforms a point: rotates and then translates it.
+impl ops::Mul<Vector3> for Matrix4 {
+    type Output = Vector3;
+
+    fn mul(self, vector: Vector3) -> Vector3 {
+        let d = &self.data;
+        Vector3 {
+            x: vector.x * d[0] + vector.y * d[1] + vector.z * d[2] + d[3],
+            y: vector.x * d[4] + vector.y * d[5] + vector.z * d[6] + d[7],
+            z: vector.x * d[8] + vector.y * d[9] + vector.z * d[10] + d[11],
+        }
+    }
+}
+
+/// Quaternion `r + i·x + j·y + k·z`, used to hold orientations.
+/// Only normalized quaternions describe rotations; a unit quaternion rotates by the
+/// angle `θ` around the axis `a` when it is `cos(θ/2) + sin(θ/2) a`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Quaternion {
+    /// Holds the real component of the quaternion.
+    pub r: f64,
+
+    /// Holds the first complex component of the quaternion.
+    pub i: f64,
+
+    /// Holds the second complex component of the quaternion.
+    pub j: f64,
+
+    /// Holds the third complex component of the quaternion.
+    pub k: f64,
+}
+
+impl Quaternion {
+    /// Constructor
+    pub const fn new(r: f64, i: f64, j: f64, k: f64) -> Self {
+        Self { r, i, j, k }
+    }
+
+    /// Returns the quaternion of no rotation.
+    pub const fn identity() -> Self {
+        Self::new(1., 0., 0., 0.)
+    }
+
+    /// Returns the quaternion rotating by the given angle, in radians, around the given axis.
+    /// The axis is normalized, so it doesn't need to be unit-length.
+    pub fn from_axis_angle(axis: Vector3, angle: f64) -> Self {
+        let axis = axis.get_normalized();
+        let (sin, cos) = (angle * 0.5).sin_cos();
+        Self::new(cos, axis.x * sin, axis.y * sin, axis.z * sin)
+    }
+
+    /// Returns the square of the magnitude of the quaternion.
+    pub fn square_magnitude(&self) -> f64 {
+        self.r * self.r + self.i * self.i + self.j * self.j + self.k * self.k
+    }
+
+    /// Normalizes the quaternion to unit length, making it a valid orientation.
+    /// A zero-length quaternion becomes the identity.
+    pub fn normalize(&mut self) {
+        let d = self.square_magnitude();
+
+        // Check for zero length quaternion, and use the no-rotation quaternion in that case.
+        if d < f64::EPSILON {
+            *self = Self::identity();
+            return;
+        }
+
+        let d = 1. / d.sqrt();
+        self.r *= d;
+        self.i *= d;
+        self.j *= d;
+        self.k *= d;
+    }
+
+    /// Returns the normalized version of the quaternion.
+    pub fn get_normalized(mut self) -> Quaternion {
+        self.normalize();
+        self
+    }
+
+    /// Adds the given vector to the quaternion, scaled by the given amount.
+    /// Used to turn an orientation by an angular velocity over a duration:
+    /// `q += ½ (0, ω) q dt`. The result has to be normalized again.
+    pub fn add_scaled_vector(&mut self, vector: Vector3, scale: f64) {
+        let q = Quaternion::new(0., vector.x * scale, vector.y * scale, vector.z * scale) * *self;
+        self.r += q.r * 0.5;
+        self.i += q.i * 0.5;
+        self.j += q.j * 0.5;
+        self.k += q.k * 0.5;
+    }
+
+    /// Interpolates the orientation towards the target at a constant angular speed,
+    /// with `t` clamped to [0, 1], taking the shortest way around. Both quaternions
+    /// should be normalized, and so is the result.
+    pub fn slerp(&self, target: &Quaternion, t: f64) -> Quaternion {
+        let t = t.clamp(0., 1.);
+
+        // q and -q are the same orientation; pick the one closer to this quaternion.
+        let mut cos = self.r * target.r + self.i * target.i + self.j * target.j + self.k * target.k;
+        let sign = if cos < 0. { -1. } else { 1. };
+        cos *= sign;
+
+        // Nearly equal orientations would divide by a vanishing sine, so blend linearly.
+        let (a, b) = if cos > 0.9995 {
+            (1. - t, t)
+        } else {
+            let angle = cos.acos();
+            let sin = angle.sin();
+            (((1. - t) * angle).sin() / sin, (t * angle).sin() / sin)
+        };
+        let b = b * sign;
+        Quaternion::new(
+            self.r * a + target.r * b,
+            self.i * a + target.i * b,
+            self.j * a + target.j * b,
+            self.k * a + target.k * b,
+        )
+        .get_normalized()
+    }
+}
+
+/// Multiplies two quaternions. The product of two orientations applies the right one first.
+impl ops::Mul<Quaternion> for Quaternion {
+    type Output = Quaternion;
+
+    fn mul(self, o: Quaternion) -> Quaternion {
+        Quaternion {
+            r: self.r * o.r - self.i * o.i - self.j * o.j - self.k * o.k,
+            i: self.r * o.i + self.i * o.r + self.j * o.k - self.k * o.j,
+            j: self.r * o.j + self.j * o.r + self.k * o.i - self.i * o.k,
+            k: self.r * o.k + self.k * o.r + self.i * o.j - self.j * o.i,
+        }
+    }
+}
+
+impl ops::MulAssign<Quaternion> for Quaternion {
+    fn mul_assign(&mut self, o: Quaternion) {
+        *self = *self * o;
+    }
+}
+
+/// An infinite plane, made of the points `p` for which `normal * p == offset`.
+#[derive(Debug, Clone, Copy)]
+pub struct Plane {
+    /// Holds the unit normal of the plane, pointing to its positive side.
+    pub normal: Vector3,
+
+    /// Holds the distance of the plane from the origin, along the normal.
+    pub offset: f64,
+}
+
+impl Plane {
+    /// Constructor
+    /// The normal is normalized, so it doesn't need to be unit-length.
+    pub fn new(normal: Vector3, offset: f64) -> Self {
+        Self {
+            normal: normal.get_normalized(),
+            offset,
+        }
+    }
+
+    /// Constructs the plane going through the given point with the given normal.
+    pub fn from_point_normal(point: Vector3, normal: Vector3) -> Self {
+        let normal = normal.get_normalized();
+        Self {
+            normal,
+            offset: normal * point,
+        }
+    }
+
+    /// Returns the signed distance of the given point from the plane.
+    /// It is positive on the side the normal points to.
+    pub fn signed_distance(&self, point: Vector3) -> f64 {
+        self.normal * point - self.offset
+    }
+}
+
+/// A half-line starting at `origin`, made of the points `origin + direction * t` for `t >= 0`.
+#[derive(Debug, Clone, Copy)]
+pub struct Ray {
+    /// Holds the point the ray starts at.
+    pub origin: Vector3,
+
+    /// Holds the direction of the ray. It doesn't need to be unit-length, but then
+    /// the parameters along the ray aren't distances.
+    pub direction: Vector3,
+}
+
+impl Ray {
+    /// Constructor
+    pub fn new(origin: Vector3, direction: Vector3) -> Self {
+        Self { origin, direction }
+    }
+
+    /// Returns the point at the given parameter along the ray.
+    pub fn point_at(&self, t: f64) -> Vector3 {
+        self.origin + self.direction * t
+    }
+}
+
+/// An axis-aligned bounding box, made of the points between `min` and `max` on every axis.
+#[derive(Debug, Clone, Copy)]
+pub struct Aabb {
+    /// Holds the corner with the smallest coordinates.
+    pub min: Vector3,
+
+    /// Holds the corner with the largest coordinates.
+    pub max: Vector3,
+}
+
+impl Aabb {
+    /// Constructor
+    pub fn new(min: Vector3, max: Vector3) -> Self {
+        Self { min, max }
+    }
+
+    /// Returns the parameters along the ray where it enters and leaves the box, or `None`
+    /// if it misses. When the ray starts inside the box, the entry is negative.
+    /// Uses the slab method, checking rays parallel to a pair of faces against the
+    /// slab between them directly, so zero direction components never divide by zero.
+    pub fn intersect_ray(&self, ray: &Ray) -> Option<(f64, f64)> {
+        let mut entry = f64::NEG_INFINITY;
+        let mut exit = f64::INFINITY;
+
+        let axes = [
+            (ray.origin.x, ray.direction.x, self.min.x, self.max.x),
+            (ray.origin.y, ray.direction.y, self.min.y, self.max.y),
+            (ray.origin.z, ray.direction.z, self.min.z, self.max.z),
+        ];
+        for (origin, direction, min, max) in axes {
+            if direction == 0. {
+                // Parallel to the slab, so either always inside it or never.
+                if origin < min || origin > max {
+                    return None;
+                }
+                continue;
+            }
+
+            let inverse = 1. / direction;
+            let (near, far) = {
+                let a = (min - origin) * inverse;
+                let b = (max - origin) * inverse;
+                if a <= b {
+                    (a, b)
+                } else {
+                    (b, a)
+                }
+            };
+            entry = entry.max(near);
+            exit = exit.min(far);
+            if entry > exit {
+                return None;
+            }
+        }
+
+        // The box is behind the ray, or the ray has no direction and starts inside.
+        if exit < 0. || entry == f64::NEG_INFINITY {
+            return None;
+        }
+        Some((entry, exit))
+    }
+}
+
+/// Moves `current` towards `target` like a critically damped spring, reaching it without overshoot.
+/// `velocity` holds the velocity of the follower, and must be kept between calls.
+/// `smooth_time` is roughly the time it takes to reach the target.
+/// Uses the rational approximation of the exponential from Game Programming Gems 4.
+pub fn smooth_damp(
+    current: Vector3,
+    target: Vector3,
+    velocity: &mut Vector3,
+    smooth_time: f64,
+    duration: f64,
+) -> Vector3 {
+    let smooth_time = smooth_time.max(1e-4);
+    let omega = 2. / smooth_time;
+    let x = omega * duration;
+    let exp = 1. / (1. + x + 0.48 * x * x + 0.235 * x * x * x);
+
+    let change = current - target;
+    let temp = (*velocity + change * omega) * duration;
+    *velocity = (*velocity - temp * omega) * exp;
+    let mut output = target + (change + temp) * exp;
+
+    // Prevent overshooting the target.
+    if (target - current) * (output - target) > 0. {
+        output = target;
+        *velocity = Vector3::new(0., 0., 0.);
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ZERO: Vector3 = Vector3::new(0., 0., 0.);
+
+    /// Asserts that two vectors are equal to within the given tolerance, per component.
+    fn assert_close(a: Vector3, b: Vector3, tolerance: f64) {
+        assert!(
+            (a - b).chebyshev_distance(&ZERO) <= tolerance,
+            "{a:?} != {b:?}"
+        );
+    }
+
+    #[test]
+    fn approx_zero_compares_the_magnitude_with_epsilon() {
+        assert!(ZERO.approx_zero(1e-9));
+        assert!(Vector3::new(1e-12, -1e-12, 0.).approx_zero(1e-9));
+        assert!(!Vector3::new(0., 1., 0.).approx_zero(1e-9));
+        // The bound is exclusive, so nothing is shorter than zero.
+        assert!(!ZERO.approx_zero(0.));
+    }
+
+    #[test]
+    fn direction_guards_treat_tiny_vectors_as_zero() {
+        let tiny = Vector3::new(1e-17, 0., 0.);
+        let up = Vector3::new(0., 1., 0.);
+        assert_close(tiny.normalize_or(up), up, 0.);
+        assert_close(up.project_onto_plane(&tiny), up, 0.);
+        assert_eq!(tiny.cos_angle(&up), 1.);
+        assert_close(tiny.rotate_towards(&up, 1.), tiny, 0.);
+    }
+
+    #[test]
+    fn rounding_works_per_component() {
+        let v = Vector3::new(1.5, -1.5, -0.25);
+        assert_close(v.floor(), Vector3::new(1., -2., -1.), 0.);
+        assert_close(v.ceil(), Vector3::new(2., -1., 0.), 0.);
+        assert_close(v.round(), Vector3::new(2., -2., 0.), 0.);
+        assert_close(v.trunc(), Vector3::new(1., -1., 0.), 0.);
+        assert!(v.ceil().z.is_sign_negative());
+    }
+
+    #[test]
+    fn cos_angle_is_clamped_for_nearly_parallel_vectors() {
+        let a = Vector3::new(0.1, 0.3, 0.7);
+        let b = a * (29. * 0.37);
+        let raw = a.scalar_product(b) / (a.magnitude() * b.magnitude());
+        assert!(raw > 1. && raw.acos().is_nan());
+
+        assert_eq!(a.cos_angle(&b), 1.);
+        assert_eq!(a.angle_between(&b), 0.);
+        let mut back = b;
+        back.invert();
+        assert_eq!(a.cos_angle(&back), -1.);
+        assert_eq!(a.angle_between(&back), std::f64::consts::PI);
+    }
+
+    #[test]
+    fn le_bytes_round_trip_in_little_endian_order() {
+        let v = Vector3::new(1., -2.5, f64::MAX);
+        let bytes = v.to_le_bytes();
+        assert_eq!(bytes[0..8], [0, 0, 0, 0, 0, 0, 0xf0, 0x3f]);
+        assert_eq!(bytes[8..16], [0, 0, 0, 0, 0, 0, 0x04, 0xc0]);
+        assert_eq!(
+            bytes[16..24],
+            [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xef, 0x7f]
+        );
+
+        let back = Vector3::from_le_bytes(&bytes);
+        assert_eq!(
+            [back.x, back.y, back.z].map(f64::to_bits),
+            [v.x, v.y, v.z].map(f64::to_bits)
+        );
+    }
+
+    #[test]
+    fn cmp_magnitude_sorts_by_length() {
+        let mut velocities = [
+            Vector3::new(0., -3., 0.),
+            Vector3::new(1., 0., 0.),
+            Vector3::new(2., 2., 2.),
+            Vector3::new(0., 0., -2.),
+        ];
+        velocities.sort_by(Vector3::cmp_magnitude);
+        let lengths: Vec<f64> = velocities.iter().map(Vector3::magnitude).collect();
+        assert_eq!(lengths, [1., 2., 3., 12f64.sqrt()]);
+
+        let nan = Vector3::new(f64::NAN, 0., 0.);
+        assert_eq!(nan.cmp_magnitude(&velocities[0]), Ordering::Equal);
+        assert_eq!(
+            Vector3::new(3., 0., 0.).cmp_magnitude(&velocities[2]),
+            Ordering::Equal
+        );
+    }
+
+    #[test]
+    fn checked_add_detects_overflow() {
+        let big = Vector3::new(f64::MAX, 0., 1.);
+        assert!(big.checked_add(&Vector3::new(f64::MAX, 0., 0.)).is_none());
+        assert!(big.checked_add(&Vector3::new(0., f64::NAN, 0.)).is_none());
+        // Huge components are fine as long as the result is finite.
+        assert!(big.checked_add(&Vector3::new(-f64::MAX, 0., 0.)).is_some());
+
+        let sum = Vector3::new(1., 2., 3.)
+            .checked_add(&Vector3::new(-0.5, 0.5, 1.))
+            .unwrap();
+        assert_close(sum, Vector3::new(0.5, 2.5, 4.), 0.);
+    }
+
+    #[test]
+    fn grid_distances_bound_the_euclidean_one() {
+        let a = Vector3::new(1., -2., 3.);
+        let b = a + Vector3::new(2., 3., -6.);
+        assert_eq!(a.manhattan_distance(&b), 11.);
+        assert_eq!(a.chebyshev_distance(&b), 6.);
+        assert_eq!((b - a).magnitude(), 7.);
+        assert_eq!(b.manhattan_distance(&a), 11.);
+        assert_eq!(b.chebyshev_distance(&a), 6.);
+    }
+
+    #[test]
+    fn column_and_row_vector_products_differ_for_non_symmetric_matrices() {
+        let m = Matrix3::new(1., 2., 3., 4., 5., 6., 7., 8., 10.);
+        let v = Vector3::new(1., -1., 2.);
+        assert_close(m * v, Vector3::new(5., 11., 19.), 0.);
+        assert_close(v * m, Vector3::new(11., 13., 17.), 0.);
+        assert_close(v * m, m.transpose() * v, 0.);
+        assert_close(m.transform(v), m * v, 0.);
+
+        let symmetric = m + m.transpose();
+        assert_close(symmetric * v, v * symmetric, 0.);
+    }
+
+    #[test]
+    fn smooth_damp_converges_without_overshoot() {
+        let target = Vector3::new(3., -1., 2.);
+        let mut current = Vector3::new(-2., 4., 0.);
+        let mut velocity = ZERO;
+        for _ in 0..600 {
+            let previous = current;
+            current = smooth_damp(current, target, &mut velocity, 0.5, 1. / 60.);
+            // Every component keeps heading towards the target, never past it.
+            for (before, after, goal) in [
+                (previous.x, current.x, target.x),
+                (previous.y, current.y, target.y),
+                (previous.z, current.z, target.z),
+            ] {
+                assert!((goal - after) * (goal - before) >= 0.);
+            }
+        }
+        assert_close(current, target, 1e-6);
+        assert!(velocity.magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn move_towards_steps_at_most_max_delta() {
+        let start = Vector3::new(1., 1., 1.);
+        let target = Vector3::new(4., 5., 1.);
+        assert_close(
+            start.move_towards(&target, 2.),
+            Vector3::new(2.2, 2.6, 1.),
+            1e-15,
+        );
+        // Within range it lands exactly on the target.
+        assert_close(start.move_towards(&target, 5.), target, 0.);
+        assert_close(start.move_towards(&target, 100.), target, 0.);
+        assert_close(target.move_towards(&target, 1.), target, 0.);
+    }
+
+    #[test]
+    fn lerp_variants_differ_only_outside_the_unit_range() {
+        let a = Vector3::new(1., -2., 4.);
+        let b = Vector3::new(3., 2., 0.);
+        assert_close(a.lerp_clamped(&b, 1.5), b, 0.);
+        assert_close(a.lerp_unclamped(&b, 1.5), Vector3::new(4., 4., -2.), 1e-12);
+        assert_close(a.lerp_clamped(&b, -1.), a, 0.);
+        assert_close(a.lerp(&b, 1.5), a.lerp_clamped(&b, 1.5), 0.);
+
+        let half = Vector3::new(2., 0., 2.);
+        assert_close(a.lerp_clamped(&b, 0.5), half, 1e-12);
+        assert_close(a.lerp_unclamped(&b, 0.5), half, 1e-12);
+    }
+
+    #[test]
+    fn projections_drop_the_normal_component() {
+        let v = Vector3::new(1., 5., 1.);
+        assert_close(
+            v.project_onto_plane(&Vector3::new(0., 1., 0.)),
+            Vector3::new(1., 0., 1.),
+            0.,
+        );
+        // The normal doesn't need to be unit-length.
+        assert_close(
+            v.project_onto_plane(&Vector3::new(0., -3., 0.)),
+            Vector3::new(1., 0., 1.),
+            1e-12,
+        );
+        let tilted = Vector3::new(1., 1., 0.);
+        assert!(v.project_onto_plane(&tilted).scalar_product(tilted).abs() < 1e-12);
+
+        assert_eq!(v.to_vector2_xz(), Vector2::new(1., 1.));
+        assert_eq!(
+            Vector3::new(2., 3., 4.).to_vector2_xy(),
+            Vector2::new(2., 3.)
+        );
+    }
+
+    #[test]
+    fn rotate_towards_snaps_within_the_limit_and_turns_partway_beyond_it() {
+        use std::f64::consts::{FRAC_PI_2, PI};
+
+        let forward = Vector3::new(2., 0., 0.);
+        let target = Vector3::new(1., 0.1, 0.);
+        let snapped = forward.rotate_towards(&target, 0.2);
+        assert_close(snapped, target.get_normalized() * 2., 1e-12);
+
+        let up = Vector3::new(0., 5., 0.);
+        let turned = forward.rotate_towards(&up, 0.3);
+        assert!((turned.angle_between(&forward) - 0.3).abs() < 1e-12);
+        assert!((turned.angle_between(&up) - (FRAC_PI_2 - 0.3)).abs() < 1e-12);
+        assert!((turned.magnitude() - 2.).abs() < 1e-12);
+        assert_eq!(turned.z, 0.);
+
+        // Turning around still only turns by the limit.
+        let back = forward.rotate_towards(&(forward * -1.), 1.);
+        assert!((back.angle_between(&forward) - 1.).abs() < 1e-12);
+        assert!((forward.rotate_towards(&(forward * -1.), PI).x + 2.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn component_reductions() {
+        let v = Vector3::new(2., -3., 0.5);
+        assert_eq!(v.component_sum(), -0.5);
+        assert_eq!(v.component_product_scalar(), -3.);
+        // The volume of a box from its extents.
+        assert_eq!(Vector3::new(2., 3., 4.).component_product_scalar(), 24.);
+    }
+
+    #[cfg(feature = "glam")]
+    #[test]
+    fn glam_conversions_round_trip() {
+        let v = Vector3::new(1.5, -2., 3.25);
+        let dvec: glam::DVec3 = v.into();
+        assert_eq!(dvec, glam::DVec3::new(1.5, -2., 3.25));
+        assert_close(Vector3::from(dvec), v, 0.);
+    }
+
+    #[cfg(feature = "mint")]
+    #[test]
+    fn mint_conversions_round_trip() {
+        let v = Vector3::new(1.5, -2., 3.25);
+        let vector: mint::Vector3<f64> = v.into();
+        assert_eq!(
+            vector,
+            mint::Vector3 {
+                x: 1.5,
+                y: -2.,
+                z: 3.25
+            }
+        );
+        assert_close(Vector3::from(vector), v, 0.);
+    }
+
+    #[test]
+    fn normalize_or_falls_back_for_degenerate_vectors() {
+        let up = Vector3::new(0., 1., 0.);
+        assert_close(
+            Vector3::new(0., 3., 4.).normalize_or(up),
+            Vector3::new(0., 0.6, 0.8),
+            1e-12,
+        );
+        assert_close(ZERO.normalize_or(up), up, 0.);
+        assert_close(Vector3::new(1e-300, 0., 0.).normalize_or(up), up, 0.);
+        assert_close(Vector3::new(f64::INFINITY, 0., 0.).normalize_or(up), up, 0.);
+    }
+
+    #[test]
+    fn assign_operators_take_references() {
+        let other = Vector3::new(1., 2., 3.);
+        let mut v = Vector3::new(0.5, 0.5, 0.5);
+        v += &other;
+        assert_close(v, Vector3::new(1.5, 2.5, 3.5), 0.);
+        v -= &other;
+        v -= &other;
+        assert_close(v, Vector3::new(-0.5, -1.5, -2.5), 0.);
+
+        // Generic code bounded on the reference impl accepts them too.
+        fn sum<'a, T: ops::AddAssign<&'a T> + Copy + 'a>(start: T, items: &'a [T]) -> T {
+            let mut total = start;
+            for item in items {
+                total += item;
+            }
+            total
+        }
+        assert_close(sum(ZERO, &[other, other]), Vector3::new(2., 4., 6.), 0.);
+    }
+
+    #[test]
+    fn refract_follows_snells_law() {
+        let normal = Vector3::new(0., 1., 0.);
+        let incoming = Vector3::new(1., -1., 0.).get_normalized();
+        assert_close(incoming.refract(&normal, 1.).unwrap(), incoming, 1e-12);
+
+        // Air into water: sin θt = sin θi / 1.33, bending towards the normal.
+        let eta = 1. / 1.33;
+        let bent = incoming.refract(&normal, eta).unwrap();
+        assert!((bent.magnitude() - 1.).abs() < 1e-12);
+        assert!(bent.y < 0. && bent.z == 0.);
+        assert!((bent.x - incoming.x * eta).abs() < 1e-12);
+
+        // Water into air still refracts at 45°, but not past the critical angle of about 48.8°.
+        assert!(incoming.refract(&normal, 1.33).is_some());
+        let grazing = Vector3::new(60f64.to_radians().sin(), -60f64.to_radians().cos(), 0.);
+        assert!(grazing.refract(&normal, 1.33).is_none());
+    }
+
+    #[test]
+    fn is_unit_checks_the_magnitude_within_a_tolerance() {
+        assert!(Vector3::new(0., 0.6, 0.8).is_unit(0.));
+        assert!(Vector3::new(1. + 1e-9, 0., 0.).is_unit(1e-6));
+        assert!(!Vector3::new(1. + 1e-9, 0., 0.).is_unit(1e-12));
+        assert!(!Vector3::new(1., 1., 0.).is_unit(1e-6));
+        assert!(!ZERO.is_unit(0.5));
+    }
+
+    #[test]
+    fn reflect_with_restitution_scales_only_the_normal_component() {
+        let normal = Vector3::new(0., 1., 0.);
+        let v = Vector3::new(3., -4., 1.);
+        assert_close(
+            v.reflect_with_restitution(&normal, 1.),
+            v.reflect(&normal),
+            0.,
+        );
+        assert_close(
+            v.reflect_with_restitution(&normal, 1.),
+            Vector3::new(3., 4., 1.),
+            0.,
+        );
+        assert_close(
+            v.reflect_with_restitution(&normal, 0.),
+            Vector3::new(3., 0., 1.),
+            0.,
+        );
+        assert_close(
+            v.reflect_with_restitution(&normal, 0.5),
+            Vector3::new(3., 2., 1.),
+            0.,
+        );
+
+        // The same holds for a tilted normal.
+        let tilted = Vector3::new(1., 1., 0.).get_normalized();
+        let stopped = v.reflect_with_restitution(&tilted, 0.);
+        assert!((stopped * tilted).abs() < 1e-12);
+        assert_close(stopped, v.project_onto_plane(&tilted), 1e-12);
+    }
+
+    #[test]
+    fn hermite_matches_its_endpoints_and_tangents() {
+        let (p0, m0) = (Vector3::new(0., 1., 2.), Vector3::new(3., 0., -1.));
+        let (p1, m1) = (Vector3::new(4., -2., 0.), Vector3::new(0., 5., 1.));
+        let at = |t| Vector3::hermite(&p0, &m0, &p1, &m1, t);
+        assert_close(at(0.), p0, 1e-12);
+        assert_close(at(1.), p1, 1e-12);
+
+        let h = 1e-6;
+        assert_close((at(h) - at(0.)) * (1. / h), m0, 1e-4);
+        assert_close((at(1.) - at(1. - h)) * (1. / h), m1, 1e-4);
+    }
+
+    #[test]
+    fn catmull_rom_passes_through_every_control_point() {
+        let points = [
+            Vector3::new(0., 0., 0.),
+            Vector3::new(1., 2., 0.),
+            Vector3::new(3., 1., -1.),
+            Vector3::new(4., 4., 2.),
+        ];
+        for (i, point) in points.iter().enumerate() {
+            assert_close(Vector3::catmull_rom(&points, i as f64), *point, 1e-12);
+        }
+        // Outside the range the ends are held.
+        assert_close(Vector3::catmull_rom(&points, -1.), points[0], 0.);
+        assert_close(Vector3::catmull_rom(&points, 9.), points[3], 0.);
+        // One point is a constant spline.
+        assert_close(Vector3::catmull_rom(&points[1..2], 0.5), points[1], 0.);
+
+        // Between points the curve leaves along the chord of the neighbors.
+        let h = 1e-6;
+        let tangent = (Vector3::catmull_rom(&points, 1. + h) - points[1]) * (1. / h);
+        assert_close(tangent, (points[2] - points[0]) * 0.5, 1e-4);
+    }
+
+    #[test]
+    fn nlerp_is_unit_and_close_to_slerp_for_small_angles() {
+        let a = Vector3::new(1., 0., 0.);
+        let close = Vector3::new(1., 0.1, 0.).get_normalized();
+        for t in [0.1, 0.5, 0.9] {
+            let n = a.nlerp(&close, t);
+            assert!(n.is_unit(1e-12));
+            assert_close(n, a.slerp(&close, t), 1e-4);
+        }
+
+        // Far apart, nlerp lags behind slerp away from the middle.
+        let up = Vector3::new(0., 1., 0.);
+        let n = a.nlerp(&up, 0.25);
+        assert!(n.is_unit(1e-12));
+        assert!(n.angle_between(&a) < a.slerp(&up, 0.25).angle_between(&a) - 0.05);
+    }
+
+    fn unit_box() -> Aabb {
+        Aabb::new(Vector3::new(-1., -1., -1.), Vector3::new(1., 1., 1.))
+    }
+
+    #[test]
+    fn rays_enter_and_leave_the_box_on_its_faces() {
+        let ray = Ray::new(Vector3::new(-5., 0.5, 0.), Vector3::new(2., 0., 0.));
+        let (entry, exit) = unit_box().intersect_ray(&ray).unwrap();
+        assert_eq!((entry, exit), (2., 3.));
+        assert_close(ray.point_at(entry), Vector3::new(-1., 0.5, 0.), 0.);
+        assert_close(ray.point_at(exit), Vector3::new(1., 0.5, 0.), 0.);
+
+        // Diagonally, through opposite corners.
+        let ray = Ray::new(Vector3::new(-2., -2., -2.), Vector3::new(1., 1., 1.));
+        assert_eq!(unit_box().intersect_ray(&ray), Some((1., 3.)));
+
+        // From inside, the entry is behind the origin.
+        let ray = Ray::new(Vector3::new(0., 0., 0.), Vector3::new(0., 0., -1.));
+        assert_eq!(unit_box().intersect_ray(&ray), Some((-1., 1.)));
+    }
+
+    #[test]
+    fn rays_missing_the_box_have_no_intersection() {
+        // Passing by a corner.
+        let ray = Ray::new(Vector3::new(-5., 0., 0.), Vector3::new(1., 1.5, 0.));
+        assert_eq!(unit_box().intersect_ray(&ray), None);
+        // Pointing away.
+        let ray = Ray::new(Vector3::new(3., 0., 0.), Vector3::new(1., 0., 0.));
+        assert_eq!(unit_box().intersect_ray(&ray), None);
+    }
+
+    #[test]
+    fn rays_parallel_to_a_face_do_not_divide_by_zero() {
+        // Inside the slab, along the y = 0.5 plane.
+        let ray = Ray::new(Vector3::new(0.5, 0.5, -4.), Vector3::new(0., 0., 1.));
+        assert_eq!(unit_box().intersect_ray(&ray), Some((3., 5.)));
+        // Grazing a face is still a hit.
+        let ray = Ray::new(Vector3::new(1., -4., 0.), Vector3::new(0., 1., 0.));
+        assert_eq!(unit_box().intersect_ray(&ray), Some((3., 5.)));
+        // Outside the slab it never gets in.
+        let ray = Ray::new(Vector3::new(0., 1.5, -4.), Vector3::new(0., 0., 1.));
+        assert_eq!(unit_box().intersect_ray(&ray), None);
+    }
 }
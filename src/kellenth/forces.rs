@@ -0,0 +1,1273 @@
+//! Holds the force generators that can be applied to particles, and in `rigid`
+//! the ones that can be applied to rigid bodies.
+
+pub mod rigid;
+
+use crate::kellenth::core::*;
+use crate::kellenth::noise::vector_noise;
+use crate::kellenth::particle::Particle;
+use crate::kellenth::world::{ParticleHandle, ParticleSet};
+
+/// A force generator can be asked to add a force to one or more particles.
+pub trait ParticleForceGenerator {
+    /// Calculates and updates the force applied to the given particle.
+    fn update_force(&mut self, particle: &mut Particle, duration: f64);
+}
+
+/// A force generator that acts on a particle depending on the state of another particle,
+/// like a spring connecting the two.
+/// Only the first particle is modified; register the generator once per end
+/// to apply the force to both.
+pub trait ParticlePairForceGenerator {
+    /// Calculates and updates the force applied to the given particle by the other one.
+    fn update_force(&mut self, particle: &mut Particle, other: &Particle, duration: f64);
+}
+
+/// Adapts any closure into a force generator, for one-off force laws
+/// that don't deserve their own type.
+pub struct ParticleForceClosure<F>
+where
+    F: FnMut(&mut Particle, f64),
+{
+    /// Holds the closure called on every force update.
+    closure: F,
+}
+
+impl<F> ParticleForceClosure<F>
+where
+    F: FnMut(&mut Particle, f64),
+{
+    /// Constructor
+    pub fn new(closure: F) -> Self {
+        Self { closure }
+    }
+}
+
+impl<F> ParticleForceGenerator for ParticleForceClosure<F>
+where
+    F: FnMut(&mut Particle, f64),
+{
+    fn update_force(&mut self, particle: &mut Particle, duration: f64) {
+        (self.closure)(particle, duration);
+    }
+}
+
+/// Identifies a registration made in a `ParticleForceRegistry`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ForceRegistrationId(u64);
+
+/// The kinds of force generator a registration can hold.
+enum RegisteredGenerator {
+    Single(Box<dyn ParticleForceGenerator>),
+    Pair {
+        other: ParticleHandle,
+        generator: Box<dyn ParticlePairForceGenerator>,
+    },
+}
+
+/// Keeps track of one force generator and the particle it applies to.
+struct ParticleForceRegistration {
+    id: ForceRegistrationId,
+    particle: ParticleHandle,
+    generator: RegisteredGenerator,
+    enabled: bool,
+}
+
+/// Describes a registration, for debugging.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ForceRegistrationInfo {
+    /// Identifies the registration.
+    pub id: ForceRegistrationId,
+
+    /// Holds the particle the force is applied to.
+    pub particle: ParticleHandle,
+
+    /// Holds the other particle, for pair generators.
+    pub other: Option<ParticleHandle>,
+
+    /// Holds whether the generator is currently applied.
+    pub enabled: bool,
+}
+
+/// Holds all the force generators and the particles that they apply to.
+/// Particles are referred to by their handle in the set given to `update_forces`.
+#[derive(Default)]
+pub struct ParticleForceRegistry {
+    /// Holds the list of registrations.
+    registrations: Vec<ParticleForceRegistration>,
+
+    /// Holds the id given to the next registration.
+    next_id: u64,
+}
+
+impl ParticleForceRegistry {
+    /// Constructor
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the given force generator to apply to the given particle.
+    pub fn add(
+        &mut self,
+        particle: ParticleHandle,
+        generator: Box<dyn ParticleForceGenerator>,
+    ) -> ForceRegistrationId {
+        self.register(particle, RegisteredGenerator::Single(generator))
+    }
+
+    /// Registers the given pair force generator to apply to `particle`, depending on `other`.
+    pub fn add_pair(
+        &mut self,
+        particle: ParticleHandle,
+        other: ParticleHandle,
+        generator: Box<dyn ParticlePairForceGenerator>,
+    ) -> ForceRegistrationId {
+        self.register(particle, RegisteredGenerator::Pair { other, generator })
+    }
+
+    fn register(
+        &mut self,
+        particle: ParticleHandle,
+        generator: RegisteredGenerator,
+    ) -> ForceRegistrationId {
+        let id = ForceRegistrationId(self.next_id);
+        self.next_id += 1;
+        self.registrations.push(ParticleForceRegistration {
+            id,
+            particle,
+            generator,
+            enabled: true,
+        });
+        id
+    }
+
+    /// Registers the given closure as a force generator for the given particle.
+    pub fn add_fn<F>(&mut self, particle: ParticleHandle, closure: F) -> ForceRegistrationId
+    where
+        F: FnMut(&mut Particle, f64) + 'static,
+    {
+        self.add(particle, Box::new(ParticleForceClosure::new(closure)))
+    }
+
+    /// Removes the given registration from the registry.
+    /// Returns false if the registration was not found.
+    pub fn remove(&mut self, id: ForceRegistrationId) -> bool {
+        let length = self.registrations.len();
+        self.registrations
+            .retain(|registration| registration.id != id);
+        self.registrations.len() != length
+    }
+
+    /// Removes every registration applying to, or depending on, the given particle.
+    /// Call it when the particle is destroyed; `ParticleWorld::remove_particle` does so itself.
+    /// Returns the number of registrations removed.
+    pub fn remove_all_for_particle(&mut self, particle: ParticleHandle) -> usize {
+        let length = self.registrations.len();
+        self.registrations.retain(|registration| {
+            let other = match registration.generator {
+                RegisteredGenerator::Single(_) => None,
+                RegisteredGenerator::Pair { other, .. } => Some(other),
+            };
+            registration.particle != particle && other != Some(particle)
+        });
+        length - self.registrations.len()
+    }
+
+    /// Enables or disables the given registration. A disabled generator keeps its
+    /// configuration but is skipped by `update_forces`.
+    /// Returns false if the registration was not found.
+    pub fn set_enabled(&mut self, id: ForceRegistrationId, enabled: bool) -> bool {
+        match self
+            .registrations
+            .iter_mut()
+            .find(|registration| registration.id == id)
+        {
+            Some(registration) => {
+                registration.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns whether the given registration is enabled, or `None` if it was not found.
+    pub fn is_enabled(&self, id: ForceRegistrationId) -> Option<bool> {
+        self.registrations
+            .iter()
+            .find(|registration| registration.id == id)
+            .map(|registration| registration.enabled)
+    }
+
+    /// Returns the number of registrations.
+    pub fn len(&self) -> usize {
+        self.registrations.len()
+    }
+
+    /// Returns true if there are no registrations.
+    pub fn is_empty(&self) -> bool {
+        self.registrations.is_empty()
+    }
+
+    /// Iterates over the registrations in the order they are applied.
+    pub fn iter(&self) -> impl Iterator<Item = ForceRegistrationInfo> + '_ {
+        self.registrations
+            .iter()
+            .map(|registration| ForceRegistrationInfo {
+                id: registration.id,
+                particle: registration.particle,
+                other: match registration.generator {
+                    RegisteredGenerator::Single(_) => None,
+                    RegisteredGenerator::Pair { other, .. } => Some(other),
+                },
+                enabled: registration.enabled,
+            })
+    }
+
+    /// Clears all the registrations from the registry.
+    /// This will not delete the particles or the force generators themselves.
+    pub fn clear(&mut self) {
+        self.registrations.clear();
+    }
+
+    /// Calls all the force generators to update the forces of their corresponding particles.
+    /// Disabled registrations are skipped, and so are registrations referring to particles
+    /// that are gone: handles never come back to life, so they do nothing until purged
+    /// with `remove_all_for_particle`.
+    /// Pair generators see the other particle as it was before this update.
+    /// Returns the number of registrations applied.
+    pub fn update_forces(&mut self, particles: &mut ParticleSet, duration: f64) -> usize {
+        let mut applied = 0;
+        for registration in &mut self.registrations {
+            if !registration.enabled {
+                continue;
+            }
+            match &mut registration.generator {
+                RegisteredGenerator::Single(generator) => {
+                    if let Some(particle) = particles.get_mut(registration.particle) {
+                        generator.update_force(particle, duration);
+                        applied += 1;
+                    }
+                }
+                RegisteredGenerator::Pair { other, generator } => {
+                    // Particles are cheap to copy, which sidesteps borrowing two
+                    // particles of the set at once.
+                    let other = match particles.get(*other) {
+                        Some(other) => *other,
+                        None => continue,
+                    };
+                    if let Some(particle) = particles.get_mut(registration.particle) {
+                        generator.update_force(particle, &other, duration);
+                        applied += 1;
+                    }
+                }
+            }
+        }
+        applied
+    }
+}
+
+/// A force generator that applies a gravitational force, the same acceleration for
+/// every particle whatever its mass. Particles with infinite mass are skipped.
+#[derive(Debug, Clone, Copy)]
+pub struct ParticleGravity {
+    /// Holds the acceleration due to gravity.
+    pub gravity: Vector3,
+}
+
+impl ParticleGravity {
+    /// Constructor
+    pub fn new(gravity: Vector3) -> Self {
+        Self { gravity }
+    }
+}
+
+impl ParticleForceGenerator for ParticleGravity {
+    fn update_force(&mut self, particle: &mut Particle, _duration: f64) {
+        // Check that we do not have infinite mass.
+        if !particle.has_finite_mass() {
+            return;
+        }
+
+        // Apply the mass-scaled force to the particle.
+        particle.add_force(self.gravity * particle.get_mass());
+    }
+}
+
+/// A force generator that fakes a stiff spring force, where one end is
+/// attached to a fixed point in space.
+///
+/// Instead of applying Hooke's law, which explodes under Euler integration
+/// when the spring is stiff, it predicts where the damped harmonic motion
+/// would take the particle by the end of the frame and applies the force
+/// needed to get it there. Any damping works: critically damped and overdamped
+/// springs creep back to the anchor instead of oscillating.
+///
+/// The particle reaches the predicted position, but leaves the frame with its average
+/// velocity over the frame rather than the predicted one, so even a critically damped
+/// spring can overshoot the anchor by a few percent.
+#[derive(Debug, Clone, Copy)]
+pub struct ParticleFakeSpring {
+    /// The location of the anchored end of the spring.
+    pub anchor: Vector3,
+
+    /// Holds the spring constant.
+    pub spring_constant: f64,
+
+    /// Holds the damping on the oscillation of the spring.
+    pub damping: f64,
+}
+
+impl ParticleFakeSpring {
+    /// Constructor
+    pub fn new(anchor: Vector3, spring_constant: f64, damping: f64) -> Self {
+        Self {
+            anchor,
+            spring_constant,
+            damping,
+        }
+    }
+}
+
+impl ParticleForceGenerator for ParticleFakeSpring {
+    fn update_force(&mut self, particle: &mut Particle, duration: f64) {
+        // Check that we do not have infinite mass.
+        if !particle.has_finite_mass() {
+            return;
+        }
+
+        // Calculate the relative position of the particle to the anchor.
+        let position = particle.position - self.anchor;
+
+        // Predict where the damped harmonic motion takes the particle by the end of
+        // the frame: `e^(-d·t/2) · (x·C + (v + x·d/2)·S)`, where `C` and `S` depend on
+        // whether the spring oscillates (underdamped) or not.
+        let decay = f64::exp(-0.5 * duration * self.damping);
+        let gamma_squared = 4. * self.spring_constant - self.damping * self.damping;
+        let (cosine, sine) = if gamma_squared > 0. {
+            // Underdamped: it oscillates with frequency `gamma`.
+            let gamma = 0.5 * gamma_squared.sqrt();
+            (
+                decay * f64::cos(gamma * duration),
+                decay * f64::sin(gamma * duration) / gamma,
+            )
+        } else {
+            // Critically damped or overdamped: it creeps back without oscillating,
+            // mixing two decaying exponentials `e^((-d/2 ± s)·t)`. The decay is folded
+            // into the exponents so large dampings don't overflow `cosh` and `sinh`.
+            let s = 0.5 * (-gamma_squared).sqrt();
+            let slow = f64::exp((s - 0.5 * self.damping) * duration);
+            let fast = f64::exp(-(s + 0.5 * self.damping) * duration);
+            let sine = if s * duration < 1e-4 {
+                // Near critical damping `sinh(s·t) / s` cancels badly, and is 0 / 0
+                // at it, so use its series instead.
+                decay * duration * (1. + s * s * duration * duration / 6.)
+            } else {
+                0.5 * (slow - fast) / s
+            };
+            (0.5 * (slow + fast), sine)
+        };
+        let target =
+            position * cosine + (particle.velocity + position * (0.5 * self.damping)) * sine;
+
+        // Calculate the resulting acceleration and therefore the force.
+        let acceleration = (target - position) * (1. / (duration * duration))
+            - particle.velocity * (1. / duration);
+        particle.add_force(acceleration * particle.get_mass());
+    }
+}
+
+/// A single sine component of the built-in gust noise.
+#[derive(Debug, Clone, Copy)]
+pub struct GustWave {
+    /// Frequency of the wave, in cycles per second.
+    pub frequency: f64,
+
+    /// Amplitude of the wave, added on top of the unit base multiplier.
+    pub amplitude: f64,
+
+    /// Phase offset of the wave, in radians.
+    pub phase: f64,
+}
+
+impl GustWave {
+    /// Constructor
+    pub fn new(frequency: f64, amplitude: f64, phase: f64) -> Self {
+        Self {
+            frequency,
+            amplitude,
+            phase,
+        }
+    }
+}
+
+/// Describes how the strength of the wind varies over time.
+pub enum Gust {
+    /// The wind blows with a constant strength.
+    Steady,
+
+    /// The strength multiplier is computed by the given function of time.
+    Callback(Box<dyn Fn(f64) -> f64>),
+
+    /// The strength multiplier is `1 + sum(amplitude * sin(2 * PI * frequency * t + phase))`,
+    /// a cheap smooth noise.
+    Waves(Vec<GustWave>),
+}
+
+impl Gust {
+    /// Returns the strength multiplier at the given time.
+    pub fn strength(&self, time: f64) -> f64 {
+        match self {
+            Gust::Steady => 1.,
+            Gust::Callback(callback) => callback(time),
+            Gust::Waves(waves) => {
+                1. + waves
+                    .iter()
+                    .map(|wave| {
+                        wave.amplitude
+                            * f64::sin(
+                                2. * std::f64::consts::PI * wave.frequency * time + wave.phase,
+                            )
+                    })
+                    .sum::<f64>()
+            }
+        }
+    }
+}
+
+/// A force generator that pushes particles towards the velocity of the wind.
+///
+/// The force is proportional to the velocity of the wind relative to the
+/// particle, so a particle already moving with the wind feels nothing.
+/// Each call to `update_force` advances the gust clock by the frame duration,
+/// so every particle should be given its own instance.
+pub struct ParticleWind {
+    /// Holds the velocity of the wind at unit gust strength.
+    pub wind_velocity: Vector3,
+
+    /// Holds the coupling coefficient between the relative velocity and the force.
+    pub coupling: f64,
+
+    /// Holds how the wind strength varies over time.
+    pub gust: Gust,
+
+    /// Holds the time elapsed on the gust clock.
+    time: f64,
+}
+
+impl ParticleWind {
+    /// Constructor
+    pub fn new(wind_velocity: Vector3, coupling: f64) -> Self {
+        Self {
+            wind_velocity,
+            coupling,
+            gust: Gust::Steady,
+            time: 0.,
+        }
+    }
+
+    /// Makes the wind strength follow the given function of time.
+    pub fn with_gust_callback(mut self, callback: impl Fn(f64) -> f64 + 'static) -> Self {
+        self.gust = Gust::Callback(Box::new(callback));
+        self
+    }
+
+    /// Makes the wind strength follow a sum of sines.
+    pub fn with_gust_waves(mut self, waves: Vec<GustWave>) -> Self {
+        self.gust = Gust::Waves(waves);
+        self
+    }
+
+    /// Returns the time elapsed on the gust clock.
+    pub fn get_time(&self) -> f64 {
+        self.time
+    }
+
+    /// Advances the gust clock by the given amount.
+    pub fn advance_time(&mut self, duration: f64) {
+        self.time += duration;
+    }
+
+    /// Returns the current gust strength multiplier.
+    pub fn gust_strength(&self) -> f64 {
+        self.gust.strength(self.time)
+    }
+
+    /// Returns the current velocity of the wind, gusts included.
+    pub fn current_wind_velocity(&self) -> Vector3 {
+        self.wind_velocity * self.gust_strength()
+    }
+}
+
+impl ParticleForceGenerator for ParticleWind {
+    fn update_force(&mut self, particle: &mut Particle, duration: f64) {
+        self.advance_time(duration);
+
+        let relative_velocity = self.current_wind_velocity() - particle.velocity;
+        particle.add_force(relative_velocity * self.coupling);
+    }
+}
+
+/// A force generator that attracts particles towards a point with an
+/// inverse-square falloff, like a planet or a black hole.
+///
+/// The force has magnitude `μ·m / (r² + ε²)`, where `ε` is the softening
+/// length that keeps the force bounded when a particle passes through the center.
+#[derive(Debug, Clone, Copy)]
+pub struct ParticleGravityWell {
+    /// Holds the point the particles are attracted to.
+    pub center: Vector3,
+
+    /// Holds the gravitational parameter `μ` (the gravitational constant times the attracting mass).
+    pub gravitational_parameter: f64,
+
+    /// Holds the softening length `ε`.
+    pub softening: f64,
+}
+
+impl ParticleGravityWell {
+    /// Constructor
+    pub fn new(center: Vector3, gravitational_parameter: f64, softening: f64) -> Self {
+        Self {
+            center,
+            gravitational_parameter,
+            softening,
+        }
+    }
+}
+
+impl ParticleForceGenerator for ParticleGravityWell {
+    fn update_force(&mut self, particle: &mut Particle, _duration: f64) {
+        // Check that we do not have infinite mass.
+        if !particle.has_finite_mass() {
+            return;
+        }
+
+        let offset = self.center - particle.position;
+        let magnitude = self.gravitational_parameter * particle.get_mass()
+            / (offset.square_magnitude() + self.softening * self.softening);
+        particle.add_force(offset.get_normalized() * magnitude);
+    }
+}
+
+/// Accumulates the mutual gravitational attraction between every pair of particles.
+///
+/// Each pair attracts with `g·m1·m2 / (r² + ε²)`. Runs in O(n²), so it is only meant
+/// for small scenes. Particles with infinite mass are skipped entirely.
+pub fn nbody_gravity(particles: &mut [Particle], g: f64, softening: f64) {
+    for i in 0..particles.len() {
+        if !particles[i].has_finite_mass() {
+            continue;
+        }
+        for j in (i + 1)..particles.len() {
+            if !particles[j].has_finite_mass() {
+                continue;
+            }
+
+            let offset = particles[j].position - particles[i].position;
+            let magnitude = g * particles[i].get_mass() * particles[j].get_mass()
+                / (offset.square_magnitude() + softening * softening);
+            let force = offset.get_normalized() * magnitude;
+
+            particles[i].add_force(force);
+            particles[j].add_force(force * -1.);
+        }
+    }
+}
+
+/// A force generator that swirls particles around an axis.
+///
+/// The force is tangential to the circle around the axis passing through the
+/// particle, and decays as `strength / (1 + (r / falloff_radius)²)` with the
+/// distance `r` from the axis. Particles on the axis receive no force.
+#[derive(Debug, Clone, Copy)]
+pub struct ParticleVortex {
+    /// Holds a point on the axis of the vortex.
+    pub axis_point: Vector3,
+
+    /// Holds the direction of the axis. It doesn't need to be normalized.
+    /// The particles spin counter-clockwise when looking against this direction.
+    pub axis_dir: Vector3,
+
+    /// Holds the magnitude of the force right next to the axis.
+    pub strength: f64,
+
+    /// Holds the distance from the axis at which the force has halved.
+    pub falloff_radius: f64,
+}
+
+impl ParticleVortex {
+    /// Constructor
+    pub fn new(axis_point: Vector3, axis_dir: Vector3, strength: f64, falloff_radius: f64) -> Self {
+        Self {
+            axis_point,
+            axis_dir,
+            strength,
+            falloff_radius,
+        }
+    }
+
+    /// Returns the force the vortex applies at the given position.
+    pub fn force_at(&self, position: Vector3) -> Vector3 {
+        let axis = self.axis_dir.get_normalized();
+        let offset = position - self.axis_point;
+
+        // Remove the component along the axis to get the radial vector.
+        let radial = offset - axis * (offset * axis);
+        let distance = radial.magnitude();
+        if distance == 0. {
+            return Vector3::new(0., 0., 0.);
+        }
+
+        let tangent = axis % (radial * (1. / distance));
+        let ratio = distance / self.falloff_radius;
+        tangent * (self.strength / (1. + ratio * ratio))
+    }
+}
+
+impl ParticleForceGenerator for ParticleVortex {
+    fn update_force(&mut self, particle: &mut Particle, _duration: f64) {
+        particle.add_force(self.force_at(particle.position));
+    }
+}
+
+/// A force generator that pushes particles around with a pseudo-random force
+/// sampled from a smooth noise field.
+///
+/// The field is fully determined by the seed, so runs are reproducible.
+#[derive(Debug, Clone, Copy)]
+pub struct ParticleTurbulence {
+    /// Holds the seed of the noise field.
+    pub seed: u64,
+
+    /// Holds the maximum magnitude of each component of the force.
+    pub strength: f64,
+
+    /// Holds the size of the features of the field, in world units.
+    pub scale: f64,
+}
+
+impl ParticleTurbulence {
+    /// Constructor
+    pub fn new(seed: u64, strength: f64, scale: f64) -> Self {
+        assert!(scale > 0.);
+        Self {
+            seed,
+            strength,
+            scale,
+        }
+    }
+
+    /// Returns the force the field applies at the given position.
+    pub fn force_at(&self, position: Vector3) -> Vector3 {
+        vector_noise(self.seed, position * (1. / self.scale)) * self.strength
+    }
+}
+
+impl ParticleForceGenerator for ParticleTurbulence {
+    fn update_force(&mut self, particle: &mut Particle, _duration: f64) {
+        particle.add_force(self.force_at(particle.position));
+    }
+}
+
+/// A force generator that pushes particles with a constant wind force, perturbed by a
+/// noise field so particles in different places sway differently, e.g. for foliage.
+///
+/// Unlike `ParticleWind`, the force doesn't depend on the velocity of the particle.
+/// The perturbation only depends on the seed and the position, so runs are reproducible.
+#[derive(Debug, Clone, Copy)]
+pub struct ParticleWindField {
+    /// Holds the force applied everywhere.
+    pub base: Vector3,
+
+    /// Holds the maximum magnitude of each component of the perturbation.
+    pub turbulence: f64,
+
+    /// Holds the seed of the noise field.
+    pub seed: u64,
+}
+
+impl ParticleWindField {
+    /// Constructor
+    pub fn new(base: Vector3, turbulence: f64, seed: u64) -> Self {
+        Self {
+            base,
+            turbulence,
+            seed,
+        }
+    }
+
+    /// Returns the force the wind applies at the given position.
+    /// Without turbulence this is exactly `base`.
+    pub fn force_at(&self, position: Vector3) -> Vector3 {
+        if self.turbulence == 0. {
+            return self.base;
+        }
+        self.base + vector_noise(self.seed, position) * self.turbulence
+    }
+}
+
+impl ParticleForceGenerator for ParticleWindField {
+    fn update_force(&mut self, particle: &mut Particle, _duration: f64) {
+        particle.add_force(self.force_at(particle.position));
+    }
+}
+
+/// A force generator that applies the Lorentz force `q(E + v × B)` to charged particles.
+///
+/// Every particle it applies to carries the same charge. The magnetic part is
+/// always perpendicular to the velocity, so it bends the path without doing work.
+#[derive(Debug, Clone, Copy)]
+pub struct ParticleElectromagnetic {
+    /// Holds the electric charge of each particle.
+    pub charge_per_particle: f64,
+
+    /// Holds the electric field `E`.
+    pub electric_field: Vector3,
+
+    /// Holds the magnetic field `B`.
+    pub magnetic_field: Vector3,
+}
+
+impl ParticleElectromagnetic {
+    /// Constructor
+    pub fn new(charge_per_particle: f64, electric_field: Vector3, magnetic_field: Vector3) -> Self {
+        Self {
+            charge_per_particle,
+            electric_field,
+            magnetic_field,
+        }
+    }
+
+    /// Sets the electric field, for time-varying fields.
+    pub fn set_electric_field(&mut self, electric_field: Vector3) {
+        self.electric_field = electric_field;
+    }
+
+    /// Sets the magnetic field, for time-varying fields.
+    pub fn set_magnetic_field(&mut self, magnetic_field: Vector3) {
+        self.magnetic_field = magnetic_field;
+    }
+}
+
+impl ParticleForceGenerator for ParticleElectromagnetic {
+    fn update_force(&mut self, particle: &mut Particle, _duration: f64) {
+        let force = self.electric_field + particle.velocity % self.magnetic_field;
+        particle.add_force(force * self.charge_per_particle);
+    }
+}
+
+/// A force generator that applies a spring force between two particles.
+#[derive(Debug, Clone, Copy)]
+pub struct ParticleSpring {
+    /// Holds the spring constant.
+    pub spring_constant: f64,
+
+    /// Holds the rest length of the spring.
+    pub rest_length: f64,
+}
+
+impl ParticleSpring {
+    /// Constructor
+    pub fn new(spring_constant: f64, rest_length: f64) -> Self {
+        Self {
+            spring_constant,
+            rest_length,
+        }
+    }
+}
+
+impl ParticlePairForceGenerator for ParticleSpring {
+    fn update_force(&mut self, particle: &mut Particle, other: &Particle, _duration: f64) {
+        particle.add_force(damped_spring_force(
+            particle.position - other.position,
+            Vector3::new(0., 0., 0.),
+            self.spring_constant,
+            self.rest_length,
+            0.,
+        ));
+    }
+}
+
+/// Returns the Hooke force of a damped spring acting on an end at `offset` from the other
+/// end (`offset` points from the other end to this one), moving at `relative_velocity`.
+fn damped_spring_force(
+    offset: Vector3,
+    relative_velocity: Vector3,
+    spring_constant: f64,
+    rest_length: f64,
+    damping_coefficient: f64,
+) -> Vector3 {
+    let length = offset.magnitude();
+    if length == 0. {
+        return Vector3::new(0., 0., 0.);
+    }
+    let direction = offset * (1. / length);
+
+    // Hooke's law plus a damping term along the spring axis only.
+    let magnitude = -spring_constant * (length - rest_length)
+        - damping_coefficient * (relative_velocity * direction);
+    direction * magnitude
+}
+
+/// A force generator that applies a damped spring force between two particles.
+/// The damping only opposes the relative velocity along the spring, so sideways
+/// motion is left untouched.
+#[derive(Debug, Clone, Copy)]
+pub struct ParticleDampedSpring {
+    /// Holds the spring constant.
+    pub spring_constant: f64,
+
+    /// Holds the rest length of the spring.
+    pub rest_length: f64,
+
+    /// Holds the damping coefficient `c` of the force `-c (v_rel · d) d`.
+    pub damping_coefficient: f64,
+}
+
+impl ParticleDampedSpring {
+    /// Constructor
+    pub fn new(spring_constant: f64, rest_length: f64, damping_coefficient: f64) -> Self {
+        Self {
+            spring_constant,
+            rest_length,
+            damping_coefficient,
+        }
+    }
+
+    /// Constructs a critically damped spring, with `c = 2√(k·m)`.
+    /// When both ends are movable, pass the reduced mass `m1·m2 / (m1 + m2)`.
+    pub fn critically_damped(mass: f64, spring_constant: f64, rest_length: f64) -> Self {
+        Self::new(
+            spring_constant,
+            rest_length,
+            2. * f64::sqrt(spring_constant * mass),
+        )
+    }
+}
+
+impl ParticlePairForceGenerator for ParticleDampedSpring {
+    fn update_force(&mut self, particle: &mut Particle, other: &Particle, _duration: f64) {
+        particle.add_force(damped_spring_force(
+            particle.position - other.position,
+            particle.velocity - other.velocity,
+            self.spring_constant,
+            self.rest_length,
+            self.damping_coefficient,
+        ));
+    }
+}
+
+/// A force generator that applies a damped spring force, where one end is
+/// attached to a fixed point in space.
+#[derive(Debug, Clone, Copy)]
+pub struct ParticleAnchoredDampedSpring {
+    /// The location of the anchored end of the spring.
+    pub anchor: Vector3,
+
+    /// Holds the spring constant.
+    pub spring_constant: f64,
+
+    /// Holds the rest length of the spring.
+    pub rest_length: f64,
+
+    /// Holds the damping coefficient `c` of the force `-c (v · d) d`.
+    pub damping_coefficient: f64,
+}
+
+impl ParticleAnchoredDampedSpring {
+    /// Constructor
+    pub fn new(
+        anchor: Vector3,
+        spring_constant: f64,
+        rest_length: f64,
+        damping_coefficient: f64,
+    ) -> Self {
+        Self {
+            anchor,
+            spring_constant,
+            rest_length,
+            damping_coefficient,
+        }
+    }
+
+    /// Constructs a critically damped spring for a particle of the given mass, with `c = 2√(k·m)`.
+    pub fn critically_damped(
+        anchor: Vector3,
+        mass: f64,
+        spring_constant: f64,
+        rest_length: f64,
+    ) -> Self {
+        Self::new(
+            anchor,
+            spring_constant,
+            rest_length,
+            2. * f64::sqrt(spring_constant * mass),
+        )
+    }
+}
+
+impl ParticleForceGenerator for ParticleAnchoredDampedSpring {
+    fn update_force(&mut self, particle: &mut Particle, _duration: f64) {
+        particle.add_force(damped_spring_force(
+            particle.position - self.anchor,
+            particle.velocity,
+            self.spring_constant,
+            self.rest_length,
+            self.damping_coefficient,
+        ));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ZERO: Vector3 = Vector3::new(0., 0., 0.);
+
+    /// Returns an undamped particle with the given mass at rest at the given position.
+    fn particle_at(position: Vector3, mass: f64) -> Particle {
+        let mut particle = Particle::new(position, ZERO, ZERO, 1.);
+        particle.set_mass(mass);
+        particle
+    }
+
+    /// Steps the particle under the given generator and returns its positions along the way.
+    fn run(
+        generator: &mut dyn ParticleForceGenerator,
+        particle: &mut Particle,
+        duration: f64,
+        steps: usize,
+    ) -> Vec<Vector3> {
+        (0..steps)
+            .map(|_| {
+                generator.update_force(particle, duration);
+                particle.integrate(duration);
+                particle.position
+            })
+            .collect()
+    }
+
+    #[test]
+    fn fake_spring_stays_stable_where_hooke_spring_explodes() {
+        let start = Vector3::new(1., 0., 0.);
+        let (spring_constant, duration) = (1e4, 0.05);
+
+        let mut hooke = ParticleAnchoredDampedSpring::new(ZERO, spring_constant, 0., 0.);
+        let mut particle = particle_at(start, 1.);
+        run(&mut hooke, &mut particle, duration, 100);
+        let distance = particle.position.magnitude();
+        assert!(distance > 1e6 || distance.is_nan());
+
+        let mut fake = ParticleFakeSpring::new(ZERO, spring_constant, 2.);
+        let mut particle = particle_at(start, 1.);
+        let positions = run(&mut fake, &mut particle, duration, 200);
+        assert!(positions.iter().all(|p| p.magnitude() <= 1.));
+        assert!(particle.position.magnitude() < 1e-3);
+    }
+
+    #[test]
+    fn fake_spring_creeps_back_when_overdamped_or_critically_damped() {
+        for damping in [20., 50., 1e3] {
+            let mut fake = ParticleFakeSpring::new(ZERO, 100., damping);
+            let mut particle = particle_at(Vector3::new(1., 0., 0.), 2.);
+            let positions = run(&mut fake, &mut particle, 0.02, 2000);
+            // It goes back towards the anchor, overshooting by no more than the
+            // frame-averaged velocity carries it past, a few percent.
+            assert!(positions.iter().all(|p| p.x <= 1. && p.x > -0.05));
+            assert!(particle.position.magnitude() < 0.05, "damping {damping}");
+        }
+    }
+
+    #[test]
+    fn fake_spring_ignores_immovable_particles() {
+        let mut fake = ParticleFakeSpring::new(ZERO, 100., 1.);
+        let mut particle = Particle::new(Vector3::new(1., 0., 0.), ZERO, ZERO, 1.);
+        let key = particle.bit_key();
+        fake.update_force(&mut particle, 0.1);
+        assert_eq!(particle.bit_key(), key);
+    }
+
+    #[test]
+    fn wind_drags_particles_up_to_its_velocity() {
+        let wind_velocity = Vector3::new(3., 0., -1.);
+        let mut wind = ParticleWind::new(wind_velocity, 2.);
+        let mut particle = particle_at(ZERO, 1.);
+        run(&mut wind, &mut particle, 0.01, 1000);
+        assert!((particle.velocity - wind_velocity).magnitude() < 1e-6);
+
+        // Moving with the wind, the particle feels nothing.
+        let mut particle = Particle::new(ZERO, wind_velocity, ZERO, 1.);
+        let key = particle.bit_key();
+        wind.update_force(&mut particle, 0.01);
+        assert_eq!(particle.bit_key(), key);
+    }
+
+    #[test]
+    fn gust_strength_follows_its_envelope() {
+        let mut wind = ParticleWind::new(Vector3::new(1., 0., 0.), 1.).with_gust_waves(vec![
+            GustWave::new(0.5, 0.3, 0.),
+            GustWave::new(2., 0.1, 1.),
+        ]);
+        let (mut lowest, mut highest) = (f64::INFINITY, f64::NEG_INFINITY);
+        for _ in 0..4000 {
+            wind.advance_time(0.001);
+            let strength = wind.gust_strength();
+            lowest = lowest.min(strength);
+            highest = highest.max(strength);
+            assert!((wind.current_wind_velocity().x - strength).abs() < 1e-12);
+        }
+        assert!(lowest >= 0.6 && highest <= 1.4);
+        assert!(lowest < 0.8 && highest > 1.2);
+        assert!((wind.get_time() - 4.).abs() < 1e-9);
+
+        let mut wind =
+            ParticleWind::new(Vector3::new(1., 0., 0.), 1.).with_gust_callback(|t| 2. * t);
+        let mut particle = particle_at(ZERO, 1.);
+        wind.update_force(&mut particle, 0.25);
+        assert_eq!(wind.gust_strength(), 0.5);
+    }
+
+    #[test]
+    fn two_body_circular_orbit_keeps_its_radius() {
+        // Two unit masses two apart circle their common center at radius 1, where
+        // the attraction of 1/4 needs a speed of 1/2.
+        let mut particles = [
+            Particle::new(
+                Vector3::new(1., 0., 0.),
+                Vector3::new(0., 0.5, 0.),
+                ZERO,
+                1.,
+            ),
+            Particle::new(
+                Vector3::new(-1., 0., 0.),
+                Vector3::new(0., -0.5, 0.),
+                ZERO,
+                1.,
+            ),
+        ];
+        let period = 4. * std::f64::consts::PI;
+        let duration = 1e-3;
+        for _ in 0..(10. * period / duration) as usize {
+            nbody_gravity(&mut particles, 1., 0.);
+            particles.iter_mut().for_each(|p| p.integrate(duration));
+            let separation = (particles[0].position - particles[1].position).magnitude();
+            assert!((separation - 2.).abs() < 0.01, "separation {separation}");
+        }
+        let center = (particles[0].position + particles[1].position) * 0.5;
+        assert!(center.magnitude() < 1e-9);
+    }
+
+    #[test]
+    fn gravity_well_softening_bounds_the_force() {
+        let mut well = ParticleGravityWell::new(ZERO, 10., 0.5);
+        // The acceleration is at most μ / ε², however close the particle gets.
+        for offset in [1., 0.1, 1e-6] {
+            let mut particle = particle_at(Vector3::new(offset, 0., 0.), 2.);
+            well.update_force(&mut particle, 0.1);
+            particle.integrate(1.);
+            assert!(particle.velocity.x < 0. && particle.velocity.x >= -40.);
+        }
+        let mut particle = particle_at(ZERO, 2.);
+        well.update_force(&mut particle, 0.1);
+        particle.integrate(1.);
+        assert!(particle.velocity.magnitude() == 0.);
+    }
+
+    #[test]
+    fn closures_run_on_every_update_until_removed() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        let mut particles = ParticleSet::new();
+        let handle = particles.insert(particle_at(ZERO, 1.));
+        let mut registry = ParticleForceRegistry::new();
+        let calls = Rc::new(Cell::new(0));
+        let counter = calls.clone();
+        let id = registry.add_fn(handle, move |particle, duration| {
+            counter.set(counter.get() + 1);
+            particle.add_force(Vector3::new(0., duration * 10., 0.));
+        });
+        registry.add(handle, Box::new(ParticleGravityWell::new(ZERO, 1., 1.)));
+
+        for _ in 0..3 {
+            assert_eq!(registry.update_forces(&mut particles, 0.1), 2);
+        }
+        assert_eq!(calls.get(), 3);
+        particles.get_mut(handle).unwrap().integrate(1.);
+        assert!((particles[handle].velocity.y - 3.).abs() < 1e-12);
+
+        assert!(registry.remove(id));
+        assert!(!registry.remove(id));
+        assert_eq!(registry.update_forces(&mut particles, 0.1), 1);
+        assert_eq!(calls.get(), 3);
+    }
+
+    #[test]
+    fn vortex_force_is_tangential_and_zero_on_the_axis() {
+        let axis_point = Vector3::new(1., -2., 0.5);
+        let axis_dir = Vector3::new(0.3, 1., -0.2);
+        let vortex = ParticleVortex::new(axis_point, axis_dir, 5., 2.);
+        let axis = axis_dir.get_normalized();
+        let mut rng = crate::kellenth::noise::Random::new(7);
+        for _ in 0..100 {
+            let position = axis_point + rng.in_sphere(10.);
+            let offset = position - axis_point;
+            let radial = offset - axis * (offset * axis);
+            let force = vortex.force_at(position);
+            assert!(force.magnitude() > 0. && force.magnitude() <= 5.);
+            assert!((force * axis).abs() < 1e-9);
+            assert!((force * radial).abs() < 1e-9);
+        }
+
+        assert_eq!(vortex.force_at(axis_point).magnitude(), 0.);
+        let vortex = ParticleVortex::new(ZERO, Vector3::new(0., 0., 2.), 5., 2.);
+        let on_axis = vortex.force_at(Vector3::new(0., 0., -3.));
+        assert!(on_axis.is_finite() && on_axis.magnitude() == 0.);
+    }
+
+    #[test]
+    fn turbulence_is_deterministic_for_a_seed() {
+        let a = ParticleTurbulence::new(42, 3., 1.5);
+        let b = ParticleTurbulence::new(42, 3., 1.5);
+        let other = ParticleTurbulence::new(43, 3., 1.5);
+        let mut rng = crate::kellenth::noise::Random::new(1);
+        let mut differs = false;
+        for _ in 0..100 {
+            let position = rng.in_sphere(20.);
+            let force = a.force_at(position);
+            assert_eq!(force.manhattan_distance(&b.force_at(position)), 0.);
+            assert!(force.chebyshev_distance(&ZERO) <= 3.);
+            differs |= force.manhattan_distance(&other.force_at(position)) > 0.;
+        }
+        assert!(differs);
+    }
+
+    #[test]
+    fn magnetic_field_bends_particles_into_circles() {
+        // r = m·v / (q·B) = 2·3 / (0.5·1.5) = 8, around a center below the start.
+        let mut field = ParticleElectromagnetic::new(0.5, ZERO, Vector3::new(0., 0., 1.5));
+        let mut particle = particle_at(ZERO, 2.);
+        particle.velocity = Vector3::new(3., 0., 0.);
+        let center = Vector3::new(0., -8., 0.);
+        let period = 2. * std::f64::consts::PI * 8. / 3.;
+        let duration = 1e-3;
+        for position in run(
+            &mut field,
+            &mut particle,
+            duration,
+            (period / duration) as usize,
+        ) {
+            assert!(((position - center).magnitude() - 8.).abs() < 0.05);
+            assert_eq!(position.z, 0.);
+        }
+        assert!((particle.speed() - 3.).abs() < 0.01);
+        assert!(particle.position.magnitude() < 0.1);
+
+        field.set_magnetic_field(ZERO);
+        field.set_electric_field(Vector3::new(0., 0., 4.));
+        let mut particle = particle_at(ZERO, 2.);
+        run(&mut field, &mut particle, 0.5, 2);
+        assert_eq!(particle.velocity.z, 1.);
+    }
+
+    #[test]
+    fn critically_damped_spring_settles_without_overshoot() {
+        let start = Vector3::new(2., 0., 0.);
+        let mut critical = ParticleAnchoredDampedSpring::critically_damped(ZERO, 2., 50., 1.);
+        let mut particle = particle_at(start, 2.);
+        let positions = run(&mut critical, &mut particle, 1e-3, 5000);
+        assert!(positions.iter().all(|p| p.x > 1. - 1e-3));
+        assert!((particle.position.x - 1.).abs() < 1e-3);
+
+        let mut under = ParticleAnchoredDampedSpring::new(ZERO, 50., 1., 1.);
+        let mut particle = particle_at(start, 2.);
+        let positions = run(&mut under, &mut particle, 1e-3, 5000);
+        assert!(positions.iter().any(|p| p.x < 0.5));
+    }
+
+    #[test]
+    fn damped_pair_spring_settles_at_its_rest_length() {
+        let mut particles = ParticleSet::new();
+        let a = particles.insert(particle_at(ZERO, 1.));
+        let b = particles.insert(particle_at(Vector3::new(3., 0., 0.), 3.));
+        let spring = ParticleDampedSpring::critically_damped(0.75, 40., 1.);
+        let mut registry = ParticleForceRegistry::new();
+        registry.add_pair(a, b, Box::new(spring));
+        registry.add_pair(b, a, Box::new(spring));
+
+        let mut separations = Vec::new();
+        for _ in 0..5000 {
+            registry.update_forces(&mut particles, 1e-3);
+            particles
+                .as_mut_slice()
+                .iter_mut()
+                .for_each(|p| p.integrate(1e-3));
+            separations.push((particles[b].position - particles[a].position).x);
+        }
+        assert!(separations.iter().all(|&s| s > 1. - 1e-3));
+        assert!((separations[4999] - 1.).abs() < 1e-3);
+        assert!(particles[a].speed() < 1e-3 && particles[b].speed() < 1e-3);
+    }
+
+    #[test]
+    fn registrations_can_be_disabled_removed_and_purged() {
+        let mut particles = ParticleSet::new();
+        let a = particles.insert(particle_at(ZERO, 1.));
+        let b = particles.insert(particle_at(Vector3::new(2., 0., 0.), 1.));
+        let mut registry = ParticleForceRegistry::new();
+        let push = |particle: &mut Particle, _: f64| particle.add_force(Vector3::new(1., 0., 0.));
+        let first = registry.add_fn(a, push);
+        let second = registry.add_fn(a, push);
+        let on_b = registry.add_fn(b, push);
+        let spring = registry.add_pair(a, b, Box::new(ParticleSpring::new(1., 2.)));
+        assert_eq!(registry.len(), 4);
+
+        assert!(registry.set_enabled(second, false));
+        assert_eq!(registry.is_enabled(second), Some(false));
+        assert_eq!(registry.update_forces(&mut particles, 0.1), 3);
+        assert!(registry.set_enabled(second, true));
+        assert_eq!(registry.update_forces(&mut particles, 0.1), 4);
+
+        // Removing one registration leaves the others, in order.
+        assert!(registry.remove(first));
+        let ids: Vec<_> = registry.iter().map(|info| info.id).collect();
+        assert_eq!(ids, [second, on_b, spring]);
+        assert_eq!(registry.iter().last().unwrap().other, Some(b));
+
+        // Registrations of a removed particle do nothing until purged.
+        particles.remove(b);
+        assert_eq!(registry.update_forces(&mut particles, 0.1), 1);
+        assert_eq!(registry.remove_all_for_particle(b), 2);
+        assert_eq!(registry.len(), 1);
+        assert_eq!(registry.is_enabled(on_b), None);
+        assert!(!registry.set_enabled(spring, false));
+    }
+
+    #[test]
+    fn wind_field_adds_position_dependent_turbulence_to_its_base() {
+        let base = Vector3::new(2., 0., -1.);
+        let mut calm = ParticleWindField::new(base, 0., 5);
+        let mut particle = particle_at(Vector3::new(3., 1., 4.), 2.);
+        calm.update_force(&mut particle, 0.01);
+        particle.integrate(0.01);
+        assert_eq!(
+            particle
+                .get_last_frame_acceleration()
+                .manhattan_distance(&(base * 0.5)),
+            0.
+        );
+
+        let gusty = ParticleWindField::new(base, 0.5, 5);
+        let mut rng = crate::kellenth::noise::Random::new(2);
+        let first = gusty.force_at(Vector3::new(3., 1., 4.));
+        let mut varies = false;
+        for _ in 0..50 {
+            let position = rng.in_sphere(10.);
+            let force = gusty.force_at(position);
+            assert!((force - base).chebyshev_distance(&ZERO) <= 0.5);
+            assert_eq!(force.manhattan_distance(&gusty.force_at(position)), 0.);
+            varies |= force.manhattan_distance(&first) > 0.;
+        }
+        assert!(varies);
+    }
+}
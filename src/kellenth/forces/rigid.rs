@@ -0,0 +1,377 @@
+//! Holds the force generators that can be applied to rigid bodies.
+
+use crate::kellenth::core::*;
+use crate::kellenth::rigid_body::{BodyHandle, RigidBody, RigidBodySet};
+
+/// A force generator can be asked to add a force to one or more rigid bodies.
+pub trait ForceGenerator {
+    /// Calculates and updates the force applied to the given body.
+    fn update_force(&mut self, body: &mut RigidBody, duration: f64);
+}
+
+/// Adapts any closure into a force generator, for one-off force laws
+/// that don't deserve their own type.
+pub struct ForceClosure<F>
+where
+    F: FnMut(&mut RigidBody, f64),
+{
+    /// Holds the closure called on every force update.
+    closure: F,
+}
+
+impl<F> ForceClosure<F>
+where
+    F: FnMut(&mut RigidBody, f64),
+{
+    /// Constructor
+    pub fn new(closure: F) -> Self {
+        Self { closure }
+    }
+}
+
+impl<F> ForceGenerator for ForceClosure<F>
+where
+    F: FnMut(&mut RigidBody, f64),
+{
+    fn update_force(&mut self, body: &mut RigidBody, duration: f64) {
+        (self.closure)(body, duration);
+    }
+}
+
+/// A force generator that applies a gravitational force to rigid bodies.
+/// One instance can be used for multiple bodies.
+///
+/// Each body feels its own effective gravity, so its gravity scale and override
+/// are honored. The force acts at the center of mass and produces no torque.
+/// Sleeping bodies are left asleep, and bodies with infinite mass are skipped.
+#[derive(Debug, Clone, Copy)]
+pub struct Gravity {
+    /// Holds the acceleration due to gravity.
+    pub gravity: Vector3,
+}
+
+impl Gravity {
+    /// Constructor
+    pub fn new(gravity: Vector3) -> Self {
+        Self { gravity }
+    }
+}
+
+impl ForceGenerator for Gravity {
+    fn update_force(&mut self, body: &mut RigidBody, _duration: f64) {
+        // Check that we do not have infinite mass, and that we wouldn't
+        // wake a body that is resting.
+        if !body.has_finite_mass() || !body.is_awake() {
+            return;
+        }
+
+        let gravity = body.get_effective_gravity(self.gravity);
+        if gravity.square_magnitude() == 0. {
+            return;
+        }
+
+        // Apply the mass-scaled force to the body.
+        body.add_force(gravity * body.get_mass());
+    }
+}
+
+/// Identifies a registration made in a `ForceRegistry`.
+/// It is a different type from the ids of the particle registry, so the two can't be mixed up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ForceRegistrationId(u64);
+
+/// Keeps track of one force generator and the body it applies to.
+struct ForceRegistration {
+    id: ForceRegistrationId,
+    body: BodyHandle,
+    generator: Box<dyn ForceGenerator>,
+    enabled: bool,
+}
+
+/// Describes a registration, for debugging.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ForceRegistrationInfo {
+    /// Identifies the registration.
+    pub id: ForceRegistrationId,
+
+    /// Holds the body the force is applied to.
+    pub body: BodyHandle,
+
+    /// Holds whether the generator is currently applied.
+    pub enabled: bool,
+}
+
+/// Holds all the force generators and the rigid bodies that they apply to.
+/// Bodies are referred to by their handle in the set given to `update_forces`.
+#[derive(Default)]
+pub struct ForceRegistry {
+    /// Holds the list of registrations.
+    registrations: Vec<ForceRegistration>,
+
+    /// Holds the id given to the next registration.
+    next_id: u64,
+}
+
+impl ForceRegistry {
+    /// Constructor
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the given force generator to apply to the given body.
+    pub fn add(
+        &mut self,
+        body: BodyHandle,
+        generator: Box<dyn ForceGenerator>,
+    ) -> ForceRegistrationId {
+        let id = ForceRegistrationId(self.next_id);
+        self.next_id += 1;
+        self.registrations.push(ForceRegistration {
+            id,
+            body,
+            generator,
+            enabled: true,
+        });
+        id
+    }
+
+    /// Registers the given closure as a force generator for the given body.
+    pub fn add_fn<F>(&mut self, body: BodyHandle, closure: F) -> ForceRegistrationId
+    where
+        F: FnMut(&mut RigidBody, f64) + 'static,
+    {
+        self.add(body, Box::new(ForceClosure::new(closure)))
+    }
+
+    /// Removes the given registration from the registry.
+    /// Returns false if the registration was not found.
+    pub fn remove(&mut self, id: ForceRegistrationId) -> bool {
+        let length = self.registrations.len();
+        self.registrations
+            .retain(|registration| registration.id != id);
+        self.registrations.len() != length
+    }
+
+    /// Removes every registration applying to the given body.
+    /// Call it when the body is removed from its set.
+    /// Returns the number of registrations removed.
+    pub fn remove_all_for_body(&mut self, body: BodyHandle) -> usize {
+        let length = self.registrations.len();
+        self.registrations
+            .retain(|registration| registration.body != body);
+        length - self.registrations.len()
+    }
+
+    /// Enables or disables the given registration. A disabled generator keeps its
+    /// configuration but is skipped by `update_forces`.
+    /// Returns false if the registration was not found.
+    pub fn set_enabled(&mut self, id: ForceRegistrationId, enabled: bool) -> bool {
+        match self
+            .registrations
+            .iter_mut()
+            .find(|registration| registration.id == id)
+        {
+            Some(registration) => {
+                registration.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Returns whether the given registration is enabled, or `None` if it was not found.
+    pub fn is_enabled(&self, id: ForceRegistrationId) -> Option<bool> {
+        self.registrations
+            .iter()
+            .find(|registration| registration.id == id)
+            .map(|registration| registration.enabled)
+    }
+
+    /// Returns the number of registrations.
+    pub fn len(&self) -> usize {
+        self.registrations.len()
+    }
+
+    /// Returns true if there are no registrations.
+    pub fn is_empty(&self) -> bool {
+        self.registrations.is_empty()
+    }
+
+    /// Iterates over the registrations in the order they are applied.
+    pub fn iter(&self) -> impl Iterator<Item = ForceRegistrationInfo> + '_ {
+        self.registrations
+            .iter()
+            .map(|registration| ForceRegistrationInfo {
+                id: registration.id,
+                body: registration.body,
+                enabled: registration.enabled,
+            })
+    }
+
+    /// Clears all the registrations from the registry.
+    /// This will not delete the bodies or the force generators themselves.
+    pub fn clear(&mut self) {
+        self.registrations.clear();
+    }
+
+    /// Calls all the force generators to update the forces of their corresponding bodies.
+    /// Disabled registrations are skipped, and so are registrations referring to bodies
+    /// that are gone: handles never come back to life, so they do nothing until purged
+    /// with `remove_all_for_body`.
+    /// Returns the number of registrations applied.
+    pub fn update_forces(&mut self, bodies: &mut RigidBodySet, duration: f64) -> usize {
+        let mut applied = 0;
+        for registration in &mut self.registrations {
+            if !registration.enabled {
+                continue;
+            }
+            if let Some(body) = bodies.get_mut(registration.body) {
+                registration.generator.update_force(body, duration);
+                applied += 1;
+            }
+        }
+        applied
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kellenth::rigid_body::{BodyType, RigidBodyBuilder};
+
+    const GRAVITY: Vector3 = Vector3::new(0., -10., 0.);
+
+    /// Adds the weight of the body under `GRAVITY`.
+    fn weight(body: &mut RigidBody, _duration: f64) {
+        body.add_force(GRAVITY * body.get_mass());
+    }
+
+    /// Returns a set of bodies with the given masses.
+    fn bodies(masses: &[f64]) -> (RigidBodySet, Vec<BodyHandle>) {
+        let mut set = RigidBodySet::new();
+        let handles = masses
+            .iter()
+            .map(|&mass| set.insert(RigidBodyBuilder::new().with_mass(mass).build()))
+            .collect();
+        (set, handles)
+    }
+
+    #[test]
+    fn generators_only_apply_to_the_bodies_they_are_registered_for() {
+        let (mut set, handles) = bodies(&[1., 2., 3., 4.]);
+        let mut registry = ForceRegistry::new();
+        registry.add_fn(handles[0], weight);
+        registry.add_fn(handles[2], weight);
+
+        assert_eq!(registry.update_forces(&mut set, 0.1), 2);
+        let forces: Vec<f64> = handles
+            .iter()
+            .map(|&handle| set[handle].get_accumulated_force().y)
+            .collect();
+        assert_eq!(forces, vec![-10., 0., -30., 0.]);
+    }
+
+    #[test]
+    fn disabled_and_removed_registrations_are_skipped() {
+        let (mut set, handles) = bodies(&[1., 2.]);
+        let mut registry = ForceRegistry::new();
+        let first = registry.add_fn(handles[0], weight);
+        let second = registry.add_fn(handles[1], weight);
+
+        assert!(registry.set_enabled(first, false));
+        assert_eq!(registry.is_enabled(first), Some(false));
+        assert_eq!(registry.update_forces(&mut set, 0.1), 1);
+        assert_eq!(set[handles[0]].get_accumulated_force().y, 0.);
+
+        assert!(registry.set_enabled(first, true));
+        set.remove(handles[1]);
+        assert_eq!(registry.update_forces(&mut set, 0.1), 1);
+        assert_eq!(set[handles[0]].get_accumulated_force().y, -10.);
+
+        assert_eq!(registry.remove_all_for_body(handles[1]), 1);
+        assert!(!registry.remove(second));
+        assert_eq!(registry.is_enabled(second), None);
+        let info: Vec<_> = registry.iter().collect();
+        assert_eq!(
+            info,
+            vec![ForceRegistrationInfo {
+                id: first,
+                body: handles[0],
+                enabled: true,
+            }]
+        );
+
+        registry.clear();
+        assert!(registry.is_empty());
+    }
+
+    #[test]
+    fn gravity_follows_the_scale_and_override_of_every_body() {
+        let (mut set, handles) = bodies(&[1., 2., 3., 4.]);
+        let mut registry = ForceRegistry::new();
+        for (&handle, scale) in handles.iter().zip([1., 0., -0.5, 2.]) {
+            let body = &mut set[handle];
+            body.set_gravity_scale(scale);
+            body.set_can_sleep(false);
+            body.linear_damping = 1.;
+            registry.add(handle, Box::new(Gravity::new(GRAVITY)));
+        }
+        set[handles[3]].set_gravity_override(Some(Vector3::new(1., 0., 0.)));
+
+        for _ in 0..100 {
+            registry.update_forces(&mut set, 0.01);
+            for body in set.as_mut_slice() {
+                body.integrate(0.01);
+            }
+        }
+        let fall = set[handles[0]].position.y;
+        assert!(fall < -4.);
+        assert_eq!(set[handles[1]].position.magnitude(), 0.);
+        assert!((set[handles[2]].position.y / fall + 0.5).abs() < 1e-12);
+        // The override replaces the world gravity, and is still scaled.
+        let sideways = set[handles[3]].position;
+        assert_eq!(sideways.y, 0.);
+        assert!((sideways.x / fall + 0.2).abs() < 1e-12);
+    }
+
+    #[test]
+    fn gravity_accelerates_every_mass_alike_without_torque() {
+        let (mut set, handles) = bodies(&[1., 50.]);
+        let mut gravity = Gravity::new(GRAVITY);
+        for &handle in &handles {
+            let body = &mut set[handle];
+            gravity.update_force(body, 0.1);
+            assert!((body.get_accumulated_force() - GRAVITY * body.get_mass()).magnitude() < 1e-12);
+            assert_eq!(body.get_accumulated_torque().magnitude(), 0.);
+            body.integrate(0.1);
+        }
+        let light = set[handles[0]].get_last_frame_acceleration();
+        let heavy = set[handles[1]].get_last_frame_acceleration();
+        assert!((light - GRAVITY).magnitude() < 1e-12);
+        assert!((heavy - light).magnitude() < 1e-12);
+    }
+
+    #[test]
+    fn gravity_skips_bodies_it_should_not_move() {
+        let mut gravity = Gravity::new(GRAVITY);
+
+        let mut weightless = RigidBodyBuilder::new().with_mass(2.).build();
+        weightless.set_gravity_scale(0.);
+        gravity.update_force(&mut weightless, 0.1);
+        assert_eq!(weightless.get_accumulated_force().magnitude(), 0.);
+
+        // Resting bodies are left asleep.
+        let mut resting = RigidBodyBuilder::new().with_mass(2.).build();
+        resting.set_awake(false);
+        gravity.update_force(&mut resting, 0.1);
+        assert!(!resting.is_awake());
+        assert_eq!(resting.get_accumulated_force().magnitude(), 0.);
+
+        for body_type in [BodyType::Static, BodyType::Kinematic] {
+            let mut body = RigidBodyBuilder::new().with_mass(2.).build();
+            body.set_body_type(body_type);
+            gravity.update_force(&mut body, 0.1);
+            assert_eq!(body.get_accumulated_force().magnitude(), 0.);
+        }
+    }
+}
@@ -0,0 +1,57 @@
+//! Holds the inertia tensors of common shapes of uniform density.
+//! Every tensor is expressed about the center of mass, in body space.
+
+use crate::kellenth::core::*;
+
+/// Returns the inertia tensor of a solid sphere: `2/5 m r²` about every axis.
+pub fn inertia_solid_sphere(mass: f64, radius: f64) -> Matrix3 {
+    let i = 0.4 * mass * radius * radius;
+    Matrix3::diagonal(i, i, i)
+}
+
+/// Returns the inertia tensor of a solid box with the given half-sizes along each axis.
+/// About the x axis this is `1/3 m (hy² + hz²)`, which is `1/12 m (h² + d²)` for full sizes.
+pub fn inertia_box(mass: f64, half_extents: Vector3) -> Matrix3 {
+    let squares = half_extents.component_product(&half_extents);
+    let k = mass / 3.;
+    Matrix3::diagonal(
+        k * (squares.y + squares.z),
+        k * (squares.x + squares.z),
+        k * (squares.x + squares.y),
+    )
+}
+
+/// Returns the inertia tensor of a solid cylinder whose axis is the y axis.
+/// It is `1/2 m r²` about the axis and `1/12 m (3r² + h²)` about the other two.
+pub fn inertia_cylinder(mass: f64, radius: f64, height: f64) -> Matrix3 {
+    let axial = 0.5 * mass * radius * radius;
+    let transverse = mass * (3. * radius * radius + height * height) / 12.;
+    Matrix3::diagonal(transverse, axial, transverse)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Asserts that the tensor is diagonal with the given values, to within rounding.
+    fn assert_diagonal(tensor: Matrix3, diagonal: [f64; 3]) {
+        let expected = Matrix3::diagonal(diagonal[0], diagonal[1], diagonal[2]);
+        for (value, expected) in tensor.data.iter().zip(expected.data) {
+            assert!((value - expected).abs() < 1e-15, "{tensor:?}");
+        }
+    }
+
+    #[test]
+    fn unit_shapes_match_the_textbook_values() {
+        assert_diagonal(inertia_solid_sphere(1., 1.), [0.4; 3]);
+        assert_diagonal(inertia_box(1., Vector3::new(0.5, 0.5, 0.5)), [1. / 6.; 3]);
+        assert_diagonal(inertia_cylinder(1., 1., 1.), [1. / 3., 0.5, 1. / 3.]);
+    }
+
+    #[test]
+    fn box_inertia_uses_the_other_two_extents() {
+        // A 2 x 4 x 6 box of mass 12: 1/12 m (h² + d²) about each axis.
+        let tensor = inertia_box(12., Vector3::new(1., 2., 3.));
+        assert_diagonal(tensor, [16. + 36., 4. + 36., 4. + 16.]);
+    }
+}
@@ -0,0 +1,488 @@
+//! Holds the links, which connect particles together and generate a contact
+//! when they violate the constraints of the link.
+//!
+//! A link whose particles have been removed generates no contacts.
+
+use crate::kellenth::contacts::{ParticleContact, ParticleContactGenerator};
+use crate::kellenth::core::*;
+use crate::kellenth::world::{ParticleHandle, ParticleSet};
+
+/// Returns the positions of the two given particles, or `None` if either is gone.
+fn positions(
+    particles: &ParticleSet,
+    pair: (ParticleHandle, ParticleHandle),
+) -> Option<(Vector3, Vector3)> {
+    Some((
+        particles.get(pair.0)?.position,
+        particles.get(pair.1)?.position,
+    ))
+}
+
+/// Cables link a pair of particles, generating a contact if they stray too far apart.
+#[derive(Debug, Clone, Copy)]
+pub struct ParticleCable {
+    /// Holds the pair of particles connected by this link.
+    pub particles: (ParticleHandle, ParticleHandle),
+
+    /// Holds the maximum length of the cable.
+    pub max_length: f64,
+
+    /// Holds the restitution (bounciness) of the cable.
+    pub restitution: f64,
+}
+
+impl ParticleCable {
+    /// Constructor
+    pub fn new(
+        particles: (ParticleHandle, ParticleHandle),
+        max_length: f64,
+        restitution: f64,
+    ) -> Self {
+        Self {
+            particles,
+            max_length,
+            restitution,
+        }
+    }
+}
+
+impl ParticleContactGenerator for ParticleCable {
+    fn add_contacts(
+        &self,
+        particles: &ParticleSet,
+        contacts: &mut Vec<ParticleContact>,
+        limit: usize,
+    ) -> usize {
+        if limit == 0 {
+            return 0;
+        }
+
+        let (first, second) = match positions(particles, self.particles) {
+            Some(positions) => positions,
+            None => return 0,
+        };
+
+        // Find the length of the cable, and check if we're overextended.
+        let length = (first - second).magnitude();
+        if length < self.max_length {
+            return 0;
+        }
+
+        // Otherwise return the contact, pulling the first particle towards the second.
+        let normal = (second - first).get_normalized();
+        contacts.push(ParticleContact::new(
+            (self.particles.0, Some(self.particles.1)),
+            self.restitution,
+            normal,
+            length - self.max_length,
+        ));
+        1
+    }
+}
+
+/// Rods link a pair of particles, keeping them at an exact distance apart.
+/// The contacts have no restitution so the rod never bounces.
+///
+/// Every rod generates two contacts, one against stretching and one against
+/// compressing, so it holds both ways even when resolving other contacts, like the
+/// neighboring links of a chain, moves its particles past the rest length.
+/// The price is that a rod always uses two contacts of the frame's contact budget,
+/// even at rest, and counts twice in the contacts of its generator.
+#[derive(Debug, Clone, Copy)]
+pub struct ParticleRod {
+    /// Holds the pair of particles connected by this link.
+    pub particles: (ParticleHandle, ParticleHandle),
+
+    /// Holds the length of the rod.
+    pub length: f64,
+}
+
+impl ParticleRod {
+    /// Constructor
+    pub fn new(particles: (ParticleHandle, ParticleHandle), length: f64) -> Self {
+        Self { particles, length }
+    }
+}
+
+impl ParticleContactGenerator for ParticleRod {
+    fn add_contacts(
+        &self,
+        particles: &ParticleSet,
+        contacts: &mut Vec<ParticleContact>,
+        limit: usize,
+    ) -> usize {
+        if limit == 0 {
+            return 0;
+        }
+
+        let (first, second) = match positions(particles, self.particles) {
+            Some(positions) => positions,
+            None => return 0,
+        };
+
+        // Find the length of the rod.
+        let current_length = (first - second).magnitude();
+
+        // Calculate the normal.
+        let normal = (second - first).get_normalized();
+
+        // Generate one contact against extending and one against compressing, with
+        // zero restitution (no bounciness). The one on the other side of the current
+        // length has negative penetration, so it only stops closing velocity.
+        let extension = current_length - self.length;
+        let count = limit.min(2);
+        for (normal, penetration) in [(normal, extension), (normal * -1., -extension)]
+            .into_iter()
+            .take(count)
+        {
+            contacts.push(ParticleContact::new(
+                (self.particles.0, Some(self.particles.1)),
+                0.,
+                normal,
+                penetration,
+            ));
+        }
+        count
+    }
+}
+
+/// Cable constraints link a particle to a fixed point in the world, generating a
+/// contact if the particle strays too far away from it. Only the particle moves.
+#[derive(Debug, Clone, Copy)]
+pub struct ParticleCableConstraint {
+    /// Holds the particle connected by this constraint.
+    pub particle: ParticleHandle,
+
+    /// The point to which the particle is anchored.
+    pub anchor: Vector3,
+
+    /// Holds the maximum length of the cable.
+    pub max_length: f64,
+
+    /// Holds the restitution (bounciness) of the cable.
+    pub restitution: f64,
+}
+
+impl ParticleCableConstraint {
+    /// Constructor
+    pub fn new(
+        particle: ParticleHandle,
+        anchor: Vector3,
+        max_length: f64,
+        restitution: f64,
+    ) -> Self {
+        Self {
+            particle,
+            anchor,
+            max_length,
+            restitution,
+        }
+    }
+}
+
+impl ParticleContactGenerator for ParticleCableConstraint {
+    fn add_contacts(
+        &self,
+        particles: &ParticleSet,
+        contacts: &mut Vec<ParticleContact>,
+        limit: usize,
+    ) -> usize {
+        if limit == 0 {
+            return 0;
+        }
+
+        let position = match particles.get(self.particle) {
+            Some(particle) => particle.position,
+            None => return 0,
+        };
+
+        // Find the length of the cable, and check if we're overextended.
+        let length = (position - self.anchor).magnitude();
+        if length < self.max_length {
+            return 0;
+        }
+
+        // Otherwise return the contact, pulling the particle towards the anchor.
+        let normal = (self.anchor - position).get_normalized();
+        contacts.push(ParticleContact::new(
+            (self.particle, None),
+            self.restitution,
+            normal,
+            length - self.max_length,
+        ));
+        1
+    }
+}
+
+/// Rod constraints link a particle to a fixed point in the world, keeping it at an
+/// exact distance from the anchor. The contacts have no restitution so the rod never bounces.
+/// Like `ParticleRod`, it always generates two contacts, one for each direction,
+/// using two contacts of the budget.
+#[derive(Debug, Clone, Copy)]
+pub struct ParticleRodConstraint {
+    /// Holds the particle connected by this constraint.
+    pub particle: ParticleHandle,
+
+    /// The point to which the particle is anchored.
+    pub anchor: Vector3,
+
+    /// Holds the length of the rod.
+    pub length: f64,
+}
+
+impl ParticleRodConstraint {
+    /// Constructor
+    pub fn new(particle: ParticleHandle, anchor: Vector3, length: f64) -> Self {
+        Self {
+            particle,
+            anchor,
+            length,
+        }
+    }
+}
+
+impl ParticleContactGenerator for ParticleRodConstraint {
+    fn add_contacts(
+        &self,
+        particles: &ParticleSet,
+        contacts: &mut Vec<ParticleContact>,
+        limit: usize,
+    ) -> usize {
+        if limit == 0 {
+            return 0;
+        }
+
+        let position = match particles.get(self.particle) {
+            Some(particle) => particle.position,
+            None => return 0,
+        };
+
+        // Find the length of the rod.
+        let current_length = (position - self.anchor).magnitude();
+
+        // Calculate the normal.
+        let normal = (self.anchor - position).get_normalized();
+
+        // Generate one contact against extending and one against compressing, with
+        // zero restitution (no bounciness). The one on the other side of the current
+        // length has negative penetration, so it only stops closing velocity.
+        let extension = current_length - self.length;
+        let count = limit.min(2);
+        for (normal, penetration) in [(normal, extension), (normal * -1., -extension)]
+            .into_iter()
+            .take(count)
+        {
+            contacts.push(ParticleContact::new(
+                (self.particle, None),
+                0.,
+                normal,
+                penetration,
+            ));
+        }
+        count
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kellenth::contacts::ParticleContactResolver;
+    use crate::kellenth::particle::Particle;
+
+    const ZERO: Vector3 = Vector3::new(0., 0., 0.);
+
+    /// Returns a particle with the given mass at rest at the given position.
+    fn particle_at(position: Vector3, mass: f64) -> Particle {
+        let mut particle = Particle::new(position, ZERO, ZERO, 1.);
+        particle.set_mass(mass);
+        particle
+    }
+
+    /// Generates the contacts of the given generator and resolves them.
+    fn resolve(generator: &dyn ParticleContactGenerator, particles: &mut ParticleSet) {
+        let mut contacts = Vec::new();
+        generator.add_contacts(particles, &mut contacts, 16);
+        ParticleContactResolver::new(8).resolve_contacts(&mut contacts, particles, 0.01);
+    }
+
+    #[test]
+    fn rod_restores_its_length_from_both_sides() {
+        for separation in [0.5, 1.5] {
+            let mut particles = ParticleSet::new();
+            let a = particles.insert(particle_at(ZERO, 1.));
+            let b = particles.insert(particle_at(Vector3::new(separation, 0., 0.), 1.));
+            let rod = ParticleRod::new((a, b), 1.);
+            resolve(&rod, &mut particles);
+            let length = (particles[b].position - particles[a].position).magnitude();
+            assert!((length - 1.).abs() < 1e-12, "{separation} -> {length}");
+        }
+    }
+
+    #[test]
+    fn rod_generates_two_contacts_even_at_rest_length() {
+        let mut particles = ParticleSet::new();
+        let a = particles.insert(particle_at(ZERO, 1.));
+        let b = particles.insert(particle_at(Vector3::new(1., 0., 0.), 1.));
+        let rod = ParticleRod::new((a, b), 1.);
+
+        let mut contacts = Vec::new();
+        assert_eq!(rod.add_contacts(&particles, &mut contacts, 16), 2);
+        assert_eq!(rod.add_contacts(&particles, &mut contacts, 1), 1);
+        assert_eq!(contacts.len(), 3);
+        assert!(contacts
+            .iter()
+            .all(|c| c.penetration == 0. && c.restitution == 0.));
+    }
+
+    #[test]
+    fn rod_chain_stays_stable_under_gravity() {
+        let mut particles = ParticleSet::new();
+        let gravity = Vector3::new(0., -9.81, 0.);
+        let mut handles = vec![particles.insert(Particle::new(ZERO, ZERO, ZERO, 1.))];
+        for i in 1..6 {
+            let mut particle = particle_at(Vector3::new(i as f64 * 0.5, 0., 0.), 1.);
+            particle.acceleration = gravity;
+            particle.damping = 0.3;
+            handles.push(particles.insert(particle));
+        }
+        let rods: Vec<ParticleRod> = handles
+            .windows(2)
+            .map(|pair| ParticleRod::new((pair[0], pair[1]), 0.5))
+            .collect();
+
+        let mut resolver = ParticleContactResolver::new(100);
+        for _ in 0..2000 {
+            for particle in particles.as_mut_slice() {
+                particle.integrate(0.01);
+            }
+            let mut contacts = Vec::new();
+            for rod in &rods {
+                rod.add_contacts(&particles, &mut contacts, 16);
+            }
+            resolver.resolve_contacts(&mut contacts, &mut particles, 0.01);
+        }
+
+        for pair in handles.windows(2) {
+            let length = (particles[pair[1]].position - particles[pair[0]].position).magnitude();
+            assert!((length - 0.5).abs() < 1e-3, "{length}");
+        }
+        // The chain ends up hanging straight down from the fixed end.
+        let end = particles[handles[5]].position;
+        assert!(end.x.abs() < 0.05 && (end.y + 2.5).abs() < 0.01, "{end:?}");
+    }
+
+    #[test]
+    fn cable_only_pulls_when_taut() {
+        let mut particles = ParticleSet::new();
+        let a = particles.insert(particle_at(Vector3::new(1., 1., 0.), 1.));
+        let b = particles.insert(particle_at(Vector3::new(1., 3., 0.), 1.));
+        let mut contacts = Vec::new();
+        let slack = ParticleCable::new((a, b), 2.5, 0.3);
+        assert_eq!(slack.add_contacts(&particles, &mut contacts, 16), 0);
+
+        let taut = ParticleCable::new((a, b), 1.5, 0.3);
+        assert_eq!(taut.add_contacts(&particles, &mut contacts, 16), 1);
+        assert_eq!(taut.add_contacts(&particles, &mut contacts, 0), 0);
+        let contact = contacts[0];
+        assert_eq!(contact.particles, (a, Some(b)));
+        assert_eq!(contact.contact_normal.y, 1.);
+        assert_eq!(contact.penetration, 0.5);
+        assert_eq!(contact.restitution, 0.3);
+    }
+
+    #[test]
+    fn cable_pendulum_keeps_its_length() {
+        let mut particles = ParticleSet::new();
+        let pivot = particles.insert(Particle::new(ZERO, ZERO, ZERO, 1.));
+        let mut bob = particle_at(Vector3::new(2., 0., 0.), 1.);
+        bob.acceleration = Vector3::new(0., -9.81, 0.);
+        let bob = particles.insert(bob);
+        let cable = ParticleCable::new((bob, pivot), 2., 0.);
+
+        let (mut lowest, mut leftmost) = (0f64, 2f64);
+        let mut resolver = ParticleContactResolver::new(4);
+        for _ in 0..500 {
+            particles[bob].integrate(0.01);
+            let mut contacts = Vec::new();
+            cable.add_contacts(&particles, &mut contacts, 16);
+            resolver.resolve_contacts(&mut contacts, &mut particles, 0.01);
+            let length = particles[bob].position.magnitude();
+            assert!(length < 2. + 1e-9, "{length}");
+            lowest = lowest.min(particles[bob].position.y);
+            leftmost = leftmost.min(particles[bob].position.x);
+        }
+        // It swings through the bottom and up the other side.
+        assert!(lowest < -1.9 && leftmost < -1.);
+        assert_eq!(particles[pivot].position.magnitude(), 0.);
+    }
+
+    #[test]
+    fn cable_constraint_pulls_towards_its_anchor() {
+        let mut particles = ParticleSet::new();
+        let handle = particles.insert(particle_at(Vector3::new(0., -3., 4.), 1.));
+        let anchor = Vector3::new(0., -3., 1.);
+        let mut contacts = Vec::new();
+        let slack = ParticleCableConstraint::new(handle, anchor, 3.5, 0.);
+        assert_eq!(slack.add_contacts(&particles, &mut contacts, 16), 0);
+
+        let taut = ParticleCableConstraint::new(handle, anchor, 2., 0.4);
+        assert_eq!(taut.add_contacts(&particles, &mut contacts, 16), 1);
+        let contact = contacts[0];
+        assert_eq!(contact.particles, (handle, None));
+        assert_eq!(contact.contact_normal.z, -1.);
+        assert_eq!(contact.penetration, 1.);
+        assert_eq!(contact.restitution, 0.4);
+    }
+
+    #[test]
+    fn cable_constraint_swings_like_a_pendulum() {
+        let mut particles = ParticleSet::new();
+        let anchor = Vector3::new(0., 5., 0.);
+        let mut bob = particle_at(anchor + Vector3::new(0., 0., 1.), 1.);
+        bob.acceleration = Vector3::new(0., -9.81, 0.);
+        let bob = particles.insert(bob);
+        let cable = ParticleCableConstraint::new(bob, anchor, 1., 0.);
+
+        let mut crossings = 0;
+        let mut side = 1f64;
+        let mut resolver = ParticleContactResolver::new(2);
+        for _ in 0..1000 {
+            particles[bob].integrate(0.005);
+            let mut contacts = Vec::new();
+            cable.add_contacts(&particles, &mut contacts, 16);
+            resolver.resolve_contacts(&mut contacts, &mut particles, 0.005);
+            let offset = particles[bob].position - anchor;
+            assert!(offset.magnitude() < 1. + 1e-9);
+            if offset.z.signum() != side {
+                side = offset.z.signum();
+                crossings += 1;
+            }
+        }
+        // Released level, a one metre pendulum swings with a period of about 2.4s,
+        // crossing below the anchor twice in each.
+        assert!((4..=6).contains(&crossings), "{crossings}");
+    }
+
+    #[test]
+    fn rod_constraint_restores_its_length_from_both_sides() {
+        let anchor = Vector3::new(0., 2., 0.);
+        for distance in [0.5, 1.5] {
+            let mut particles = ParticleSet::new();
+            let a = particles.insert(particle_at(anchor + Vector3::new(0., -distance, 0.), 1.));
+            resolve(&ParticleRodConstraint::new(a, anchor, 1.), &mut particles);
+            let length = (particles[a].position - anchor).magnitude();
+            assert!((length - 1.).abs() < 1e-12, "{distance} -> {length}");
+        }
+    }
+
+    #[test]
+    fn rod_constraint_leaves_immovable_particles_alone() {
+        let anchor = Vector3::new(0., 2., 0.);
+        let mut particles = ParticleSet::new();
+        let a = particles.insert(Particle::new(ZERO, ZERO, ZERO, 1.));
+        resolve(&ParticleRodConstraint::new(a, anchor, 1.), &mut particles);
+        let particle = particles[a];
+        assert_eq!(particle.position.magnitude(), 0.);
+        assert_eq!(particle.velocity.magnitude(), 0.);
+    }
+}
@@ -0,0 +1,175 @@
+//! Holds the materials of particles and scenery, and the table used to look up
+//! the properties of the contacts between them.
+
+use std::collections::HashMap;
+
+/// Identifies a material registered in a `MaterialTable`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MaterialId(pub u16);
+
+impl MaterialId {
+    /// The material given to particles and scenery unless told otherwise.
+    pub const DEFAULT: MaterialId = MaterialId(0);
+}
+
+/// How the values of two materials are combined into the value of their contact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CombineRule {
+    /// Uses the smaller of the two values.
+    Min,
+
+    /// Multiplies the two values together.
+    Multiply,
+
+    /// Uses the average of the two values.
+    #[default]
+    Average,
+}
+
+impl CombineRule {
+    /// Combines the two given values.
+    pub fn combine(&self, a: f64, b: f64) -> f64 {
+        match self {
+            CombineRule::Min => a.min(b),
+            CombineRule::Multiply => a * b,
+            CombineRule::Average => 0.5 * (a + b),
+        }
+    }
+}
+
+/// Returns the key of a pair of materials, the same in either order.
+fn pair_key(a: MaterialId, b: MaterialId) -> (MaterialId, MaterialId) {
+    if a <= b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+/// Looks up the restitution of the contact between two materials.
+///
+/// Values registered for a specific pair take precedence. Otherwise the values of
+/// the two materials (or the default for unregistered ones) are combined using the
+/// combine rule.
+#[derive(Debug, Clone)]
+pub struct MaterialTable {
+    /// Holds the restitution of materials without a registered value.
+    default_restitution: f64,
+
+    /// Holds the rule used to combine the values of two materials.
+    combine_rule: CombineRule,
+
+    /// Holds the restitution registered for each material.
+    restitution: HashMap<MaterialId, f64>,
+
+    /// Holds the restitution registered for specific pairs of materials.
+    pair_restitution: HashMap<(MaterialId, MaterialId), f64>,
+}
+
+impl MaterialTable {
+    /// Constructor
+    pub fn new(default_restitution: f64, combine_rule: CombineRule) -> Self {
+        Self {
+            default_restitution,
+            combine_rule,
+            restitution: HashMap::new(),
+            pair_restitution: HashMap::new(),
+        }
+    }
+
+    /// Returns the restitution of materials without a registered value.
+    pub fn get_default_restitution(&self) -> f64 {
+        self.default_restitution
+    }
+
+    /// Sets the restitution of materials without a registered value.
+    pub fn set_default_restitution(&mut self, restitution: f64) {
+        self.default_restitution = restitution;
+    }
+
+    /// Returns the rule used to combine the values of two materials.
+    pub fn get_combine_rule(&self) -> CombineRule {
+        self.combine_rule
+    }
+
+    /// Sets the rule used to combine the values of two materials.
+    pub fn set_combine_rule(&mut self, combine_rule: CombineRule) {
+        self.combine_rule = combine_rule;
+    }
+
+    /// Registers the restitution of the given material.
+    pub fn set_restitution(&mut self, material: MaterialId, restitution: f64) {
+        self.restitution.insert(material, restitution);
+    }
+
+    /// Registers the restitution of the contacts between the two given materials,
+    /// in either order, overriding the combine rule.
+    pub fn set_pair_restitution(&mut self, a: MaterialId, b: MaterialId, restitution: f64) {
+        self.pair_restitution.insert(pair_key(a, b), restitution);
+    }
+
+    /// Returns the restitution of the given material on its own.
+    pub fn material_restitution(&self, material: MaterialId) -> f64 {
+        self.restitution
+            .get(&material)
+            .copied()
+            .unwrap_or(self.default_restitution)
+    }
+
+    /// Returns the restitution of the contacts between the two given materials.
+    /// The result is the same in either order.
+    pub fn restitution(&self, a: MaterialId, b: MaterialId) -> f64 {
+        if let Some(&restitution) = self.pair_restitution.get(&pair_key(a, b)) {
+            return restitution;
+        }
+        self.combine_rule
+            .combine(self.material_restitution(a), self.material_restitution(b))
+    }
+}
+
+impl Default for MaterialTable {
+    fn default() -> Self {
+        Self::new(0.5, CombineRule::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const STEEL: MaterialId = MaterialId(1);
+    const RUBBER: MaterialId = MaterialId(2);
+    const CONCRETE: MaterialId = MaterialId(3);
+
+    #[test]
+    fn pair_lookups_are_symmetric() {
+        let mut table = MaterialTable::new(0.4, CombineRule::Multiply);
+        table.set_restitution(STEEL, 0.9);
+        table.set_restitution(RUBBER, 0.8);
+        table.set_pair_restitution(CONCRETE, RUBBER, 0.75);
+        for (a, b) in [
+            (STEEL, RUBBER),
+            (RUBBER, CONCRETE),
+            (STEEL, MaterialId::DEFAULT),
+        ] {
+            assert_eq!(table.restitution(a, b), table.restitution(b, a));
+        }
+        assert_eq!(table.restitution(RUBBER, CONCRETE), 0.75);
+    }
+
+    #[test]
+    fn combine_rule_applies_to_per_material_values() {
+        let mut table = MaterialTable::new(0.5, CombineRule::Min);
+        table.set_restitution(STEEL, 0.9);
+        table.set_restitution(RUBBER, 0.3);
+        assert_eq!(table.restitution(STEEL, RUBBER), 0.3);
+        table.set_combine_rule(CombineRule::Multiply);
+        assert!((table.restitution(STEEL, RUBBER) - 0.27).abs() < 1e-15);
+        table.set_combine_rule(CombineRule::Average);
+        assert!((table.restitution(STEEL, RUBBER) - 0.6).abs() < 1e-15);
+        // Unregistered materials fall back to the default.
+        assert!((table.restitution(STEEL, CONCRETE) - 0.7).abs() < 1e-15);
+        assert_eq!(table.restitution(CONCRETE, CONCRETE), 0.5);
+    }
+}
@@ -0,0 +1,107 @@
+//! Holds deterministic noise functions used by the procedural force fields,
+//! and a small random number generator built on the same mixing function.
+//!
+//! Everything here only depends on its inputs and the seed, so a given
+//! seed produces exactly the same results on every run.
+
+use crate::kellenth::core::*;
+
+/// Mixes the bits of the given value (the SplitMix64 finalizer).
+fn mix(mut value: u64) -> u64 {
+    value = value.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    value = (value ^ (value >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    value = (value ^ (value >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    value ^ (value >> 31)
+}
+
+/// Returns a pseudo-random value in `[-1, 1]` for the given lattice point.
+fn lattice(seed: u64, x: i64, y: i64, z: i64) -> f64 {
+    let hash = mix(mix(mix(seed ^ x as u64) ^ y as u64) ^ z as u64);
+    // Use the top 53 bits to build a value in [0, 1).
+    let unit = (hash >> 11) as f64 / (1u64 << 53) as f64;
+    unit * 2. - 1.
+}
+
+/// Smoothly eases the interpolation parameter so the noise has no visible creases.
+fn fade(t: f64) -> f64 {
+    t * t * (3. - 2. * t)
+}
+
+/// Returns smooth 3D value noise in `[-1, 1]` at the given position.
+/// The lattice has unit spacing, so scale the position to change the feature size.
+pub fn value_noise(seed: u64, position: Vector3) -> f64 {
+    let cell = position.floor();
+    let (x0, y0, z0) = (cell.x as i64, cell.y as i64, cell.z as i64);
+    let tx = fade(position.x - cell.x);
+    let ty = fade(position.y - cell.y);
+    let tz = fade(position.z - cell.z);
+
+    let lerp = |a: f64, b: f64, t: f64| a + (b - a) * t;
+    let corner = |dx: i64, dy: i64, dz: i64| lattice(seed, x0 + dx, y0 + dy, z0 + dz);
+
+    let x00 = lerp(corner(0, 0, 0), corner(1, 0, 0), tx);
+    let x10 = lerp(corner(0, 1, 0), corner(1, 1, 0), tx);
+    let x01 = lerp(corner(0, 0, 1), corner(1, 0, 1), tx);
+    let x11 = lerp(corner(0, 1, 1), corner(1, 1, 1), tx);
+    lerp(lerp(x00, x10, ty), lerp(x01, x11, ty), tz)
+}
+
+/// Returns a vector whose components are independent value noise samples at the given position.
+pub fn vector_noise(seed: u64, position: Vector3) -> Vector3 {
+    Vector3::new(
+        value_noise(mix(seed), position),
+        value_noise(mix(seed ^ 1), position),
+        value_noise(mix(seed ^ 2), position),
+    )
+}
+
+/// A small, fast pseudo-random number generator (SplitMix64).
+/// Not suitable for cryptography, but a given seed always gives the same sequence.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Random {
+    /// Holds the current state of the generator.
+    state: u64,
+}
+
+impl Random {
+    /// Constructor
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    /// Returns the next pseudo-random 64-bit value.
+    pub fn next_u64(&mut self) -> u64 {
+        // `mix` adds the golden ratio increment itself, so feed it the previous state.
+        let value = mix(self.state);
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        value
+    }
+
+    /// Returns a pseudo-random value in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        // Use the top 53 bits to build a value in [0, 1).
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Returns a pseudo-random value in `[min, max)`, or `min` if the range is empty.
+    pub fn range(&mut self, min: f64, max: f64) -> f64 {
+        if max <= min {
+            return min;
+        }
+        min + (max - min) * self.next_f64()
+    }
+
+    /// Returns a pseudo-random unit vector, uniformly distributed over the sphere.
+    pub fn unit_vector(&mut self) -> Vector3 {
+        let z = self.range(-1., 1.);
+        let angle = self.range(0., std::f64::consts::TAU);
+        let radius = (1. - z * z).sqrt();
+        Vector3::new(radius * angle.cos(), radius * angle.sin(), z)
+    }
+
+    /// Returns a pseudo-random point, uniformly distributed inside the sphere of the given
+    /// radius around the origin.
+    pub fn in_sphere(&mut self, radius: f64) -> Vector3 {
+        self.unit_vector() * (radius * self.next_f64().cbrt())
+    }
+}
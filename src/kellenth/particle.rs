@@ -1,9 +1,18 @@
 //! Holds the particle class and all its properties
+//!
+//! # Determinism
+//! Addition, subtraction, multiplication, division and `sqrt` are correctly rounded
+//! by IEEE 754, and Rust never fuses them into `mul_add` on its own, so they give the
+//! same bits on every platform. Functions like `powf`, `exp` or `sin` come from the
+//! platform math library and may differ in the last bit. `integrate` uses `powf` for
+//! damping, so lockstep simulations should use `integrate_strict` instead.
 
 #[allow(unused, dead_code)]
 use crate::kellenth::core::*;
+use crate::kellenth::materials::MaterialId;
 
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Particle {
     /// Holds the position in world space of the particle
     pub position: Vector3,
@@ -23,6 +32,10 @@ pub struct Particle {
     /// The value will always be zero'd in the integration step
     accumulated_force: Vector3,
 
+    /// Holds the total acceleration, forces included, used by the last integration step.
+    /// Contact resolution uses it to spot the velocity built up during a single frame.
+    last_frame_acceleration: Vector3,
+
     /// Holds the inverse mass of the particle.
     /// Holding the actual mass instead would slow calculations because
     /// we calculate the acceleration by using `1/mass` and that division
@@ -30,6 +43,17 @@ pub struct Particle {
     ///
     /// # To make the particle immovable set an `inverseMass` of zero.
     inverse_mass: f64,
+
+    /// Holds the material of the particle, used to look up contact properties
+    /// in a `MaterialTable`.
+    pub material: MaterialId,
+
+    /// Holds the number of consecutive steps the particle has been at rest,
+    /// counted by a `ParticleWorld` with sleeping enabled.
+    rest_steps: u32,
+
+    /// Holds whether the particle is asleep, and so skipped by its world.
+    asleep: bool,
 }
 
 impl Particle {
@@ -45,56 +69,610 @@ impl Particle {
                 y: 0.,
                 z: 0.,
             },
+            last_frame_acceleration: acceleration,
             inverse_mass: 0.0,
+            material: MaterialId::DEFAULT,
+            rest_steps: 0,
+            asleep: false,
         }
     }
 
     // Returns the inverse mass of the particle.
-    pub fn get_inverse_mass(self) -> f64 {
+    pub fn get_inverse_mass(&self) -> f64 {
         self.inverse_mass
     }
 
     // Sets the inverse mass to given value.
-    pub fn set_inverse_mass(mut self, inverse_mass: f64) {
+    pub fn set_inverse_mass(&mut self, inverse_mass: f64) {
         self.inverse_mass = inverse_mass;
     }
 
     /// Returns the mass of the particle.
     /// If the object is immovable, returns `f64::MAX`
-    pub fn get_mass(self) -> f64 {
+    pub fn get_mass(&self) -> f64 {
         if self.inverse_mass == 0. {
             return f64::MAX;
         }
-        return 1. / self.inverse_mass;
+        1. / self.inverse_mass
     }
 
     /// Sets the mass of the object.
     /// It should not be zero.
     /// ### SMALL MASSES PRODUCE UNSTABLE RIGID BODIES UNDER SIMULATION
-    pub fn set_mass(mut self, mass: f64) {
+    pub fn set_mass(&mut self, mass: f64) {
         assert_ne!(mass, 0.);
         self.inverse_mass = 1. / mass;
     }
 
+    /// Returns true if the mass of the particle is not infinite.
+    pub fn has_finite_mass(&self) -> bool {
+        self.inverse_mass > 0.
+    }
+
+    /// Returns the speed of the particle, the magnitude of its velocity.
+    pub fn speed(&self) -> f64 {
+        self.velocity.magnitude()
+    }
+
+    /// Returns the square of the speed of the particle, which avoids a square root
+    /// when only comparing speeds.
+    pub fn speed_squared(&self) -> f64 {
+        self.velocity.square_magnitude()
+    }
+
+    /// Returns true if the particle moves slower than the given speed, and its velocity
+    /// changed from `previous_velocity`, the velocity it had `duration` ago, too slowly to
+    /// speed it up by more than that within a second. Useful to put idle particles to sleep.
+    ///
+    /// The change is the resulting motion, every force and impulse included, so a particle
+    /// held still by a contact is at rest however large the forces the contact balances.
+    pub fn at_rest(
+        &self,
+        linear_threshold: f64,
+        previous_velocity: Vector3,
+        duration: f64,
+    ) -> bool {
+        self.speed() < linear_threshold
+            && (self.velocity - previous_velocity).magnitude() < linear_threshold * duration
+    }
+
+    /// Returns true if the particle is awake. Particles only fall asleep in a
+    /// `ParticleWorld` with sleeping enabled.
+    pub fn is_awake(&self) -> bool {
+        !self.asleep
+    }
+
+    /// Wakes the particle up, or puts it to sleep, stopping it dead.
+    pub fn set_awake(&mut self, awake: bool) {
+        self.asleep = !awake;
+        self.rest_steps = 0;
+        if !awake {
+            self.velocity = Vector3::new(0., 0., 0.);
+        }
+    }
+
+    /// Counts one more step at rest, or starts counting again if the particle moves.
+    /// Returns the number of consecutive steps at rest.
+    pub(crate) fn count_rest_step(
+        &mut self,
+        linear_threshold: f64,
+        previous_velocity: Vector3,
+        duration: f64,
+    ) -> u32 {
+        self.rest_steps = if self.at_rest(linear_threshold, previous_velocity, duration) {
+            self.rest_steps.saturating_add(1)
+        } else {
+            0
+        };
+        self.rest_steps
+    }
+
+    /// Returns the total acceleration, forces included, used by the last integration step.
+    /// Before the first step this is the constant `acceleration`.
+    pub fn get_last_frame_acceleration(&self) -> Vector3 {
+        self.last_frame_acceleration
+    }
+
+    /// Adds the given force to the particle, to be applied at the next iteration only.
+    pub fn add_force(&mut self, force: Vector3) {
+        self.accumulated_force += force;
+    }
+
+    /// Changes the velocity of the particle instantly by `impulse * inverse_mass`.
+    /// Unlike `add_force`, the change doesn't depend on the frame duration.
+    /// Immovable particles are not affected.
+    pub fn apply_impulse(&mut self, impulse: Vector3) {
+        self.velocity.add_scaled_vector(impulse, self.inverse_mass);
+    }
+
+    /// Moves the particle to the given position without carrying over any motion.
+    /// The velocity is zeroed and the forces accumulated so far are cleared,
+    /// so the particle doesn't jolt after respawning.
+    pub fn teleport(&mut self, position: Vector3) {
+        self.position = position;
+        self.velocity = Vector3::new(0., 0., 0.);
+        self.clear_accumulator();
+    }
+
+    /// Returns a copy of the particle with its position and velocity taken from the local
+    /// frame of the given transform to world space, e.g. to attach a particle system to
+    /// a moving object. The velocity is only rotated, so it doesn't include the motion
+    /// of the frame itself.
+    pub fn transform_particle(&self, transform: &Matrix4) -> Particle {
+        let mut particle = *self;
+        particle.position = transform.transform(self.position);
+        particle.velocity = transform.transform_direction(self.velocity);
+        particle
+    }
+
     /// Integrates the particle forward in time by the given amount.
-    /// This function uses a Newton-Euler integration method, which
-    /// is a linear aproximation of the correct integral.
+    /// This function uses a semi-implicit Newton-Euler integration method,
+    /// which is a linear aproximation of the correct integral.
     /// Recieves the duration between the last two frames as a parameter.
     /// ### IT MAY BE INNACURATE IN SOME CASES
-    pub fn integrate(mut self, duration: f64) {
-        assert!(duration > 0.);
+    pub fn integrate(&mut self, duration: f64) {
+        // We don't integrate things with infinite mass.
+        if self.inverse_mass <= 0. {
+            return;
+        }
 
-        // Update the linear position
-        self.position.add_scaled_vector(self.velocity, duration);
+        assert!(duration > 0.);
 
         // Work out the acceleration from the force.
-        let res_acceleration = self.acceleration;
-        // resAcceleration.add_scaled_vector(self.get_inverse_mass());
+        let mut res_acceleration = self.acceleration;
+        res_acceleration.add_scaled_vector(self.accumulated_force, self.inverse_mass);
+        self.last_frame_acceleration = res_acceleration;
 
         // Update linear velocity from the acceleration
         self.velocity.add_scaled_vector(res_acceleration, duration);
 
         // Eliminate part of velocity with drag
         self.velocity *= f64::powf(self.damping, duration);
+
+        // Update the linear position using the new velocity.
+        // Doing this after the velocity update (semi-implicit Euler) keeps stiff
+        // systems, like the fake spring, from gaining energy every frame.
+        self.position.add_scaled_vector(self.velocity, duration);
+
+        // Clear the forces.
+        self.clear_accumulator();
+    }
+
+    /// Returns where the particle would be after one `integrate` of the given duration,
+    /// under its current velocity, acceleration and accumulated force, without changing it.
+    /// Useful for trajectory previews, like aiming arcs.
+    pub fn predict_position(&self, duration: f64) -> Vector3 {
+        // Things with infinite mass don't move.
+        if self.inverse_mass <= 0. {
+            return self.position;
+        }
+
+        // Work out the acceleration and the new velocity, exactly like `integrate`.
+        let mut res_acceleration = self.acceleration;
+        res_acceleration.add_scaled_vector(self.accumulated_force, self.inverse_mass);
+
+        let mut velocity = self.velocity;
+        velocity.add_scaled_vector(res_acceleration, duration);
+        velocity *= f64::powf(self.damping, duration);
+
+        // And move the position along the new velocity.
+        let mut position = self.position;
+        position.add_scaled_vector(velocity, duration);
+        position
+    }
+
+    /// Returns `steps` predicted positions evenly spaced over the given duration, the
+    /// last one at the end of it, for drawing an aiming arc.
+    /// Every sample is a `predict_position` of its own time from now.
+    pub fn sample_trajectory(&self, duration: f64, steps: usize) -> Vec<Vector3> {
+        (1..=steps)
+            .map(|step| self.predict_position(duration * step as f64 / steps as f64))
+            .collect()
+    }
+
+    /// Integrates the particle forward in time like `integrate`, using only additions
+    /// and multiplications in a fixed order, so the result is bit-identical on every platform.
+    /// The damping `damping^duration` is approximated by `1 - (1 - damping) * duration`,
+    /// which avoids `powf` and is close for short frames.
+    pub fn integrate_strict(&mut self, duration: f64) {
+        // We don't integrate things with infinite mass.
+        if self.inverse_mass <= 0. {
+            return;
+        }
+
+        assert!(duration > 0.);
+
+        // Work out the acceleration from the force, one component at a time.
+        let acceleration = Vector3::new(
+            self.acceleration.x + self.accumulated_force.x * self.inverse_mass,
+            self.acceleration.y + self.accumulated_force.y * self.inverse_mass,
+            self.acceleration.z + self.accumulated_force.z * self.inverse_mass,
+        );
+        self.last_frame_acceleration = acceleration;
+
+        // Update linear velocity from the acceleration, then apply the damping.
+        let damping = 1. - (1. - self.damping) * duration;
+        self.velocity = Vector3::new(
+            (self.velocity.x + acceleration.x * duration) * damping,
+            (self.velocity.y + acceleration.y * duration) * damping,
+            (self.velocity.z + acceleration.z * duration) * damping,
+        );
+
+        // Update the linear position using the new velocity.
+        self.position = Vector3::new(
+            self.position.x + self.velocity.x * duration,
+            self.position.y + self.velocity.y * duration,
+            self.position.z + self.velocity.z * duration,
+        );
+
+        // Clear the forces.
+        self.clear_accumulator();
+    }
+
+    /// Integrates the particle forward in time like `integrate`, but replaces the
+    /// exponential `damping` with a viscous drag force `-drag_coefficient * velocity`.
+    /// The drag is part of the force balance, so a falling particle reaches the
+    /// terminal velocity `mass * gravity / drag_coefficient`.
+    pub fn integrate_with_viscous_drag(&mut self, drag_coefficient: f64, duration: f64) {
+        // We don't integrate things with infinite mass.
+        if self.inverse_mass <= 0. {
+            return;
+        }
+
+        assert!(duration > 0.);
+
+        // Work out the acceleration from the force, drag included.
+        let mut res_acceleration = self.acceleration;
+        res_acceleration.add_scaled_vector(self.accumulated_force, self.inverse_mass);
+        res_acceleration.add_scaled_vector(self.velocity, -drag_coefficient * self.inverse_mass);
+        self.last_frame_acceleration = res_acceleration;
+
+        // Update linear velocity from the acceleration, then the position from the new velocity.
+        self.velocity.add_scaled_vector(res_acceleration, duration);
+        self.position.add_scaled_vector(self.velocity, duration);
+
+        // Clear the forces.
+        self.clear_accumulator();
+    }
+
+    /// Clears the forces applied to the particle.
+    /// Called automatically after each integration step.
+    pub fn clear_accumulator(&mut self) {
+        self.accumulated_force = Vector3 {
+            x: 0.,
+            y: 0.,
+            z: 0.,
+        };
+    }
+
+    /// Size in bytes of the layout produced by `to_le_bytes`.
+    pub const ENCODED_LEN: usize = 114;
+
+    /// Returns the full state of the particle as little-endian bytes:
+    /// position, velocity, acceleration, damping, accumulated force, inverse mass and material, in order.
+    /// The layout is the same on every host.
+    pub fn to_le_bytes(&self) -> [u8; Self::ENCODED_LEN] {
+        let mut bytes = [0; Self::ENCODED_LEN];
+        bytes[0..24].copy_from_slice(&self.position.to_le_bytes());
+        bytes[24..48].copy_from_slice(&self.velocity.to_le_bytes());
+        bytes[48..72].copy_from_slice(&self.acceleration.to_le_bytes());
+        bytes[72..80].copy_from_slice(&self.damping.to_le_bytes());
+        bytes[80..104].copy_from_slice(&self.accumulated_force.to_le_bytes());
+        bytes[104..112].copy_from_slice(&self.inverse_mass.to_le_bytes());
+        bytes[112..114].copy_from_slice(&self.material.0.to_le_bytes());
+        bytes
+    }
+
+    /// Reads a particle back from the layout produced by `to_le_bytes`.
+    /// The last frame acceleration isn't stored, so it starts out as the constant `acceleration`.
+    pub fn from_le_bytes(bytes: &[u8; Self::ENCODED_LEN]) -> Self {
+        let vector = |offset: usize| {
+            let mut vector = [0; 24];
+            vector.copy_from_slice(&bytes[offset..offset + 24]);
+            Vector3::from_le_bytes(&vector)
+        };
+        let scalar = |offset: usize| {
+            let mut scalar = [0; 8];
+            scalar.copy_from_slice(&bytes[offset..offset + 8]);
+            f64::from_le_bytes(scalar)
+        };
+        Self {
+            position: vector(0),
+            velocity: vector(24),
+            acceleration: vector(48),
+            damping: scalar(72),
+            accumulated_force: vector(80),
+            last_frame_acceleration: vector(48),
+            inverse_mass: scalar(104),
+            material: MaterialId(u16::from_le_bytes([bytes[112], bytes[113]])),
+            rest_steps: 0,
+            asleep: false,
+        }
+    }
+
+    /// Number of words in the key produced by `bit_key`.
+    pub const BIT_KEY_LEN: usize = 15;
+
+    /// Returns the bit patterns of the state stored by `to_le_bytes`, in the same order,
+    /// as a key for hash maps and deduplication. The particle itself isn't `Eq` or `Hash`
+    /// because of its floats.
+    ///
+    /// The key compares bits, not values: `0.0` and `-0.0` give different keys, and so do
+    /// NaNs with different payloads, while two particles holding the same NaN give the
+    /// same key even though NaN never equals itself.
+    pub fn bit_key(&self) -> [u64; Self::BIT_KEY_LEN] {
+        let p = &self.position;
+        let v = &self.velocity;
+        let a = &self.acceleration;
+        let f = &self.accumulated_force;
+        [
+            p.x.to_bits(),
+            p.y.to_bits(),
+            p.z.to_bits(),
+            v.x.to_bits(),
+            v.y.to_bits(),
+            v.z.to_bits(),
+            a.x.to_bits(),
+            a.y.to_bits(),
+            a.z.to_bits(),
+            self.damping.to_bits(),
+            f.x.to_bits(),
+            f.y.to_bits(),
+            f.z.to_bits(),
+            self.inverse_mass.to_bits(),
+            self.material.0 as u64,
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ZERO: Vector3 = Vector3::new(0., 0., 0.);
+
+    /// Returns a particle with every stored field set to something distinct.
+    fn sample_particle() -> Particle {
+        let mut particle = Particle::new(
+            Vector3::new(1., 2., 3.),
+            Vector3::new(-4., 5., -6.),
+            Vector3::new(0., -9.81, 0.),
+            0.95,
+        );
+        particle.set_mass(4.);
+        particle.add_force(Vector3::new(0.5, 0., -0.25));
+        particle.material = MaterialId(0x0102);
+        particle
+    }
+
+    #[test]
+    fn le_bytes_round_trip_in_little_endian_order() {
+        let particle = sample_particle();
+        let bytes = particle.to_le_bytes();
+        assert_eq!(bytes[0..8], 1f64.to_le_bytes());
+        assert_eq!(bytes[104..112], [0, 0, 0, 0, 0, 0, 0xd0, 0x3f]);
+        assert_eq!(bytes[112..114], [0x02, 0x01]);
+
+        let back = Particle::from_le_bytes(&bytes);
+        assert_eq!(back.bit_key(), particle.bit_key());
+        assert_eq!(back.to_le_bytes(), bytes);
+    }
+
+    #[test]
+    fn teleport_drops_velocity_and_forces() {
+        let mut particle = sample_particle();
+        let target = Vector3::new(-7., 0.5, 2.);
+        particle.teleport(target);
+        assert_eq!(particle.position.manhattan_distance(&target), 0.);
+        assert_eq!(particle.velocity.magnitude(), 0.);
+        assert_eq!(particle.accumulated_force.magnitude(), 0.);
+
+        // The constant acceleration is kept, so it still falls afterwards.
+        particle.integrate(0.1);
+        assert!(particle.velocity.y < 0.);
+        assert_eq!(particle.velocity.x, 0.);
+    }
+
+    #[test]
+    fn apply_impulse_scales_by_inverse_mass() {
+        let mut particle = Particle::new(
+            Vector3::new(0., 0., 0.),
+            Vector3::new(1., 0., 0.),
+            Vector3::new(0., 0., 0.),
+            1.,
+        );
+        particle.set_mass(2.);
+        particle.apply_impulse(Vector3::new(4., -2., 1.));
+        assert_eq!(
+            particle
+                .velocity
+                .manhattan_distance(&Vector3::new(3., -1., 0.5)),
+            0.
+        );
+
+        let mut immovable = particle;
+        immovable.set_inverse_mass(0.);
+        immovable.apply_impulse(Vector3::new(100., 0., 0.));
+        assert_eq!(
+            immovable.velocity.manhattan_distance(&particle.velocity),
+            0.
+        );
+    }
+
+    #[test]
+    fn viscous_drag_reaches_the_analytic_terminal_velocity() {
+        let mut particle = Particle::new(ZERO, ZERO, Vector3::new(0., -9.81, 0.), 1.);
+        particle.set_mass(2.);
+        let drag = 0.5;
+        for _ in 0..10_000 {
+            particle.integrate_with_viscous_drag(drag, 0.01);
+        }
+        let terminal = -2. * 9.81 / drag;
+        assert!((particle.velocity.y - terminal).abs() < 1e-9);
+        assert!(particle.velocity.x == 0. && particle.velocity.z == 0.);
+
+        // The drag opposes a push, so a heavier particle coasts further.
+        let mut light = Particle::new(ZERO, Vector3::new(1., 0., 0.), ZERO, 1.);
+        light.set_mass(1.);
+        let mut heavy = light;
+        heavy.set_mass(10.);
+        for _ in 0..100 {
+            light.integrate_with_viscous_drag(drag, 0.01);
+            heavy.integrate_with_viscous_drag(drag, 0.01);
+        }
+        assert!(light.velocity.x > 0. && light.velocity.x < heavy.velocity.x);
+        assert!(heavy.velocity.x < 1.);
+    }
+
+    #[test]
+    fn speed_is_the_magnitude_of_the_velocity() {
+        let particle = sample_particle();
+        assert_eq!(particle.speed(), particle.velocity.magnitude());
+        assert_eq!(
+            particle.speed_squared(),
+            particle.velocity.square_magnitude()
+        );
+        assert_eq!(Particle::new(ZERO, ZERO, ZERO, 1.).speed(), 0.);
+    }
+
+    #[test]
+    fn strict_integration_is_reproducible() {
+        let run = || {
+            let mut particle = sample_particle();
+            for step in 0..1000 {
+                particle.add_force(Vector3::new((step % 7) as f64 * 0.1, 0.3, -0.2));
+                particle.integrate_strict(1. / 60.);
+            }
+            particle.to_le_bytes()
+        };
+        assert_eq!(run(), run());
+
+        // Short frames stay close to the exact damping of `integrate`.
+        let mut strict = sample_particle();
+        let mut exact = sample_particle();
+        strict.integrate_strict(1. / 60.);
+        exact.integrate(1. / 60.);
+        assert!(strict.velocity.chebyshev_distance(&exact.velocity) < 1e-3);
+    }
+
+    #[test]
+    fn predict_position_matches_integrating_a_copy() {
+        let particle = sample_particle();
+        let key = particle.bit_key();
+        let mut copy = particle;
+        copy.integrate(0.25);
+        assert_eq!(
+            particle
+                .predict_position(0.25)
+                .manhattan_distance(&copy.position),
+            0.
+        );
+        // The prediction leaves the particle, forces included, untouched.
+        assert_eq!(particle.bit_key(), key);
+        assert!(particle.accumulated_force.magnitude() > 0.);
+
+        let fixed = Particle::new(Vector3::new(1., 1., 1.), Vector3::new(1., 0., 0.), ZERO, 1.);
+        assert_eq!(
+            fixed
+                .predict_position(10.)
+                .manhattan_distance(&fixed.position),
+            0.
+        );
+    }
+
+    #[test]
+    fn trajectory_samples_end_at_the_prediction() {
+        let particle = sample_particle();
+        let samples = particle.sample_trajectory(2., 8);
+        assert_eq!(samples.len(), 8);
+        assert_eq!(
+            samples[7].manhattan_distance(&particle.predict_position(2.)),
+            0.
+        );
+        assert_eq!(
+            samples[3].manhattan_distance(&particle.predict_position(1.)),
+            0.
+        );
+        assert!(particle.sample_trajectory(2., 0).is_empty());
+    }
+
+    #[test]
+    fn at_rest_needs_low_speed_and_little_change_in_velocity() {
+        let mut particle = Particle::new(ZERO, Vector3::new(0., 0., 5.), ZERO, 1.);
+        particle.set_mass(2.);
+        assert!(!particle.at_rest(0.1, particle.velocity, 0.01));
+
+        particle.velocity = Vector3::new(0.01, 0., 0.);
+        assert!(particle.at_rest(0.1, particle.velocity, 0.01));
+
+        // Speeding up by 0.005 in 0.01s is 0.5 every second.
+        let previous = particle.velocity;
+        particle.velocity.y += 0.005;
+        assert!(!particle.at_rest(0.1, previous, 0.01));
+        assert!(particle.at_rest(1., previous, 0.01));
+
+        // Forces that don't change the velocity don't matter.
+        particle.add_force(Vector3::new(0., 100., 0.));
+        assert!(particle.at_rest(1., previous, 0.01));
+    }
+
+    #[test]
+    fn transform_particle_moves_it_into_world_space() {
+        let quarter_turn =
+            Quaternion::from_axis_angle(Vector3::new(0., 0., 1.), std::f64::consts::FRAC_PI_2);
+        let transform =
+            Matrix4::from_orientation_and_position(quarter_turn, Vector3::new(10., 0., -2.));
+        let local = Particle::new(
+            Vector3::new(1., 0., 3.),
+            Vector3::new(0., 2., 0.),
+            ZERO,
+            0.9,
+        );
+
+        let world = local.transform_particle(&transform);
+        assert!(
+            world
+                .position
+                .chebyshev_distance(&Vector3::new(10., 1., 1.))
+                < 1e-12
+        );
+        assert!(
+            world
+                .velocity
+                .chebyshev_distance(&Vector3::new(-2., 0., 0.))
+                < 1e-12
+        );
+        assert_eq!(world.damping, local.damping);
+        // The original stays in its local frame.
+        assert_eq!(
+            local.position.manhattan_distance(&Vector3::new(1., 0., 3.)),
+            0.
+        );
+    }
+
+    #[test]
+    fn identical_particles_share_a_bit_key() {
+        use std::collections::HashSet;
+
+        let particle = sample_particle();
+        let copy = particle;
+        assert_eq!(particle.bit_key(), copy.bit_key());
+        let mut keys = HashSet::new();
+        assert!(keys.insert(particle.bit_key()));
+        assert!(!keys.insert(copy.bit_key()));
+
+        // Any change to the state changes the key, even one the values would hide.
+        let mut moved = particle;
+        moved.position.z = -0.;
+        let mut flipped = moved;
+        flipped.position.z = 0.;
+        assert_ne!(moved.bit_key(), flipped.bit_key());
+        assert!(keys.insert(moved.bit_key()));
+
+        // The same NaN gives the same key, even though it never equals itself.
+        let mut lost = particle;
+        lost.velocity.x = f64::NAN;
+        let lost_copy = lost;
+        assert_eq!(lost.bit_key(), lost_copy.bit_key());
     }
 }
@@ -0,0 +1,1564 @@
+//! Holds the rigid body class and all its properties.
+//!
+//! Unlike a particle, a rigid body has an orientation and can rotate, so it can
+//! stand for objects with extent.
+//!
+//! # Sleeping
+//! Bodies that barely move for a while fall asleep: `integrate` skips them until a
+//! force, an impulse or `set_awake` wakes them up. How still a body has to be is set
+//! for every body at once by `set_sleep_epsilon`, unless the body overrides it with
+//! `RigidBody::set_sleep_epsilon_override`, which keeps one simulation's tuning from
+//! changing every other one.
+
+mod builder;
+mod set;
+
+pub use self::builder::RigidBodyBuilder;
+pub use self::set::{BodyHandle, RigidBodySet};
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::kellenth::core::*;
+
+/// Holds the bits of the sleep epsilon, shared by every body.
+static SLEEP_EPSILON: AtomicU64 = AtomicU64::new(0x3FD3_3333_3333_3333);
+
+/// Returns the motion under which bodies fall asleep, 0.3 unless set otherwise.
+pub fn get_sleep_epsilon() -> f64 {
+    f64::from_bits(SLEEP_EPSILON.load(Ordering::Relaxed))
+}
+
+/// Sets the motion under which bodies fall asleep, for every body without an override.
+/// The motion is the sum of the squares of the linear and angular speeds, so a body
+/// sleeps once it moves slower than about `sqrt(epsilon)` for a while.
+pub fn set_sleep_epsilon(epsilon: f64) {
+    SLEEP_EPSILON.store(epsilon.to_bits(), Ordering::Relaxed);
+}
+
+/// How a body is moved by the simulation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum BodyType {
+    /// The body is moved by forces, impulses and contacts.
+    #[default]
+    Dynamic,
+
+    /// The body moves with the velocity and rotation set by the user, like a moving
+    /// platform or a door. It pushes dynamic bodies but is never pushed back, so it
+    /// has an infinite mass, and ignores forces and impulses. Bodies resting on it are
+    /// carried along by the friction of their contacts, see `contacts::rigid`.
+    Kinematic,
+
+    /// The body never moves. It has an infinite mass and ignores forces and impulses.
+    Static,
+}
+
+/// A body that moves and turns under forces and torques, with a mass and an inertia tensor.
+///
+/// With the `serde` feature the body can be saved and loaded. Its whole state is saved,
+/// but for the transform matrix and the world space inertia tensor, which are derived
+/// from it again on load, and the state before the last step used by
+/// `interpolated_transform`, which starts over at the loaded one. Loading fails if the orientation isn't normalized or a
+/// damping isn't in [0, 1]. A loaded body moves exactly like the saved one, as long as
+/// the format reads floats back exactly, like `serde_json` with `float_roundtrip`.
+#[derive(Debug, Clone, Copy)]
+pub struct RigidBody {
+    /// Holds the position in world space of the origin of the body, which is its
+    /// center of mass unless offset by `set_center_of_mass`.
+    pub position: Vector3,
+
+    /// Holds the angular orientation of the body in world space.
+    pub orientation: Quaternion,
+
+    /// Holds the linear velocity of the center of mass of the body in world space.
+    pub velocity: Vector3,
+
+    /// Holds the angular velocity, or rotation, of the body in world space.
+    pub rotation: Vector3,
+
+    /// Holds the acceleration of the body, like gravity.
+    pub acceleration: Vector3,
+
+    /// Holds the amount of damping applied to linear motion.
+    pub linear_damping: f64,
+
+    /// Holds the amount of damping applied to angular motion,
+    /// independently from the linear one.
+    pub angular_damping: f64,
+
+    /// Holds the highest angular speed, in radians per second, the body can reach
+    /// by integration, if any.
+    max_angular_velocity: Option<f64>,
+
+    /// Holds whether integration includes the gyroscopic torque.
+    gyroscopic: bool,
+
+    /// Holds the factor the gravity acting on the body is scaled by.
+    gravity_scale: f64,
+
+    /// Holds the gravity acting on the body instead of the gravity of the world, if any.
+    gravity_override: Option<Vector3>,
+
+    /// Holds the inverse mass of the body.
+    /// # To make the body immovable set an `inverse_mass` of zero.
+    inverse_mass: f64,
+
+    /// Holds the inverse of the inertia tensor of the body about its center of mass, in body space.
+    /// # To make the body unable to rotate set it to zero.
+    inverse_inertia_tensor: Matrix3,
+
+    /// Holds the center of mass of the body, in body space.
+    center_of_mass: Vector3,
+
+    /// Holds the amount of accumulated force to be applied at the next integration step.
+    force_accumulator: Vector3,
+
+    /// Holds the amount of accumulated torque to be applied at the next integration step.
+    torque_accumulator: Vector3,
+
+    /// Holds the linear acceleration, forces included, used by the last integration step.
+    last_frame_acceleration: Vector3,
+
+    /// Holds the transform from body space to world space.
+    /// Derived from the position and orientation by `calculate_derived_data`.
+    transform_matrix: Matrix4,
+
+    /// Holds the inverse of the inertia tensor of the body, in world space.
+    /// Derived from the orientation by `calculate_derived_data`.
+    inverse_inertia_tensor_world: Matrix3,
+
+    /// Holds a recency-weighted average of the motion of the body,
+    /// compared against the sleep epsilon to put it to sleep.
+    motion: f64,
+
+    /// Holds whether the body is awake, and so integrated.
+    is_awake: bool,
+
+    /// Holds whether the body is allowed to fall asleep.
+    can_sleep: bool,
+
+    /// Holds the motion under which the body falls asleep instead of the global one, if any.
+    sleep_epsilon: Option<f64>,
+
+    /// Holds how the body is moved by the simulation.
+    body_type: BodyType,
+
+    /// Holds a number left to the user, e.g. the game entity the body stands for.
+    user_index: u64,
+
+    /// Holds the position at the start of the last integration step, for rendering.
+    previous_position: Vector3,
+
+    /// Holds the orientation at the start of the last integration step, for rendering.
+    previous_orientation: Quaternion,
+}
+
+impl RigidBody {
+    /// The highest angular speed reached by integration, unless set otherwise.
+    /// Fast enough for wheels and tumbling debris, while keeping the orientation update,
+    /// which is only accurate for small turns, under one radian per frame at 60 Hz.
+    pub const DEFAULT_MAX_ANGULAR_VELOCITY: f64 = 50.;
+
+    /// Constructor
+    /// The body starts at rest and immovable, with a damping of 0.99 for both
+    /// linear and angular motion, until given a mass and an inertia tensor.
+    pub fn new(position: Vector3, orientation: Quaternion) -> Self {
+        let zero = Vector3::new(0., 0., 0.);
+        let mut body = Self {
+            position,
+            orientation,
+            velocity: zero,
+            rotation: zero,
+            acceleration: zero,
+            linear_damping: 0.99,
+            angular_damping: 0.99,
+            max_angular_velocity: Some(Self::DEFAULT_MAX_ANGULAR_VELOCITY),
+            gyroscopic: false,
+            gravity_scale: 1.,
+            gravity_override: None,
+            inverse_mass: 0.,
+            inverse_inertia_tensor: Matrix3::diagonal(0., 0., 0.),
+            center_of_mass: zero,
+            force_accumulator: zero,
+            torque_accumulator: zero,
+            last_frame_acceleration: zero,
+            transform_matrix: Matrix4::identity(),
+            inverse_inertia_tensor_world: Matrix3::diagonal(0., 0., 0.),
+            motion: get_sleep_epsilon() * 2.,
+            is_awake: true,
+            can_sleep: true,
+            sleep_epsilon: None,
+            body_type: BodyType::Dynamic,
+            user_index: 0,
+            previous_position: position,
+            previous_orientation: orientation,
+        };
+        body.calculate_derived_data();
+        body.previous_orientation = body.orientation;
+        body
+    }
+
+    /// Returns the number left to the user, 0 unless set otherwise.
+    pub fn get_user_index(&self) -> u64 {
+        self.user_index
+    }
+
+    /// Sets a number left to the user, to find the game entity the body stands for,
+    /// e.g. from a contact. It is saved along with the body.
+    pub fn set_user_index(&mut self, user_index: u64) {
+        self.user_index = user_index;
+    }
+
+    /// Returns the center of mass of the body, in body space.
+    pub fn get_center_of_mass(&self) -> Vector3 {
+        self.center_of_mass
+    }
+
+    /// Sets the center of mass of the body, in body space, for bodies whose origin
+    /// is a corner or a pivot. The position and the transform stay those of the
+    /// origin, while forces turn the body about its center of mass, which the
+    /// inertia tensor and the velocity refer to.
+    pub fn set_center_of_mass(&mut self, center_of_mass: Vector3) {
+        self.center_of_mass = center_of_mass;
+    }
+
+    /// Returns the center of mass of the body, in world space.
+    pub fn get_center_of_mass_world(&self) -> Vector3 {
+        self.position
+            + self
+                .transform_matrix
+                .transform_direction(self.center_of_mass)
+    }
+
+    /// Returns the highest angular speed, in radians per second, the body can reach by integration.
+    pub fn get_max_angular_velocity(&self) -> Option<f64> {
+        self.max_angular_velocity
+    }
+
+    /// Sets the highest angular speed, in radians per second, the body can reach by
+    /// integration, or `None` for no limit. Torques beyond it are wasted.
+    pub fn set_max_angular_velocity(&mut self, max_angular_velocity: Option<f64>) {
+        self.max_angular_velocity = max_angular_velocity;
+    }
+
+    /// Returns whether integration includes the gyroscopic torque.
+    pub fn is_gyroscopic(&self) -> bool {
+        self.gyroscopic
+    }
+
+    /// Sets whether integration includes the gyroscopic torque `ω × (I ω)`, which makes
+    /// bodies with uneven inertia tumble like they should, e.g. a box flipping when spun
+    /// about its intermediate axis. It is off by default, as it costs a matrix inversion
+    /// every step and isn't needed for bodies whose inertia is the same about every axis.
+    pub fn set_gyroscopic(&mut self, gyroscopic: bool) {
+        self.gyroscopic = gyroscopic;
+    }
+
+    /// Returns the factor the gravity acting on the body is scaled by.
+    pub fn get_gravity_scale(&self) -> f64 {
+        self.gravity_scale
+    }
+
+    /// Sets the factor the gravity acting on the body is scaled by: 1 by default,
+    /// negative for balloons, or 0 for bodies that shouldn't feel gravity at all.
+    pub fn set_gravity_scale(&mut self, gravity_scale: f64) {
+        self.gravity_scale = gravity_scale;
+    }
+
+    /// Returns the gravity acting on the body instead of the gravity of the world, if any.
+    pub fn get_gravity_override(&self) -> Option<Vector3> {
+        self.gravity_override
+    }
+
+    /// Sets the gravity acting on the body instead of the gravity of the world,
+    /// or `None` to follow the world again. It is still scaled by the gravity scale.
+    pub fn set_gravity_override(&mut self, gravity_override: Option<Vector3>) {
+        self.gravity_override = gravity_override;
+    }
+
+    /// Returns the gravity acting on the body in a world with the given gravity:
+    /// the override, or else the world gravity, times the gravity scale.
+    /// A gravity scale of zero always gives exactly zero.
+    pub fn get_effective_gravity(&self, world_gravity: Vector3) -> Vector3 {
+        if self.gravity_scale == 0. {
+            return Vector3::new(0., 0., 0.);
+        }
+        self.gravity_override.unwrap_or(world_gravity) * self.gravity_scale
+    }
+
+    /// Returns how the body is moved by the simulation.
+    pub fn get_body_type(&self) -> BodyType {
+        self.body_type
+    }
+
+    /// Sets how the body is moved by the simulation.
+    /// The mass and inertia tensor are kept for when the body becomes dynamic again.
+    pub fn set_body_type(&mut self, body_type: BodyType) {
+        self.body_type = body_type;
+        self.calculate_derived_data();
+    }
+
+    /// Returns true if the body is moved by forces, impulses and contacts.
+    pub fn is_dynamic(&self) -> bool {
+        self.body_type == BodyType::Dynamic
+    }
+
+    /// Returns the inverse mass of the body, which is zero unless it is dynamic.
+    pub fn get_inverse_mass(&self) -> f64 {
+        if !self.is_dynamic() {
+            return 0.;
+        }
+        self.inverse_mass
+    }
+
+    /// Sets the inverse mass to given value.
+    pub fn set_inverse_mass(&mut self, inverse_mass: f64) {
+        self.inverse_mass = inverse_mass;
+    }
+
+    /// Returns the mass of the body.
+    /// If the object is immovable, returns `f64::MAX`
+    pub fn get_mass(&self) -> f64 {
+        let inverse_mass = self.get_inverse_mass();
+        if inverse_mass == 0. {
+            return f64::MAX;
+        }
+        1. / inverse_mass
+    }
+
+    /// Sets the mass of the body.
+    /// It should not be zero.
+    pub fn set_mass(&mut self, mass: f64) {
+        assert_ne!(mass, 0.);
+        self.inverse_mass = 1. / mass;
+    }
+
+    /// Returns true if the mass of the body is not infinite.
+    pub fn has_finite_mass(&self) -> bool {
+        self.get_inverse_mass() > 0.
+    }
+
+    /// Returns the inertia tensor, in body space, or `None` if the body can't turn
+    /// about some axis.
+    pub fn get_inertia_tensor(&self) -> Option<Matrix3> {
+        self.inverse_inertia_tensor.inverse()
+    }
+
+    /// Sets the inertia tensor, in body space, like the ones from the `inertia` module.
+    /// The tensor must be invertible. To keep the body from turning about some axis,
+    /// set an inverse tensor with zeroes instead.
+    /// The world space tensor is only updated by `calculate_derived_data`.
+    pub fn set_inertia_tensor(&mut self, inertia_tensor: &Matrix3) {
+        self.inverse_inertia_tensor = inertia_tensor
+            .inverse()
+            .expect("the inertia tensor must be invertible");
+    }
+
+    /// Returns the inverse of the inertia tensor, in body space.
+    pub fn get_inverse_inertia_tensor(&self) -> Matrix3 {
+        self.inverse_inertia_tensor
+    }
+
+    /// Sets the inverse of the inertia tensor, in body space.
+    /// The world space tensor is only updated by `calculate_derived_data`.
+    pub fn set_inverse_inertia_tensor(&mut self, inverse_inertia_tensor: Matrix3) {
+        self.inverse_inertia_tensor = inverse_inertia_tensor;
+    }
+
+    /// Returns the inertia tensor in world space, or `None` if the body can't turn
+    /// about some axis, as of the last `calculate_derived_data`.
+    pub fn get_inertia_tensor_world(&self) -> Option<Matrix3> {
+        if !self.is_dynamic() {
+            return None;
+        }
+        // Rotate the body space tensor rather than inverting the world space inverse,
+        // whose rounding would hide a locked axis.
+        let rotation = self.transform_matrix.get_rotation();
+        self.get_inertia_tensor()
+            .map(|inertia| rotation * inertia * rotation.transpose())
+    }
+
+    /// Returns the linear momentum of the body, `m v`, or zero if its mass is infinite.
+    pub fn linear_momentum(&self) -> Vector3 {
+        if !self.has_finite_mass() {
+            return Vector3::new(0., 0., 0.);
+        }
+        self.velocity * self.get_mass()
+    }
+
+    /// Returns the angular momentum of the body about its center of mass, in world space,
+    /// `I ω`. It is zero if the mass of the body is infinite, or if it can't turn about
+    /// some axis, as the momentum about that axis has no finite value.
+    pub fn angular_momentum_world(&self) -> Vector3 {
+        if !self.has_finite_mass() {
+            return Vector3::new(0., 0., 0.);
+        }
+        match self.get_inertia_tensor_world() {
+            Some(inertia_tensor) => inertia_tensor * self.rotation,
+            None => Vector3::new(0., 0., 0.),
+        }
+    }
+
+    /// Returns the kinetic energy of the body, `½ m v² + ½ ωᵀ I ω`, or zero if its
+    /// mass is infinite. Like `angular_momentum_world`, the rotational part is left out
+    /// for bodies that can't turn about some axis.
+    pub fn kinetic_energy(&self) -> f64 {
+        if !self.has_finite_mass() {
+            return 0.;
+        }
+        0.5 * (self.linear_momentum() * self.velocity
+            + self.angular_momentum_world() * self.rotation)
+    }
+
+    /// Returns the force accumulated for the next integration step.
+    pub fn get_accumulated_force(&self) -> Vector3 {
+        self.force_accumulator
+    }
+
+    /// Returns the torque accumulated for the next integration step.
+    pub fn get_accumulated_torque(&self) -> Vector3 {
+        self.torque_accumulator
+    }
+
+    /// Returns the linear acceleration, forces included, used by the last integration step.
+    pub fn get_last_frame_acceleration(&self) -> Vector3 {
+        self.last_frame_acceleration
+    }
+
+    /// Returns true if the body is awake, and so integrated.
+    pub fn is_awake(&self) -> bool {
+        self.is_awake
+    }
+
+    /// Wakes the body up, or puts it to sleep, stopping it dead.
+    /// A woken body is given some motion so it doesn't fall asleep again at once.
+    pub fn set_awake(&mut self, awake: bool) {
+        if awake {
+            self.is_awake = true;
+            self.motion = self.get_effective_sleep_epsilon() * 2.;
+        } else {
+            self.is_awake = false;
+            self.velocity = Vector3::new(0., 0., 0.);
+            self.rotation = Vector3::new(0., 0., 0.);
+        }
+    }
+
+    /// Returns true if the body is allowed to fall asleep.
+    pub fn can_sleep(&self) -> bool {
+        self.can_sleep
+    }
+
+    /// Sets whether the body is allowed to fall asleep, like one the user controls.
+    /// A sleeping body that can't sleep anymore is woken up.
+    pub fn set_can_sleep(&mut self, can_sleep: bool) {
+        self.can_sleep = can_sleep;
+        if !can_sleep && !self.is_awake {
+            self.set_awake(true);
+        }
+    }
+
+    /// Returns the motion under which the body falls asleep instead of the global one, if any.
+    pub fn get_sleep_epsilon_override(&self) -> Option<f64> {
+        self.sleep_epsilon
+    }
+
+    /// Sets the motion under which the body falls asleep instead of the global one,
+    /// or `None` to follow `set_sleep_epsilon` again.
+    pub fn set_sleep_epsilon_override(&mut self, sleep_epsilon: Option<f64>) {
+        self.sleep_epsilon = sleep_epsilon;
+    }
+
+    /// Returns the motion under which the body falls asleep: the override, or else the
+    /// global sleep epsilon.
+    pub fn get_effective_sleep_epsilon(&self) -> f64 {
+        self.sleep_epsilon.unwrap_or_else(get_sleep_epsilon)
+    }
+
+    /// Returns the recency-weighted average of the motion of the body.
+    pub fn get_motion(&self) -> f64 {
+        self.motion
+    }
+
+    /// Adds the given force to the center of mass of the body, to be applied at the
+    /// next iteration only. It doesn't make the body turn.
+    /// Like every force and torque, it wakes the body up, keeping its motion so one too
+    /// weak to move it, like gravity on a resting body, lets it fall asleep again.
+    /// Kinematic and static bodies ignore forces and torques.
+    pub fn add_force(&mut self, force: Vector3) {
+        if !self.is_dynamic() {
+            return;
+        }
+        self.force_accumulator += force;
+        self.is_awake = true;
+    }
+
+    /// Adds the given torque to the body, to be applied at the next iteration only.
+    pub fn add_torque(&mut self, torque: Vector3) {
+        if !self.is_dynamic() {
+            return;
+        }
+        self.torque_accumulator += torque;
+        self.is_awake = true;
+    }
+
+    /// Adds the given force to the given point of the body, both in world space,
+    /// to be applied at the next iteration only.
+    /// Unless the point is the center of mass, the force also makes the body turn.
+    pub fn add_force_at_point(&mut self, force: Vector3, point: Vector3) {
+        if !self.is_dynamic() {
+            return;
+        }
+
+        // Convert to coordinates relative to the center of mass.
+        let arm = point - self.get_center_of_mass_world();
+
+        self.force_accumulator += force;
+        self.torque_accumulator += arm % force;
+        self.is_awake = true;
+    }
+
+    /// Adds the given force, in world space, to the given point, in body space.
+    /// Useful for spring forces, or other forces fixed to the body.
+    /// Uses the transform matrix of the last `calculate_derived_data`.
+    pub fn add_force_at_body_point(&mut self, force: Vector3, point: Vector3) {
+        // Convert to coordinates in world space.
+        let point = self.get_point_in_world_space(point);
+        self.add_force_at_point(force, point);
+    }
+
+    /// Changes the velocity of the body instantly by `impulse * inverse_mass`.
+    /// Like every impulse, it doesn't affect immovable, kinematic or static bodies, and wakes sleeping ones
+    /// up with enough motion that they don't fall asleep again before moving.
+    pub fn apply_impulse(&mut self, impulse: Vector3) {
+        if !self.prepare_for_impulse() {
+            return;
+        }
+        self.velocity.add_scaled_vector(impulse, self.inverse_mass);
+    }
+
+    /// Applies the given impulse at the given point of the body, both in world space,
+    /// changing the angular velocity as well, by `I⁻¹ ((point - center of mass) × impulse)`.
+    pub fn apply_impulse_at_point(&mut self, impulse: Vector3, point: Vector3) {
+        if !self.prepare_for_impulse() {
+            return;
+        }
+        self.velocity.add_scaled_vector(impulse, self.inverse_mass);
+        let arm = point - self.get_center_of_mass_world();
+        self.rotation += self.inverse_inertia_tensor_world * (arm % impulse);
+    }
+
+    /// Changes the angular velocity of the body instantly by `I⁻¹ angular_impulse`,
+    /// with the angular impulse in world space.
+    pub fn apply_torque_impulse(&mut self, angular_impulse: Vector3) {
+        if !self.prepare_for_impulse() {
+            return;
+        }
+        self.rotation += self.inverse_inertia_tensor_world * angular_impulse;
+    }
+
+    /// Wakes the body up for an impulse. Returns false if the body can't be moved by it.
+    fn prepare_for_impulse(&mut self) -> bool {
+        if !self.has_finite_mass() {
+            return false;
+        }
+        if !self.is_awake {
+            self.set_awake(true);
+        }
+        true
+    }
+
+    /// Clears the forces and torques applied to the body.
+    /// Called automatically after each integration step.
+    pub fn clear_accumulators(&mut self) {
+        self.force_accumulator = Vector3::new(0., 0., 0.);
+        self.torque_accumulator = Vector3::new(0., 0., 0.);
+    }
+
+    /// Returns the transform from body space to world space,
+    /// as of the last `calculate_derived_data`.
+    pub fn get_transform_matrix(&self) -> Matrix4 {
+        self.transform_matrix
+    }
+
+    /// Returns the transform from body space to world space the given fraction of the
+    /// way through the last integration step, for rendering between physics steps.
+    /// An `alpha` of 0 is the start of the step and 1 its end, the current transform;
+    /// with a fixed step, it is the time left over divided by the step.
+    pub fn interpolated_transform(&self, alpha: f64) -> Matrix4 {
+        let position = self.previous_position.lerp(&self.position, alpha);
+        let orientation = self.previous_orientation.slerp(&self.orientation, alpha);
+        Matrix4::from_orientation_and_position(orientation, position)
+    }
+
+    /// Returns the inverse of the inertia tensor in world space,
+    /// as of the last `calculate_derived_data`.
+    pub fn get_inverse_inertia_tensor_world(&self) -> Matrix3 {
+        self.inverse_inertia_tensor_world
+    }
+
+    /// Converts the given point from body space to world space.
+    /// Like every conversion, it uses the transform of the last `calculate_derived_data`.
+    pub fn get_point_in_world_space(&self, point: Vector3) -> Vector3 {
+        self.transform_matrix.transform(point)
+    }
+
+    /// Converts the given point from world space to body space.
+    pub fn get_point_in_local_space(&self, point: Vector3) -> Vector3 {
+        self.transform_matrix.transform_inverse(point)
+    }
+
+    /// Converts the given direction from body space to world space, ignoring the translation.
+    pub fn get_direction_in_world_space(&self, direction: Vector3) -> Vector3 {
+        self.transform_matrix.transform_direction(direction)
+    }
+
+    /// Converts the given direction from world space to body space, ignoring the translation.
+    pub fn get_direction_in_local_space(&self, direction: Vector3) -> Vector3 {
+        self.transform_matrix.transform_inverse_direction(direction)
+    }
+
+    /// Returns the velocity of the given point of the body, in world space,
+    /// as `velocity + rotation × (point - center of mass)`. Sleeping bodies don't move,
+    /// so their points all have zero velocity.
+    pub fn velocity_at_point(&self, point: Vector3) -> Vector3 {
+        if !self.is_awake {
+            return Vector3::new(0., 0., 0.);
+        }
+        self.velocity + self.rotation % (point - self.get_center_of_mass_world())
+    }
+
+    /// Returns the velocity, in world space, of the given point in body space.
+    pub fn velocity_at_body_point(&self, point: Vector3) -> Vector3 {
+        self.velocity_at_point(self.get_point_in_world_space(point))
+    }
+
+    /// Calculates the data derived from the state of the body: normalizes the
+    /// orientation, rebuilds the transform matrix and rotates the inverse inertia
+    /// tensor into world space.
+    /// Must be called whenever the position or orientation is changed directly.
+    pub fn calculate_derived_data(&mut self) {
+        self.orientation.normalize();
+
+        // Calculate the transform matrix for the body.
+        self.transform_matrix =
+            Matrix4::from_orientation_and_position(self.orientation, self.position);
+
+        // Calculate the inertia tensor in world space: R I⁻¹ Rᵀ.
+        // Only dynamic bodies can be turned by torques.
+        let rotation = self.transform_matrix.get_rotation();
+        self.inverse_inertia_tensor_world = if self.is_dynamic() {
+            rotation * self.inverse_inertia_tensor * rotation.transpose()
+        } else {
+            Matrix3::diagonal(0., 0., 0.)
+        };
+    }
+
+    /// Integrates the body forward in time by the given amount, using the same
+    /// semi-implicit Euler method as particles, then updates the derived data.
+    ///
+    /// Like Cyclone, it leaves out the gyroscopic torque `ω × (I ω)` unless enabled by
+    /// `set_gyroscopic`, so a tumbling body only conserves its angular momentum if its
+    /// inertia is the same about every axis. When enabled, the torque is applied
+    /// implicitly, which keeps it from adding energy.
+    ///
+    /// Sleeping bodies are skipped. Awake ones that can sleep update their motion, and
+    /// fall asleep once it drops under the sleep epsilon. The weight of the newest motion
+    /// depends on the duration, so how fast bodies fall asleep doesn't depend on the frame rate.
+    ///
+    /// Kinematic bodies only move along their velocity and rotation, and never sleep.
+    /// Static bodies don't move at all.
+    pub fn integrate(&mut self, duration: f64) {
+        self.previous_position = self.position;
+        self.previous_orientation = self.orientation;
+
+        match self.body_type {
+            BodyType::Dynamic => {}
+            BodyType::Kinematic => {
+                assert!(duration > 0.);
+                self.advance(duration);
+                return;
+            }
+            BodyType::Static => return,
+        }
+
+        // We don't integrate sleeping things, or things with infinite mass.
+        if !self.is_awake || self.inverse_mass <= 0. {
+            return;
+        }
+
+        assert!(duration > 0.);
+
+        // Calculate linear acceleration from force inputs.
+        self.last_frame_acceleration = self.acceleration;
+        self.last_frame_acceleration
+            .add_scaled_vector(self.force_accumulator, self.inverse_mass);
+
+        // Calculate angular acceleration from torque inputs.
+        let angular_acceleration = self.inverse_inertia_tensor_world * self.torque_accumulator;
+
+        // Adjust velocities.
+        self.velocity
+            .add_scaled_vector(self.last_frame_acceleration, duration);
+        self.rotation
+            .add_scaled_vector(angular_acceleration, duration);
+        if self.gyroscopic {
+            self.apply_gyroscopic_torque(duration);
+        }
+
+        // Impose drag.
+        self.velocity *= f64::powf(self.linear_damping, duration);
+        self.rotation *= f64::powf(self.angular_damping, duration);
+
+        // Keep the angular speed under the limit, so the orientation update stays stable.
+        if let Some(max_angular_velocity) = self.max_angular_velocity {
+            let angular_speed = self.rotation.magnitude();
+            if angular_speed > max_angular_velocity {
+                self.rotation *= max_angular_velocity / angular_speed;
+            }
+        }
+
+        // Adjust positions, using the new velocities.
+        self.advance(duration);
+
+        // Clear the accumulators.
+        self.clear_accumulators();
+
+        // Update the kinetic energy store, and possibly put the body to sleep.
+        if self.can_sleep {
+            let current_motion = self.velocity * self.velocity + self.rotation * self.rotation;
+
+            // Halve the weight of the old motion every second.
+            let bias = f64::powf(0.5, duration);
+            self.motion = bias * self.motion + (1. - bias) * current_motion;
+
+            let epsilon = self.get_effective_sleep_epsilon();
+            if self.motion < epsilon {
+                self.set_awake(false);
+            } else if self.motion > 10. * epsilon {
+                self.motion = 10. * epsilon;
+            }
+        }
+    }
+
+    /// Updates the angular velocity for the gyroscopic torque over the given duration,
+    /// solving `I (ω' - ω) + h ω' × (I ω') = 0` with one step of Newton's method in body
+    /// space. Bodies that can't turn about some axis are left alone.
+    fn apply_gyroscopic_torque(&mut self, duration: f64) {
+        let inertia_tensor = match self.get_inertia_tensor() {
+            Some(inertia_tensor) => inertia_tensor,
+            None => return,
+        };
+        let rotation = self.get_direction_in_local_space(self.rotation);
+        let angular_momentum = inertia_tensor * rotation;
+
+        // The residual at the current angular velocity, and its Jacobian.
+        let residual = (rotation % angular_momentum) * duration;
+        let jacobian = inertia_tensor
+            + Matrix3::skew_symmetric(rotation * duration) * inertia_tensor
+            + Matrix3::skew_symmetric(angular_momentum * -duration);
+
+        if let Some(inverse_jacobian) = jacobian.inverse() {
+            let rotation = rotation - inverse_jacobian * residual;
+            self.rotation = self.get_direction_in_world_space(rotation);
+        }
+    }
+
+    /// Moves the center of mass along the velocity and turns the body about it by the
+    /// rotation, then updates the derived data.
+    fn advance(&mut self, duration: f64) {
+        let mut center = self.get_center_of_mass_world();
+        center.add_scaled_vector(self.velocity, duration);
+        self.orientation.add_scaled_vector(self.rotation, duration);
+
+        // Put the origin back around the moved center, with the new orientation.
+        self.orientation.normalize();
+        self.position = center - Matrix3::from_orientation(self.orientation) * self.center_of_mass;
+
+        // Update the matrices with the new position and orientation.
+        self.calculate_derived_data();
+    }
+}
+
+/// The part of a `RigidBody` that is saved: every field but the derived data,
+/// each holding the same as in the body.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct BodyState {
+    position: Vector3,
+    orientation: Quaternion,
+    velocity: Vector3,
+    rotation: Vector3,
+    acceleration: Vector3,
+    linear_damping: f64,
+    angular_damping: f64,
+    max_angular_velocity: Option<f64>,
+    gyroscopic: bool,
+    gravity_scale: f64,
+    gravity_override: Option<Vector3>,
+    inverse_mass: f64,
+    inverse_inertia_tensor: Matrix3,
+    center_of_mass: Vector3,
+    force_accumulator: Vector3,
+    torque_accumulator: Vector3,
+    last_frame_acceleration: Vector3,
+    motion: f64,
+    is_awake: bool,
+    can_sleep: bool,
+    #[serde(default)]
+    sleep_epsilon: Option<f64>,
+    body_type: BodyType,
+    user_index: u64,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for RigidBody {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        BodyState {
+            position: self.position,
+            orientation: self.orientation,
+            velocity: self.velocity,
+            rotation: self.rotation,
+            acceleration: self.acceleration,
+            linear_damping: self.linear_damping,
+            angular_damping: self.angular_damping,
+            max_angular_velocity: self.max_angular_velocity,
+            gyroscopic: self.gyroscopic,
+            gravity_scale: self.gravity_scale,
+            gravity_override: self.gravity_override,
+            inverse_mass: self.inverse_mass,
+            inverse_inertia_tensor: self.inverse_inertia_tensor,
+            center_of_mass: self.center_of_mass,
+            force_accumulator: self.force_accumulator,
+            torque_accumulator: self.torque_accumulator,
+            last_frame_acceleration: self.last_frame_acceleration,
+            motion: self.motion,
+            is_awake: self.is_awake,
+            can_sleep: self.can_sleep,
+            sleep_epsilon: self.sleep_epsilon,
+            body_type: self.body_type,
+            user_index: self.user_index,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for RigidBody {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        let state = BodyState::deserialize(deserializer)?;
+        if (state.orientation.square_magnitude() - 1.).abs() > 1e-6 {
+            return Err(D::Error::custom("the orientation is not normalized"));
+        }
+        for damping in [state.linear_damping, state.angular_damping] {
+            if !(0. ..=1.).contains(&damping) {
+                return Err(D::Error::custom("a damping is not in [0, 1]"));
+            }
+        }
+
+        let mut body = RigidBody::new(state.position, state.orientation);
+        body.velocity = state.velocity;
+        body.rotation = state.rotation;
+        body.acceleration = state.acceleration;
+        body.linear_damping = state.linear_damping;
+        body.angular_damping = state.angular_damping;
+        body.max_angular_velocity = state.max_angular_velocity;
+        body.gyroscopic = state.gyroscopic;
+        body.gravity_scale = state.gravity_scale;
+        body.gravity_override = state.gravity_override;
+        body.inverse_mass = state.inverse_mass;
+        body.inverse_inertia_tensor = state.inverse_inertia_tensor;
+        body.center_of_mass = state.center_of_mass;
+        body.force_accumulator = state.force_accumulator;
+        body.torque_accumulator = state.torque_accumulator;
+        body.last_frame_acceleration = state.last_frame_acceleration;
+        body.motion = state.motion;
+        body.is_awake = state.is_awake;
+        body.can_sleep = state.can_sleep;
+        body.sleep_epsilon = state.sleep_epsilon;
+        body.body_type = state.body_type;
+        body.user_index = state.user_index;
+
+        // Derive the transform and the world space tensor from the loaded state.
+        body.calculate_derived_data();
+        Ok(body)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kellenth::noise::Random;
+
+    const ZERO: Vector3 = Vector3::new(0., 0., 0.);
+
+    /// Returns the conjugate of the quaternion, the inverse rotation of a unit one.
+    fn conjugate(q: Quaternion) -> Quaternion {
+        Quaternion::new(q.r, -q.i, -q.j, -q.k)
+    }
+
+    /// Rotates the vector by the quaternion, `q v q*`, without going through a matrix.
+    fn rotate(q: Quaternion, v: Vector3) -> Vector3 {
+        let rotated = q * Quaternion::new(0., v.x, v.y, v.z) * conjugate(q);
+        Vector3::new(rotated.i, rotated.j, rotated.k)
+    }
+
+    /// Returns a random orientation, not normalized.
+    fn random_orientation(rng: &mut Random) -> Quaternion {
+        let q =
+            Quaternion::from_axis_angle(rng.unit_vector(), rng.range(0., std::f64::consts::TAU));
+        let scale = rng.range(0.5, 3.);
+        Quaternion::new(q.r * scale, q.i * scale, q.j * scale, q.k * scale)
+    }
+
+    #[test]
+    fn world_inertia_is_the_body_inertia_rotated() {
+        let inertia = Matrix3::new(2., 0.3, 0., 0.3, 3., 0.1, 0., 0.1, 4.);
+        let mut rng = Random::new(11);
+        for _ in 0..20 {
+            let mut body = RigidBody::new(rng.in_sphere(10.), random_orientation(&mut rng));
+            body.set_mass(2.);
+            body.set_inertia_tensor(&inertia);
+            body.calculate_derived_data();
+            assert!((body.orientation.square_magnitude() - 1.).abs() < 1e-12);
+
+            let world = body.get_inverse_inertia_tensor_world();
+            let inverse = body.get_inverse_inertia_tensor();
+            let q = body.orientation;
+            for _ in 0..3 {
+                // R I⁻¹ Rᵀ w: back into body space, through the body tensor, and out again.
+                let w = rng.in_sphere(5.);
+                let expected = rotate(q, inverse * rotate(conjugate(q), w));
+                assert!((world * w - expected).chebyshev_distance(&ZERO) < 1e-12);
+            }
+
+            // The transform puts body points where the orientation and position say.
+            let point = Vector3::new(1., -2., 0.5);
+            let placed = body.get_point_in_world_space(point);
+            assert!(
+                (placed - (rotate(q, point) + body.position)).chebyshev_distance(&ZERO) < 1e-12
+            );
+        }
+    }
+
+    #[test]
+    fn immovable_bodies_have_no_world_inertia() {
+        let mut body = RigidBody::new(
+            ZERO,
+            Quaternion::from_axis_angle(Vector3::new(0., 1., 0.), 1.),
+        );
+        body.set_inverse_inertia_tensor(Matrix3::diagonal(1., 1., 1.));
+        body.set_body_type(BodyType::Static);
+        body.calculate_derived_data();
+        assert_eq!(
+            body.get_inverse_inertia_tensor_world(),
+            Matrix3::diagonal(0., 0., 0.)
+        );
+    }
+
+    /// Returns an undamped body of mass 1 with the given inertia tensor.
+    fn undamped_body(inertia: Matrix3) -> RigidBody {
+        let mut body = RigidBody::new(ZERO, Quaternion::identity());
+        body.set_mass(1.);
+        body.set_inertia_tensor(&inertia);
+        body.linear_damping = 1.;
+        body.angular_damping = 1.;
+        body.calculate_derived_data();
+        body
+    }
+
+    #[test]
+    fn constant_torque_spins_up_at_torque_over_inertia() {
+        let mut body = undamped_body(Matrix3::diagonal(1., 2., 4.));
+        for _ in 0..100 {
+            body.add_torque(Vector3::new(0., 0., 8.));
+            body.integrate(0.01);
+        }
+        assert!((body.rotation - Vector3::new(0., 0., 2.)).chebyshev_distance(&ZERO) < 1e-12);
+        // The angle turned is ½αt², give or take a step of semi-implicit Euler.
+        let expected = Quaternion::from_axis_angle(Vector3::new(0., 0., 1.), 1.01);
+        assert!((body.orientation.r - expected.r).abs() < 1e-3);
+        assert!((body.orientation.k - expected.k).abs() < 1e-3);
+        assert_eq!(body.velocity.magnitude(), 0.);
+        assert_eq!(body.get_accumulated_torque().magnitude(), 0.);
+    }
+
+    #[test]
+    fn free_tumbling_keeps_its_angular_momentum() {
+        let mut body = undamped_body(Matrix3::diagonal(3., 3., 3.));
+        body.rotation = Vector3::new(1., 2., -0.5);
+        body.velocity = Vector3::new(0.5, 0., 0.);
+        let momentum = body.angular_momentum_world();
+        for _ in 0..1000 {
+            body.integrate(0.01);
+            assert!((body.angular_momentum_world() - momentum).chebyshev_distance(&ZERO) < 1e-9);
+        }
+        assert!((body.position.x - 5.).abs() < 1e-9);
+        assert!((body.orientation.square_magnitude() - 1.).abs() < 1e-12);
+    }
+
+    #[test]
+    fn sleeping_and_immovable_bodies_are_not_integrated() {
+        let mut body = undamped_body(Matrix3::diagonal(1., 1., 1.));
+        // Adding a force wakes the body, so put it back to sleep afterwards.
+        body.add_force(Vector3::new(1., 0., 0.));
+        body.set_awake(false);
+        body.integrate(0.1);
+        assert_eq!(body.position.magnitude(), 0.);
+
+        let mut fixed = RigidBody::new(ZERO, Quaternion::identity());
+        fixed.velocity = Vector3::new(1., 0., 0.);
+        fixed.integrate(0.1);
+        assert_eq!(fixed.position.magnitude(), 0.);
+    }
+
+    #[test]
+    fn forces_at_points_add_the_matching_torques() {
+        let mut body = undamped_body(Matrix3::diagonal(1., 2., 3.));
+        body.position = Vector3::new(1., 2., 3.);
+        body.orientation =
+            Quaternion::from_axis_angle(Vector3::new(0., 0., 1.), std::f64::consts::FRAC_PI_2);
+        body.calculate_derived_data();
+
+        // A force through the center of mass doesn't turn the body.
+        body.set_awake(false);
+        body.add_force_at_point(Vector3::new(0., -5., 2.), body.position);
+        assert!(body.is_awake());
+        assert_eq!(body.get_accumulated_torque().magnitude(), 0.);
+        body.integrate(0.1);
+        assert_eq!(body.rotation.magnitude(), 0.);
+        assert!(body.velocity.magnitude() > 0.);
+
+        // Equal and opposite forces either side of the center make a pure couple.
+        let mut body = undamped_body(Matrix3::diagonal(1., 2., 3.));
+        let force = Vector3::new(0., 1., 0.);
+        body.add_force_at_point(force, Vector3::new(1., 0., 0.));
+        body.add_force_at_point(force * -1., Vector3::new(-1., 0., 0.));
+        assert_eq!(body.get_accumulated_force().magnitude(), 0.);
+        assert!(
+            (body.get_accumulated_torque() - Vector3::new(0., 0., 2.)).chebyshev_distance(&ZERO)
+                < 1e-12
+        );
+        body.integrate(0.1);
+        assert_eq!(body.velocity.magnitude(), 0.);
+        assert!((body.rotation.z - 2. / 3. * 0.1).abs() < 1e-12);
+
+        // Body points are turned into world space first: after a quarter turn about z,
+        // the body x axis points along the world y axis.
+        let mut turned = undamped_body(Matrix3::diagonal(1., 1., 1.));
+        turned.orientation =
+            Quaternion::from_axis_angle(Vector3::new(0., 0., 1.), std::f64::consts::FRAC_PI_2);
+        turned.calculate_derived_data();
+        turned.add_force_at_body_point(Vector3::new(1., 0., 0.), Vector3::new(1., 0., 0.));
+        assert!(
+            (turned.get_accumulated_torque() - Vector3::new(0., 0., -1.)).chebyshev_distance(&ZERO)
+                < 1e-12
+        );
+        turned.clear_accumulators();
+        assert_eq!(turned.get_accumulated_force().magnitude(), 0.);
+        assert_eq!(turned.get_accumulated_torque().magnitude(), 0.);
+    }
+
+    /// Returns the largest difference between the entries of two matrices.
+    fn matrix_distance(a: &Matrix3, b: &Matrix3) -> f64 {
+        a.data
+            .iter()
+            .zip(&b.data)
+            .map(|(x, y)| (x - y).abs())
+            .fold(0., f64::max)
+    }
+
+    #[test]
+    fn inertia_and_mass_round_trip_through_their_accessors() {
+        let inertia = Matrix3::new(2., 0.3, 0., 0.3, 3., 0.1, 0., 0.1, 4.);
+        let orientation = Quaternion::from_axis_angle(Vector3::new(1., 1., 0.), 0.7);
+        let mut body = RigidBody::new(ZERO, orientation);
+        assert!(!body.has_finite_mass());
+        assert_eq!(body.get_mass(), f64::MAX);
+        body.set_mass(4.);
+        assert!(body.has_finite_mass());
+        assert_eq!(body.get_mass(), 4.);
+        assert_eq!(body.get_inverse_mass(), 0.25);
+
+        body.set_inertia_tensor(&inertia);
+        assert!(matrix_distance(&body.get_inertia_tensor().unwrap(), &inertia) < 1e-12);
+        let inverse = body.get_inverse_inertia_tensor();
+        assert!(matrix_distance(&(inverse * inertia), &Matrix3::diagonal(1., 1., 1.)) < 1e-12);
+        body.set_inverse_inertia_tensor(inverse);
+        assert_eq!(body.get_inverse_inertia_tensor(), inverse);
+
+        body.calculate_derived_data();
+        let rotation = Matrix3::from_orientation(orientation);
+        let world = rotation * inertia * rotation.transpose();
+        assert!(matrix_distance(&body.get_inertia_tensor_world().unwrap(), &world) < 1e-12);
+        let inverse_world = rotation * inverse * rotation.transpose();
+        assert!(matrix_distance(&body.get_inverse_inertia_tensor_world(), &inverse_world) < 1e-12);
+
+        // A zero in the inverse tensor locks rotation about that axis.
+        body.set_inverse_inertia_tensor(Matrix3::diagonal(1., 0., 1.));
+        body.calculate_derived_data();
+        assert!(body.get_inertia_tensor().is_none());
+        assert!(body.get_inertia_tensor_world().is_none());
+    }
+
+    #[test]
+    #[should_panic(expected = "invertible")]
+    fn singular_inertia_tensors_are_rejected() {
+        let mut body = RigidBody::new(ZERO, Quaternion::identity());
+        body.set_inertia_tensor(&Matrix3::diagonal(1., 0., 1.));
+    }
+
+    #[test]
+    fn space_conversions_round_trip() {
+        let mut rng = Random::new(12);
+        for _ in 0..50 {
+            let body = RigidBody::new(rng.in_sphere(100.), random_orientation(&mut rng));
+            let point = rng.in_sphere(10.);
+            let world = body.get_point_in_world_space(point);
+            assert!(
+                (body.get_point_in_local_space(world) - point).chebyshev_distance(&ZERO) < 1e-12
+            );
+            let world = rng.in_sphere(10.);
+            let local = body.get_point_in_local_space(world);
+            assert!(
+                (body.get_point_in_world_space(local) - world).chebyshev_distance(&ZERO) < 1e-12
+            );
+
+            // Directions only turn with the body, whatever its position.
+            let direction = rng.unit_vector();
+            let turned = body.get_direction_in_world_space(direction);
+            assert!(
+                (turned - rotate(body.orientation, direction)).chebyshev_distance(&ZERO) < 1e-12
+            );
+            let back = body.get_direction_in_local_space(turned);
+            assert!((back - direction).chebyshev_distance(&ZERO) < 1e-12);
+            let origin = body.get_point_in_world_space(ZERO);
+            assert!(
+                (body.get_point_in_world_space(direction) - origin - turned)
+                    .chebyshev_distance(&ZERO)
+                    < 1e-12
+            );
+        }
+    }
+
+    /// Returns the time a body slowed by heavy damping takes to fall asleep, stepped
+    /// by the given duration, or `None` if it is still awake after ten seconds.
+    fn time_to_sleep(duration: f64, can_sleep: bool) -> Option<f64> {
+        let mut body = undamped_body(Matrix3::diagonal(1., 1., 1.));
+        body.linear_damping = 0.2;
+        body.angular_damping = 0.2;
+        body.velocity = Vector3::new(1., 0., 0.);
+        body.rotation = Vector3::new(0., 1., 0.);
+        body.set_can_sleep(can_sleep);
+        let steps = (10. / duration) as usize;
+        (1..=steps).find_map(|step| {
+            body.integrate(duration);
+            (!body.is_awake()).then_some(step as f64 * duration)
+        })
+    }
+
+    #[test]
+    fn damped_bodies_fall_asleep_at_the_same_time_at_any_frame_rate() {
+        let fast = time_to_sleep(1. / 120., true).unwrap();
+        let slow = time_to_sleep(1. / 30., true).unwrap();
+        assert!(fast > 0.5 && fast < 10.);
+        assert!((fast - slow).abs() < 0.1, "{fast} {slow}");
+        assert!(time_to_sleep(1. / 60., false).is_none());
+    }
+
+    #[test]
+    fn sleeping_stops_the_body_until_a_force_wakes_it() {
+        let mut body = undamped_body(Matrix3::diagonal(1., 1., 1.));
+        body.velocity = Vector3::new(0.01, 0., 0.);
+        body.rotation = Vector3::new(0., 0.01, 0.);
+        while body.is_awake() {
+            body.integrate(0.1);
+        }
+        assert_eq!(body.velocity.magnitude(), 0.);
+        assert_eq!(body.rotation.magnitude(), 0.);
+        let position = body.position;
+        body.integrate(0.1);
+        assert_eq!(body.position.manhattan_distance(&position), 0.);
+
+        body.add_force_at_point(
+            Vector3::new(0., 10., 0.),
+            body.position + Vector3::new(1., 0., 0.),
+        );
+        assert!(body.is_awake());
+        body.integrate(0.1);
+        assert!(body.is_awake());
+        assert!(body.velocity.y > 0. && body.rotation.z > 0.);
+    }
+
+    #[test]
+    fn impulse_at_a_cube_edge_pushes_and_spins_it() {
+        use crate::kellenth::inertia::inertia_box;
+
+        // A cube of side 2 and mass 3 has the inertia m a² / 6 = 2 about every axis.
+        let mut cube = RigidBody::new(Vector3::new(5., 0., 0.), Quaternion::identity());
+        cube.set_mass(3.);
+        cube.set_inertia_tensor(&inertia_box(3., Vector3::new(1., 1., 1.)));
+        cube.calculate_derived_data();
+        cube.set_awake(false);
+
+        cube.apply_impulse_at_point(Vector3::new(0., 6., 0.), Vector3::new(6., 0., 1.));
+        assert!(cube.is_awake());
+        // Δv = J / m, and Δω = I⁻¹ (r × J) with r = (1, 0, 1).
+        assert!((cube.velocity - Vector3::new(0., 2., 0.)).chebyshev_distance(&ZERO) < 1e-12);
+        assert!((cube.rotation - Vector3::new(-3., 0., 3.)).chebyshev_distance(&ZERO) < 1e-12);
+
+        cube.apply_impulse(Vector3::new(0., -6., 0.));
+        assert!(cube.velocity.chebyshev_distance(&ZERO) < 1e-12);
+        cube.apply_torque_impulse(Vector3::new(6., 0., -6.));
+        assert!(cube.rotation.chebyshev_distance(&ZERO) < 1e-12);
+
+        // Immovable bodies ignore impulses.
+        let mut wall = RigidBody::new(ZERO, Quaternion::identity());
+        wall.apply_impulse_at_point(Vector3::new(0., 6., 0.), Vector3::new(1., 0., 0.));
+        wall.apply_torque_impulse(Vector3::new(1., 0., 0.));
+        assert_eq!(wall.velocity.magnitude() + wall.rotation.magnitude(), 0.);
+    }
+
+    #[test]
+    fn kinematic_bodies_follow_their_velocity_and_static_ones_stay_put() {
+        let mut platform = RigidBody::new(ZERO, Quaternion::identity());
+        platform.set_mass(10.);
+        platform.set_inertia_tensor(&Matrix3::diagonal(1., 1., 1.));
+        platform.set_body_type(BodyType::Kinematic);
+        platform.velocity = Vector3::new(2., 0., 0.);
+        platform.rotation = Vector3::new(0., 1., 0.);
+        assert!(!platform.has_finite_mass());
+        assert_eq!(platform.get_inverse_mass(), 0.);
+
+        // Forces and impulses are ignored, without panicking.
+        platform.add_force(Vector3::new(0., -100., 0.));
+        platform.add_force_at_point(Vector3::new(0., -100., 0.), Vector3::new(1., 0., 0.));
+        platform.apply_impulse_at_point(Vector3::new(0., 50., 0.), Vector3::new(1., 0., 0.));
+        assert_eq!(platform.get_accumulated_force().magnitude(), 0.);
+        for _ in 0..100 {
+            platform.integrate(0.01);
+        }
+        assert!((platform.position - Vector3::new(2., 0., 0.)).chebyshev_distance(&ZERO) < 1e-12);
+        assert!((platform.velocity - Vector3::new(2., 0., 0.)).chebyshev_distance(&ZERO) < 1e-12);
+        assert!(platform.is_awake());
+        // The top of the platform moves with it, which is what contacts drag riders along with.
+        let top = platform.position + Vector3::new(0., 1., 0.);
+        assert!(
+            (platform.velocity_at_point(top) - Vector3::new(2., 0., 0.)).chebyshev_distance(&ZERO)
+                < 1e-12
+        );
+
+        let mut wall = RigidBody::new(ZERO, Quaternion::identity());
+        wall.set_mass(10.);
+        wall.set_body_type(BodyType::Static);
+        wall.velocity = Vector3::new(2., 0., 0.);
+        wall.apply_impulse(Vector3::new(1., 0., 0.));
+        wall.integrate(0.1);
+        assert_eq!(wall.position.magnitude(), 0.);
+    }
+
+    #[test]
+    fn linear_and_angular_damping_act_independently() {
+        let mut body = undamped_body(Matrix3::diagonal(1., 1., 1.));
+        body.set_can_sleep(false);
+        body.angular_damping = 0.1;
+        body.velocity = Vector3::new(1., 0., 0.);
+        body.rotation = Vector3::new(0., 0., 1.);
+        for _ in 0..100 {
+            body.integrate(0.01);
+        }
+        // After a second each velocity has been scaled by its own damping.
+        assert!((body.velocity.x - 1.).abs() < 1e-12);
+        assert!((body.rotation.z - 0.1).abs() < 1e-12);
+    }
+
+    #[test]
+    fn angular_speed_never_exceeds_the_cap() {
+        let mut body = undamped_body(Matrix3::diagonal(0.1, 0.1, 0.1));
+        assert_eq!(
+            body.get_max_angular_velocity(),
+            Some(RigidBody::DEFAULT_MAX_ANGULAR_VELOCITY)
+        );
+        body.set_max_angular_velocity(Some(10.));
+        let mut rng = Random::new(9);
+        for _ in 0..1000 {
+            body.add_torque(rng.unit_vector() * 1000.);
+            body.integrate(0.01);
+            assert!(body.rotation.magnitude() <= 10. + 1e-9);
+            assert!(body.orientation.r.is_finite());
+        }
+
+        // Without a cap the same torque spins it far faster.
+        body.set_max_angular_velocity(None);
+        body.add_torque(body.rotation.get_normalized() * 1000.);
+        body.integrate(0.01);
+        assert!(body.rotation.magnitude() > 100.);
+    }
+
+    #[test]
+    fn offset_center_of_mass_is_the_pivot_of_the_dynamics() {
+        let mut body = undamped_body(Matrix3::diagonal(1., 1., 1.));
+        body.set_can_sleep(false);
+        body.set_center_of_mass(Vector3::new(1., 0., 0.));
+        assert_eq!(
+            body.get_center_of_mass_world()
+                .manhattan_distance(&Vector3::new(1., 0., 0.)),
+            0.
+        );
+
+        // A push at the origin acts a unit away from the center of mass.
+        body.add_force_at_point(Vector3::new(0., 1., 0.), body.position);
+        assert!(
+            (body.get_accumulated_torque() - Vector3::new(0., 0., -1.)).chebyshev_distance(&ZERO)
+                < 1e-12
+        );
+        body.clear_accumulators();
+
+        // Spinning freely, the origin circles the center of mass, which stays put.
+        body.rotation = Vector3::new(0., 0., std::f64::consts::PI);
+        for step in 1..=100 {
+            body.integrate(0.01);
+            let center = body.get_center_of_mass_world();
+            assert!(
+                (center - Vector3::new(1., 0., 0.)).chebyshev_distance(&ZERO) < 1e-9,
+                "step {step}"
+            );
+            assert!(((body.position - center).magnitude() - 1.).abs() < 1e-9);
+            assert_eq!(
+                body.get_point_in_world_space(ZERO)
+                    .manhattan_distance(&body.position),
+                0.
+            );
+        }
+        // Half a turn later the origin is on the other side.
+        assert!((body.position - Vector3::new(2., 0., 0.)).chebyshev_distance(&ZERO) < 1e-3);
+    }
+
+    #[test]
+    fn point_velocities_add_the_spin_around_the_center() {
+        let mut body = undamped_body(Matrix3::diagonal(1., 1., 1.));
+        body.position = Vector3::new(1., 2., 3.);
+        body.calculate_derived_data();
+        body.velocity = Vector3::new(0.5, 0., -1.);
+        assert_eq!(
+            body.velocity_at_point(body.position)
+                .manhattan_distance(&body.velocity),
+            0.
+        );
+
+        body.velocity = ZERO;
+        body.rotation = Vector3::new(0., 2., 0.);
+        for distance in [0.5, 1., 3.] {
+            let radial = Vector3::new(distance, 0., 0.);
+            // Moving along the axis doesn't change the speed.
+            let point = body.position + radial + Vector3::new(0., 7., 0.);
+            let velocity = body.velocity_at_point(point);
+            assert!((velocity.magnitude() - 2. * distance).abs() < 1e-12);
+            assert!((velocity * radial).abs() < 1e-12);
+            assert!(
+                (body.velocity_at_body_point(radial) - velocity).chebyshev_distance(&ZERO) < 1e-12
+            );
+        }
+
+        body.set_awake(false);
+        assert_eq!(
+            body.velocity_at_point(Vector3::new(5., 0., 0.)).magnitude(),
+            0.
+        );
+    }
+
+    /// Spins a box about the given body axis, nudged slightly off it, for ten seconds.
+    /// Returns the lowest value reached by the component of that axis, in world space,
+    /// along where it started.
+    fn lowest_axis_alignment(axis: Vector3, gyroscopic: bool) -> f64 {
+        let mut body = undamped_body(Matrix3::diagonal(1., 2., 3.));
+        body.set_can_sleep(false);
+        body.set_gyroscopic(gyroscopic);
+        body.rotation = axis * 5. + Vector3::new(0.01, 0.01, 0.01);
+        let mut lowest: f64 = 1.;
+        for _ in 0..10_000 {
+            body.integrate(0.001);
+            lowest = lowest.min(body.get_direction_in_world_space(axis) * axis);
+        }
+        lowest
+    }
+
+    #[test]
+    fn gyroscopic_torque_flips_a_box_spun_about_its_intermediate_axis() {
+        let intermediate = Vector3::new(0., 1., 0.);
+        assert!(lowest_axis_alignment(intermediate, true) < -0.9);
+        assert!(lowest_axis_alignment(intermediate, false) > 0.99);
+
+        let major = Vector3::new(0., 0., 1.);
+        assert!(lowest_axis_alignment(major, true) > 0.99);
+        assert!(lowest_axis_alignment(major, false) > 0.99);
+    }
+
+    #[test]
+    fn gyroscopic_torque_does_not_add_energy() {
+        let mut body = undamped_body(Matrix3::diagonal(1., 2., 3.));
+        body.set_can_sleep(false);
+        body.set_gyroscopic(true);
+        body.rotation = Vector3::new(3., 1., -2.);
+        let mut energy = body.kinetic_energy();
+        for _ in 0..1000 {
+            body.integrate(0.01);
+            assert!(body.kinetic_energy() <= energy + 1e-12);
+            energy = body.kinetic_energy();
+        }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn loaded_bodies_integrate_like_the_original() {
+        let mut rng = Random::new(13);
+        let mut body = undamped_body(Matrix3::new(2., 0.3, 0., 0.3, 3., 0.1, 0., 0.1, 4.));
+        body.orientation = random_orientation(&mut rng).get_normalized();
+        body.linear_damping = 0.9;
+        body.angular_damping = 0.8;
+        body.velocity = Vector3::new(1., 2., -1.);
+        body.rotation = Vector3::new(0.5, -2., 1.);
+        body.set_center_of_mass(Vector3::new(0.1, 0., 0.2));
+        body.set_gyroscopic(true);
+        body.calculate_derived_data();
+
+        let json = serde_json::to_string(&body).unwrap();
+        let mut loaded: RigidBody = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded.get_transform_matrix(), body.get_transform_matrix());
+        assert_eq!(
+            loaded.get_inverse_inertia_tensor_world(),
+            body.get_inverse_inertia_tensor_world()
+        );
+        for _ in 0..20 {
+            for body in [&mut body, &mut loaded] {
+                body.add_force_at_body_point(Vector3::new(0., 1., 0.), Vector3::new(1., 0., 0.));
+                body.integrate(0.01);
+            }
+        }
+        assert_eq!(
+            serde_json::to_string(&loaded).unwrap(),
+            serde_json::to_string(&body).unwrap()
+        );
+        assert_eq!(loaded.get_transform_matrix(), body.get_transform_matrix());
+
+        // Invalid states are rejected on load.
+        let mut state: serde_json::Value = serde_json::from_str(&json).unwrap();
+        state["orientation"]["r"] = serde_json::json!(2.);
+        assert!(serde_json::from_value::<RigidBody>(state).is_err());
+        let mut state: serde_json::Value = serde_json::from_str(&json).unwrap();
+        state["angular_damping"] = serde_json::json!(1.5);
+        assert!(serde_json::from_value::<RigidBody>(state).is_err());
+    }
+
+    #[test]
+    fn tumbling_keeps_its_angular_momentum_magnitude() {
+        let mut body = undamped_body(Matrix3::diagonal(1., 2., 3.));
+        body.set_can_sleep(false);
+        body.set_gyroscopic(true);
+        body.rotation = Vector3::new(0.3, 2., -0.2);
+        let momentum = body.angular_momentum_world().magnitude();
+        for _ in 0..2000 {
+            body.integrate(0.001);
+            assert!((body.angular_momentum_world().magnitude() - momentum).abs() < 1e-2 * momentum);
+        }
+    }
+
+    #[test]
+    fn damped_bodies_only_lose_energy() {
+        let mut body = undamped_body(Matrix3::new(2., 0.3, 0., 0.3, 3., 0.1, 0., 0.1, 4.));
+        body.set_can_sleep(false);
+        body.linear_damping = 0.9;
+        body.angular_damping = 0.7;
+        body.velocity = Vector3::new(2., -1., 0.5);
+        body.rotation = Vector3::new(1., 0.5, -3.);
+        let initial = body.kinetic_energy();
+        let mut energy = initial;
+        for _ in 0..500 {
+            body.integrate(0.01);
+            assert!(body.kinetic_energy() <= energy);
+            energy = body.kinetic_energy();
+        }
+        assert!(energy < 0.5 * initial);
+    }
+
+    #[test]
+    fn immovable_bodies_have_no_energy_or_momentum() {
+        let mut body = RigidBody::new(ZERO, Quaternion::identity());
+        body.velocity = Vector3::new(1., 2., 3.);
+        body.rotation = Vector3::new(0., 1., 0.);
+        assert_eq!(body.kinetic_energy(), 0.);
+        assert_eq!(body.linear_momentum().magnitude(), 0.);
+        assert_eq!(body.angular_momentum_world().magnitude(), 0.);
+
+        let mut body = undamped_body(Matrix3::diagonal(2., 2., 2.));
+        body.velocity = Vector3::new(3., 0., 4.);
+        body.rotation = Vector3::new(0., 1., 0.);
+        assert!((body.linear_momentum() - body.velocity).chebyshev_distance(&ZERO) < 1e-12);
+        assert!((body.kinetic_energy() - (12.5 + 1.)).abs() < 1e-12);
+    }
+
+    #[test]
+    fn interpolated_transforms_sit_between_the_steps() {
+        let mut body = undamped_body(Matrix3::diagonal(1., 1., 1.));
+        body.set_can_sleep(false);
+        body.velocity = Vector3::new(2., 0., -4.);
+        body.rotation = Vector3::new(0., 0., 1.);
+        for _ in 0..5 {
+            body.integrate(0.1);
+        }
+        let before = body.get_transform_matrix();
+        body.integrate(0.1);
+        let after = body.get_transform_matrix();
+
+        // The ends of the step are the transforms before and after it.
+        assert_eq!(body.interpolated_transform(1.), after);
+        let point = Vector3::new(1., 2., 3.);
+        let start = body.interpolated_transform(0.).transform(point);
+        assert!((start - before.transform(point)).chebyshev_distance(&ZERO) < 1e-12);
+
+        // Halfway, the body is at the midpoint, turned by half the angle.
+        let halfway = body.interpolated_transform(0.5);
+        let center = halfway.transform(ZERO);
+        assert!((center - Vector3::new(1.1, 0., -2.2)).chebyshev_distance(&ZERO) < 1e-12);
+        let x = Vector3::new(1., 0., 0.);
+        let expected = before
+            .transform_direction(x)
+            .slerp(&after.transform_direction(x), 0.5);
+        assert!((halfway.transform_direction(x) - expected).chebyshev_distance(&ZERO) < 1e-12);
+    }
+
+    #[test]
+    fn sleep_epsilon_overrides_only_change_their_own_body() {
+        let global = get_sleep_epsilon();
+        let sleep_time = |sleep_epsilon: Option<f64>| {
+            let mut body = undamped_body(Matrix3::diagonal(1., 1., 1.));
+            body.linear_damping = 0.2;
+            body.velocity = Vector3::new(1., 0., 0.);
+            body.set_sleep_epsilon_override(sleep_epsilon);
+            (1..=1000).find(|_| {
+                body.integrate(0.01);
+                !body.is_awake()
+            })
+        };
+
+        let default = sleep_time(None).unwrap();
+        assert_eq!(sleep_time(Some(global)), Some(default));
+        assert!(sleep_time(Some(global * 0.01)).unwrap() > default);
+        assert!(sleep_time(Some(global * 10.)).unwrap() < default);
+        assert_eq!(sleep_time(Some(0.)), None);
+        assert_eq!(get_sleep_epsilon(), global);
+
+        let mut body = undamped_body(Matrix3::diagonal(1., 1., 1.));
+        assert_eq!(body.get_effective_sleep_epsilon(), global);
+        body.set_sleep_epsilon_override(Some(0.5));
+        assert_eq!(body.get_sleep_epsilon_override(), Some(0.5));
+        assert_eq!(body.get_effective_sleep_epsilon(), 0.5);
+        #[cfg(feature = "serde")]
+        {
+            let json = serde_json::to_string(&body).unwrap();
+            let loaded: RigidBody = serde_json::from_str(&json).unwrap();
+            assert_eq!(loaded.get_sleep_epsilon_override(), Some(0.5));
+        }
+    }
+}
@@ -0,0 +1,250 @@
+//! Holds the rigid body builder, which sets up the mass, the inertia and the initial
+//! state of a `RigidBody` so they agree with each other.
+
+use crate::kellenth::core::*;
+use crate::kellenth::inertia::{inertia_box, inertia_solid_sphere};
+
+use super::{BodyType, RigidBody};
+
+/// The shape of a body built by a `RigidBodyBuilder`, used for its inertia tensor.
+#[derive(Debug, Clone, Copy)]
+enum BodyShape {
+    /// A solid box with the given half-sizes along each axis.
+    Box(Vector3),
+
+    /// A solid sphere with the given radius.
+    Sphere(f64),
+}
+
+/// Builds a rigid body whose inertia tensor matches its mass and shape.
+///
+/// The inertia tensor is worked out by `build` from the final mass, so the mass and
+/// the shape can be set in any order. The derived data is calculated before the body
+/// is returned, so it is ready to be integrated.
+///
+/// # Example
+/// A 2 kg crate, half a meter on each side, dropped from 10 meters:
+/// ```
+/// use kellenth_rs::kellenth::core::Vector3;
+/// use kellenth_rs::kellenth::rigid_body::RigidBodyBuilder;
+///
+/// let mut body = RigidBodyBuilder::new()
+///     .with_position(Vector3::new(0., 10., 0.))
+///     .with_mass(2.)
+///     .with_box_shape(Vector3::new(0.25, 0.25, 0.25))
+///     .build();
+/// body.acceleration = Vector3::new(0., -9.81, 0.);
+///
+/// for _ in 0..60 {
+///     body.integrate(1. / 60.);
+/// }
+/// assert!(body.position.y < 10.);
+/// assert_eq!(body.get_mass(), 2.);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct RigidBodyBuilder {
+    /// Holds the position of the body.
+    position: Vector3,
+
+    /// Holds the orientation of the body.
+    orientation: Quaternion,
+
+    /// Holds the linear velocity of the body.
+    velocity: Vector3,
+
+    /// Holds the angular velocity of the body.
+    angular_velocity: Vector3,
+
+    /// Holds the mass of the body.
+    mass: f64,
+
+    /// Holds the shape the inertia tensor is worked out from.
+    shape: BodyShape,
+
+    /// Holds the amount of damping applied to linear motion.
+    linear_damping: f64,
+
+    /// Holds the amount of damping applied to angular motion.
+    angular_damping: f64,
+
+    /// Holds how the body is moved by the simulation.
+    body_type: BodyType,
+}
+
+impl Default for RigidBodyBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RigidBodyBuilder {
+    /// Constructor
+    /// The body starts at rest at the origin, as a dynamic sphere of radius 1 weighing 1,
+    /// with a damping of 0.99 for both linear and angular motion, until configured otherwise.
+    pub fn new() -> Self {
+        let zero = Vector3::new(0., 0., 0.);
+        Self {
+            position: zero,
+            orientation: Quaternion::identity(),
+            velocity: zero,
+            angular_velocity: zero,
+            mass: 1.,
+            shape: BodyShape::Sphere(1.),
+            linear_damping: 0.99,
+            angular_damping: 0.99,
+            body_type: BodyType::Dynamic,
+        }
+    }
+
+    /// Sets the position of the body.
+    pub fn with_position(mut self, position: Vector3) -> Self {
+        self.position = position;
+        self
+    }
+
+    /// Sets the orientation of the body. It is normalized by `build`.
+    pub fn with_orientation(mut self, orientation: Quaternion) -> Self {
+        self.orientation = orientation;
+        self
+    }
+
+    /// Sets the linear velocity of the body.
+    pub fn with_velocity(mut self, velocity: Vector3) -> Self {
+        self.velocity = velocity;
+        self
+    }
+
+    /// Sets the angular velocity of the body, in world space.
+    pub fn with_angular_velocity(mut self, angular_velocity: Vector3) -> Self {
+        self.angular_velocity = angular_velocity;
+        self
+    }
+
+    /// Sets the mass of the body.
+    pub fn with_mass(mut self, mass: f64) -> Self {
+        assert!(mass > 0.);
+        self.mass = mass;
+        self
+    }
+
+    /// Makes the body a solid box with the given half-sizes along each axis.
+    pub fn with_box_shape(mut self, half_extents: Vector3) -> Self {
+        self.shape = BodyShape::Box(half_extents);
+        self
+    }
+
+    /// Makes the body a solid sphere with the given radius.
+    pub fn with_sphere_shape(mut self, radius: f64) -> Self {
+        self.shape = BodyShape::Sphere(radius);
+        self
+    }
+
+    /// Sets the amount of damping applied to linear motion.
+    pub fn with_linear_damping(mut self, linear_damping: f64) -> Self {
+        self.linear_damping = linear_damping;
+        self
+    }
+
+    /// Sets the amount of damping applied to angular motion.
+    pub fn with_angular_damping(mut self, angular_damping: f64) -> Self {
+        self.angular_damping = angular_damping;
+        self
+    }
+
+    /// Makes the body kinematic, moving with its velocity and angular velocity only.
+    pub fn kinematic(mut self) -> Self {
+        self.body_type = BodyType::Kinematic;
+        self
+    }
+
+    /// Makes the body static, so it never moves.
+    pub fn fixed(mut self) -> Self {
+        self.body_type = BodyType::Static;
+        self
+    }
+
+    /// Builds the body, with its derived data calculated.
+    pub fn build(&self) -> RigidBody {
+        let mut body = RigidBody::new(self.position, self.orientation);
+        body.velocity = self.velocity;
+        body.rotation = self.angular_velocity;
+        body.linear_damping = self.linear_damping;
+        body.angular_damping = self.angular_damping;
+
+        body.set_mass(self.mass);
+        body.set_inertia_tensor(&match self.shape {
+            BodyShape::Box(half_extents) => inertia_box(self.mass, half_extents),
+            BodyShape::Sphere(radius) => inertia_solid_sphere(self.mass, radius),
+        });
+        body.set_body_type(self.body_type);
+
+        body.calculate_derived_data();
+        body
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inertia_follows_the_final_mass_and_shape() {
+        let half_extents = Vector3::new(0.5, 1., 2.);
+        let shape_first = RigidBodyBuilder::new()
+            .with_box_shape(half_extents)
+            .with_mass(3.)
+            .build();
+        let mass_first = RigidBodyBuilder::new()
+            .with_mass(3.)
+            .with_box_shape(half_extents)
+            .build();
+        let expected = inertia_box(3., half_extents).inverse().unwrap();
+        assert_eq!(shape_first.get_inverse_inertia_tensor(), expected);
+        assert_eq!(mass_first.get_inverse_inertia_tensor(), expected);
+        assert_eq!(shape_first.get_mass(), 3.);
+
+        let sphere = RigidBodyBuilder::new().with_sphere_shape(2.).build();
+        assert_eq!(
+            sphere.get_inverse_inertia_tensor(),
+            inertia_solid_sphere(1., 2.).inverse().unwrap()
+        );
+    }
+
+    #[test]
+    fn built_bodies_are_ready_to_integrate() {
+        let orientation = Quaternion::from_axis_angle(Vector3::new(0., 1., 0.), 0.5);
+        let body = RigidBodyBuilder::new()
+            .with_position(Vector3::new(1., 2., 3.))
+            .with_orientation(Quaternion::new(
+                orientation.r * 2.,
+                orientation.i * 2.,
+                0.,
+                0.,
+            ))
+            .with_velocity(Vector3::new(0., 1., 0.))
+            .with_angular_velocity(Vector3::new(0., 0., 2.))
+            .with_linear_damping(0.5)
+            .with_angular_damping(0.25)
+            .with_box_shape(Vector3::new(1., 2., 3.))
+            .build();
+        assert!((body.orientation.square_magnitude() - 1.).abs() < 1e-12);
+        assert_eq!(
+            body.get_transform_matrix()
+                .get_translation()
+                .manhattan_distance(&body.position),
+            0.
+        );
+        let rotation = Matrix3::from_orientation(body.orientation);
+        let world = rotation * body.get_inverse_inertia_tensor() * rotation.transpose();
+        assert_eq!(body.get_inverse_inertia_tensor_world(), world);
+        assert_eq!((body.linear_damping, body.angular_damping), (0.5, 0.25));
+        assert_eq!(body.velocity.y, 1.);
+        assert_eq!(body.rotation.z, 2.);
+
+        let platform = RigidBodyBuilder::new().kinematic().build();
+        assert_eq!(platform.get_body_type(), BodyType::Kinematic);
+        let wall = RigidBodyBuilder::new().fixed().build();
+        assert_eq!(wall.get_body_type(), BodyType::Static);
+        assert!(!wall.has_finite_mass());
+    }
+}
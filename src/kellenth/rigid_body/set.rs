@@ -0,0 +1,206 @@
+//! Holds the set of rigid bodies, which stores bodies behind generational handles.
+
+use std::ops;
+
+use crate::kellenth::arena::{Arena, Index};
+
+use super::RigidBody;
+
+/// Identifies a rigid body held by a `RigidBodySet`.
+///
+/// Handles stay valid while other bodies are added and removed. Once its body is
+/// removed a handle is stale: it never resolves again, even if the storage it used
+/// is reused by a new body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BodyHandle(Index);
+
+/// A collection of rigid bodies addressed by handles.
+///
+/// The bodies are packed together in no particular order, so they can also be
+/// handed out as a slice. Indexing with a stale handle panics; use `get` to check.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RigidBodySet {
+    /// Holds the bodies of the set.
+    bodies: Arena<RigidBody>,
+}
+
+impl RigidBodySet {
+    /// Constructor
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Constructs a set with room for the given number of bodies.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            bodies: Arena::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the number of bodies the set can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.bodies.capacity()
+    }
+
+    /// Adds a body to the set and returns its handle.
+    /// The storage of removed bodies is reused, so this only allocates past the capacity.
+    pub fn insert(&mut self, body: RigidBody) -> BodyHandle {
+        BodyHandle(self.bodies.insert(body))
+    }
+
+    /// Removes the given body from the set and returns it, or `None` if the handle is stale.
+    pub fn remove(&mut self, handle: BodyHandle) -> Option<RigidBody> {
+        self.bodies.remove(handle.0)
+    }
+
+    /// Returns true if the handle refers to a body of the set.
+    pub fn contains(&self, handle: BodyHandle) -> bool {
+        self.bodies.contains(handle.0)
+    }
+
+    /// Returns the given body, or `None` if the handle is stale.
+    pub fn get(&self, handle: BodyHandle) -> Option<&RigidBody> {
+        self.bodies.get(handle.0)
+    }
+
+    /// Returns the given body for modification, or `None` if the handle is stale.
+    pub fn get_mut(&mut self, handle: BodyHandle) -> Option<&mut RigidBody> {
+        self.bodies.get_mut(handle.0)
+    }
+
+    /// Returns the handle of the first body with the given user index, if any.
+    pub fn find_by_user_index(&self, user_index: u64) -> Option<BodyHandle> {
+        self.iter()
+            .find(|(_, body)| body.get_user_index() == user_index)
+            .map(|(handle, _)| handle)
+    }
+
+    /// Returns the bodies, packed together in no particular order.
+    pub fn as_slice(&self) -> &[RigidBody] {
+        self.bodies.values()
+    }
+
+    /// Returns the bodies for modification, packed together in no particular order.
+    pub fn as_mut_slice(&mut self) -> &mut [RigidBody] {
+        self.bodies.values_mut()
+    }
+
+    /// Iterates over the handles and bodies of the set.
+    pub fn iter(&self) -> impl Iterator<Item = (BodyHandle, &RigidBody)> + '_ {
+        self.bodies
+            .iter()
+            .map(|(index, body)| (BodyHandle(index), body))
+    }
+
+    /// Iterates over the handles and bodies of the set, for modification.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (BodyHandle, &mut RigidBody)> + '_ {
+        self.bodies
+            .iter_mut()
+            .map(|(index, body)| (BodyHandle(index), body))
+    }
+
+    /// Returns the number of bodies in the set.
+    pub fn len(&self) -> usize {
+        self.bodies.len()
+    }
+
+    /// Returns true if the set holds no bodies.
+    pub fn is_empty(&self) -> bool {
+        self.bodies.is_empty()
+    }
+}
+
+impl ops::Index<BodyHandle> for RigidBodySet {
+    type Output = RigidBody;
+
+    fn index(&self, handle: BodyHandle) -> &RigidBody {
+        self.get(handle).expect("stale body handle")
+    }
+}
+
+impl ops::IndexMut<BodyHandle> for RigidBodySet {
+    fn index_mut(&mut self, handle: BodyHandle) -> &mut RigidBody {
+        self.get_mut(handle).expect("stale body handle")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kellenth::rigid_body::RigidBodyBuilder;
+
+    /// Returns a body tagged with the given user index.
+    fn tagged(user_index: u64) -> RigidBody {
+        let mut body = RigidBodyBuilder::new().build();
+        body.set_user_index(user_index);
+        body
+    }
+
+    #[test]
+    fn stale_handles_never_resolve_again() {
+        let mut set = RigidBodySet::new();
+        let mut handles: Vec<BodyHandle> = (0..4).map(|i| set.insert(tagged(i))).collect();
+        let mut stale = Vec::new();
+        // Destroy and recreate in the same slots, over and over.
+        for round in 0..10 {
+            for i in [1, 3] {
+                let old = handles[i];
+                assert_eq!(
+                    set.remove(old).unwrap().get_user_index(),
+                    old_index(round, i)
+                );
+                handles[i] = set.insert(tagged(old_index(round + 1, i)));
+                assert_ne!(handles[i], old);
+                stale.push(old);
+            }
+        }
+        assert_eq!(set.len(), 4);
+        for handle in &stale {
+            assert!(!set.contains(*handle));
+            assert!(set.get(*handle).is_none());
+            assert!(set.remove(*handle).is_none());
+        }
+        for (i, &handle) in handles.iter().enumerate() {
+            assert_eq!(set[handle].get_user_index(), old_index(10, i));
+            assert_eq!(set.find_by_user_index(old_index(10, i)), Some(handle));
+        }
+        assert_eq!(set.find_by_user_index(old_index(3, 1)), None);
+    }
+
+    /// Returns the user index of body `i` after the given number of recreations.
+    fn old_index(round: u64, i: usize) -> u64 {
+        if i == 1 || i == 3 {
+            100 * round + i as u64
+        } else {
+            i as u64
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "stale body handle")]
+    fn indexing_with_a_stale_handle_panics() {
+        let mut set = RigidBodySet::new();
+        let handle = set.insert(tagged(7));
+        set.remove(handle);
+        set.insert(tagged(8));
+        let _ = &set[handle];
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn user_indices_and_handles_survive_serialization() {
+        let mut set = RigidBodySet::new();
+        let first = set.insert(tagged(11));
+        let removed = set.insert(tagged(12));
+        let last = set.insert(tagged(u64::MAX));
+        set.remove(removed);
+
+        let json = serde_json::to_string(&set).unwrap();
+        let loaded: RigidBodySet = serde_json::from_str(&json).unwrap();
+        assert_eq!(loaded[first].get_user_index(), 11);
+        assert_eq!(loaded[last].get_user_index(), u64::MAX);
+        assert!(loaded.get(removed).is_none());
+    }
+}
@@ -0,0 +1,189 @@
+//! Holds the soft body, a grid of particles held together by springs.
+
+use crate::kellenth::core::*;
+use crate::kellenth::forces::{ParticleForceRegistry, ParticleSpring};
+use crate::kellenth::particle::Particle;
+use crate::kellenth::world::{ParticleHandle, ParticleSet};
+
+/// A rectangular sheet of particles lying in the XZ plane, where every particle is
+/// connected to its horizontal, vertical and diagonal neighbors by springs.
+/// The diagonal springs keep the sheet from shearing flat.
+pub struct SoftBody {
+    /// Holds the particles of the body, row by row.
+    particles: ParticleSet,
+
+    /// Holds the handle of every particle, row by row.
+    handles: Vec<ParticleHandle>,
+
+    /// Holds the springs connecting the particles.
+    registry: ParticleForceRegistry,
+
+    /// Holds the number of particles along the x axis.
+    columns: usize,
+
+    /// Holds the number of particles along the z axis.
+    rows: usize,
+}
+
+impl SoftBody {
+    /// Constructor
+    /// Lays out `columns` by `rows` particles `spacing` apart, starting at `origin` and
+    /// growing along the positive x and z axes. Every spring starts at its rest length.
+    /// `damping` is the linear damping given to every particle.
+    pub fn new(
+        columns: usize,
+        rows: usize,
+        origin: Vector3,
+        spacing: f64,
+        particle_mass: f64,
+        spring_constant: f64,
+        damping: f64,
+    ) -> Self {
+        let zero = Vector3::new(0., 0., 0.);
+        let mut particles = ParticleSet::new();
+        let mut handles = Vec::with_capacity(columns * rows);
+        for row in 0..rows {
+            for column in 0..columns {
+                let position =
+                    origin + Vector3::new(column as f64 * spacing, 0., row as f64 * spacing);
+                let mut particle = Particle::new(position, zero, zero, damping);
+                particle.set_mass(particle_mass);
+                handles.push(particles.insert(particle));
+            }
+        }
+
+        let mut body = Self {
+            particles,
+            handles,
+            registry: ParticleForceRegistry::new(),
+            columns,
+            rows,
+        };
+
+        let diagonal = spacing * std::f64::consts::SQRT_2;
+        for row in 0..rows {
+            for column in 0..columns {
+                if column + 1 < columns {
+                    body.connect((column, row), (column + 1, row), spring_constant, spacing);
+                }
+                if row + 1 < rows {
+                    body.connect((column, row), (column, row + 1), spring_constant, spacing);
+                }
+                if column + 1 < columns && row + 1 < rows {
+                    body.connect(
+                        (column, row),
+                        (column + 1, row + 1),
+                        spring_constant,
+                        diagonal,
+                    );
+                    body.connect(
+                        (column + 1, row),
+                        (column, row + 1),
+                        spring_constant,
+                        diagonal,
+                    );
+                }
+            }
+        }
+        body
+    }
+
+    /// Registers a spring between the two grid points, acting on both ends.
+    fn connect(
+        &mut self,
+        a: (usize, usize),
+        b: (usize, usize),
+        spring_constant: f64,
+        rest_length: f64,
+    ) {
+        let a = self.handle(a.0, a.1);
+        let b = self.handle(b.0, b.1);
+        let spring = ParticleSpring::new(spring_constant, rest_length);
+        self.registry.add_pair(a, b, Box::new(spring));
+        self.registry.add_pair(b, a, Box::new(spring));
+    }
+
+    /// Returns the index in `get_particles` of the particle at the given grid point.
+    pub fn index(&self, column: usize, row: usize) -> usize {
+        assert!(column < self.columns && row < self.rows);
+        row * self.columns + column
+    }
+
+    /// Returns the handle of the particle at the given grid point.
+    pub fn handle(&self, column: usize, row: usize) -> ParticleHandle {
+        self.handles[self.index(column, row)]
+    }
+
+    /// Returns the number of particles along the x axis.
+    pub fn get_columns(&self) -> usize {
+        self.columns
+    }
+
+    /// Returns the number of particles along the z axis.
+    pub fn get_rows(&self) -> usize {
+        self.rows
+    }
+
+    /// Returns the particles of the body, row by row.
+    pub fn get_particles(&self) -> &[Particle] {
+        self.particles.as_slice()
+    }
+
+    /// Returns the particles of the body for modification, row by row.
+    pub fn get_particles_mut(&mut self) -> &mut [Particle] {
+        self.particles.as_mut_slice()
+    }
+
+    /// Returns the particle at the given grid point.
+    pub fn get_particle(&self, column: usize, row: usize) -> &Particle {
+        &self.particles[self.handle(column, row)]
+    }
+
+    /// Makes the particle at the given grid point immovable.
+    pub fn pin(&mut self, column: usize, row: usize) {
+        let handle = self.handle(column, row);
+        self.particles[handle].set_inverse_mass(0.);
+    }
+
+    /// Sets the constant acceleration, usually gravity, of every particle.
+    pub fn set_gravity(&mut self, gravity: Vector3) {
+        for particle in self.particles.as_mut_slice() {
+            particle.acceleration = gravity;
+        }
+    }
+
+    /// Applies the spring forces and integrates every particle forward in time.
+    pub fn step(&mut self, duration: f64) {
+        self.registry.update_forces(&mut self.particles, duration);
+        for particle in self.particles.as_mut_slice() {
+            particle.integrate(duration);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pinned_soft_body_sags_without_exploding() {
+        let mut body = SoftBody::new(2, 2, Vector3::new(0., 0., 0.), 1., 1., 100., 0.05);
+        body.pin(0, 0);
+        body.pin(1, 0);
+        body.set_gravity(Vector3::new(0., -9.81, 0.));
+        for _ in 0..10_000 {
+            body.step(1e-3);
+            assert!(body
+                .get_particles()
+                .iter()
+                .all(|p| p.position.magnitude() < 3.));
+        }
+
+        for column in 0..2 {
+            assert_eq!(body.get_particle(column, 0).position.y, 0.);
+            let hanging = body.get_particle(column, 1);
+            assert!(hanging.position.y < -0.05);
+            assert!(hanging.speed() < 1e-2);
+        }
+    }
+}
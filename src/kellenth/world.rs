@@ -0,0 +1,2046 @@
+//! Holds the particle world, which owns a set of particles and steps them together.
+//!
+//! Besides the particles, the world owns the force registry and the contact
+//! generators acting on them, so `run_physics` can apply the forces, integrate,
+//! and find and resolve the contacts of a whole frame in one call.
+
+pub mod builders;
+mod emitter;
+pub mod grid;
+
+pub use self::emitter::{EmitterShape, ParticleEmitter};
+
+use std::fmt;
+use std::ops;
+use std::time::{Duration, Instant};
+
+use crate::kellenth::arena::{Arena, Index};
+use crate::kellenth::contacts::{
+    ContactBatch, ContactEvent, ParticleCollisionGenerator, ParticleContact,
+    ParticleContactGenerator, ParticleContactListener, ParticleContactResolver,
+};
+use crate::kellenth::core::*;
+use crate::kellenth::forces::ParticleForceRegistry;
+use crate::kellenth::particle::Particle;
+
+use self::grid::UniformGrid;
+
+/// Identifies a particle held by a `ParticleSet`, usually the one of a `ParticleWorld`.
+///
+/// Handles stay valid while other particles are added and removed. Once its particle
+/// is removed a handle is stale: it never resolves again, even if the storage it used
+/// is reused by a new particle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParticleHandle(Index);
+
+impl ParticleHandle {
+    /// Returns the storage slot of the particle.
+    /// Slots are reused after despawning, so this alone does not identify a particle.
+    pub fn slot(&self) -> usize {
+        self.0.slot()
+    }
+}
+
+/// The former name of `ParticleHandle`.
+pub type ParticleId = ParticleHandle;
+
+/// A pool of particles, backed by a free list: despawning a particle leaves its slot
+/// for the next one spawned, so churning within the capacity never allocates.
+pub type ParticlePool = ParticleSet;
+
+/// A collection of particles addressed by handles.
+///
+/// The particles are packed together in no particular order, so they can also be
+/// handed out as a slice. Indexing with a stale handle panics; use `get` to check.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParticleSet {
+    /// Holds the particles of the set.
+    particles: Arena<Particle>,
+}
+
+impl ParticleSet {
+    /// Constructor
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Constructs a set with room for the given number of particles.
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            particles: Arena::with_capacity(capacity),
+        }
+    }
+
+    /// Returns the number of particles the set can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.particles.capacity()
+    }
+
+    /// Makes room for at least the given number of particles on top of the ones held.
+    pub fn reserve(&mut self, additional: usize) {
+        self.particles.reserve(additional);
+    }
+
+    /// Adds a particle to the set and returns its handle.
+    /// The storage of removed particles is reused, so this only allocates past the capacity.
+    pub fn insert(&mut self, particle: Particle) -> ParticleHandle {
+        ParticleHandle(self.particles.insert(particle))
+    }
+
+    /// Removes the given particle from the set and returns it, or `None` if the handle is stale.
+    pub fn remove(&mut self, handle: ParticleHandle) -> Option<Particle> {
+        self.particles.remove(handle.0)
+    }
+
+    /// Adds a particle to the set, in the slot of the last one despawned if there is one.
+    pub fn spawn(&mut self, particle: Particle) -> ParticleId {
+        self.insert(particle)
+    }
+
+    /// Removes the given particle from the set, leaving its slot for the next spawn.
+    /// Returns false if the handle is stale.
+    pub fn despawn(&mut self, id: ParticleId) -> bool {
+        self.remove(id).is_some()
+    }
+
+    /// Returns true if the handle refers to a particle of the set.
+    pub fn contains(&self, handle: ParticleHandle) -> bool {
+        self.particles.contains(handle.0)
+    }
+
+    /// Returns the given particle, or `None` if the handle is stale.
+    pub fn get(&self, handle: ParticleHandle) -> Option<&Particle> {
+        self.particles.get(handle.0)
+    }
+
+    /// Returns the given particle for modification, or `None` if the handle is stale.
+    pub fn get_mut(&mut self, handle: ParticleHandle) -> Option<&mut Particle> {
+        self.particles.get_mut(handle.0)
+    }
+
+    /// Returns mutable references to two different particles of the set.
+    /// Panics if the handles are equal or stale.
+    pub fn pair_mut(
+        &mut self,
+        a: ParticleHandle,
+        b: ParticleHandle,
+    ) -> (&mut Particle, &mut Particle) {
+        let a = self.index_of(a).expect("stale particle handle");
+        let b = self.index_of(b).expect("stale particle handle");
+        assert_ne!(a, b);
+        let particles = self.particles.values_mut();
+        if a < b {
+            let (left, right) = particles.split_at_mut(b);
+            (&mut left[a], &mut right[0])
+        } else {
+            let (left, right) = particles.split_at_mut(a);
+            (&mut right[0], &mut left[b])
+        }
+    }
+
+    /// Returns the position of the given particle in `as_slice`, or `None` if the handle is stale.
+    /// Positions change when particles are removed, so don't hold on to them.
+    pub fn index_of(&self, handle: ParticleHandle) -> Option<usize> {
+        self.particles.dense_index(handle.0)
+    }
+
+    /// Returns the handle of the particle at the given position in `as_slice`.
+    pub fn handle_at(&self, index: usize) -> ParticleHandle {
+        ParticleHandle(self.particles.index_at(index))
+    }
+
+    /// Returns the particles, packed together in no particular order.
+    pub fn as_slice(&self) -> &[Particle] {
+        self.particles.values()
+    }
+
+    /// Returns the particles for modification, packed together in no particular order.
+    pub fn as_mut_slice(&mut self) -> &mut [Particle] {
+        self.particles.values_mut()
+    }
+
+    /// Iterates over the handles and particles of the set.
+    pub fn iter(&self) -> impl Iterator<Item = (ParticleHandle, &Particle)> + '_ {
+        self.particles
+            .iter()
+            .map(|(index, particle)| (ParticleHandle(index), particle))
+    }
+
+    /// Iterates over the handles and particles of the set, for modification.
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = (ParticleHandle, &mut Particle)> + '_ {
+        self.particles
+            .iter_mut()
+            .map(|(index, particle)| (ParticleHandle(index), particle))
+    }
+
+    /// Returns the number of particles in the set.
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    /// Returns true if the set holds no particles.
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+}
+
+impl ops::Index<ParticleHandle> for ParticleSet {
+    type Output = Particle;
+
+    fn index(&self, handle: ParticleHandle) -> &Particle {
+        self.get(handle).expect("stale particle handle")
+    }
+}
+
+impl ops::IndexMut<ParticleHandle> for ParticleSet {
+    fn index_mut(&mut self, handle: ParticleHandle) -> &mut Particle {
+        self.get_mut(handle).expect("stale particle handle")
+    }
+}
+
+/// Wall-clock time spent in each phase of `ParticleWorld::run_physics`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PhaseTimings {
+    /// Time spent applying the force generators.
+    pub forces: Duration,
+
+    /// Time spent integrating the particles.
+    pub integrate: Duration,
+
+    /// Time spent generating the contacts and keeping the deepest.
+    pub contacts: Duration,
+
+    /// Time spent resolving the contacts.
+    pub resolve: Duration,
+}
+
+/// Measures the time between laps, or nothing when not running.
+struct PhaseClock {
+    /// Holds when the last lap ended, if running.
+    last: Option<Instant>,
+}
+
+impl PhaseClock {
+    /// Constructor
+    fn new(running: bool) -> Self {
+        Self {
+            last: running.then(Instant::now),
+        }
+    }
+
+    /// Returns whether the clock measures anything.
+    fn is_running(&self) -> bool {
+        self.last.is_some()
+    }
+
+    /// Returns the time since the last lap and starts the next one.
+    fn lap(&mut self) -> Duration {
+        match &mut self.last {
+            Some(last) => {
+                let now = Instant::now();
+                let time = now - *last;
+                *last = now;
+                time
+            }
+            None => Duration::ZERO,
+        }
+    }
+}
+
+/// Diagnostics gathered while stepping a `ParticleWorld`.
+/// The counters are always collected; the timings only when enabled in the settings.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct WorldStats {
+    /// Number of particles in the world.
+    pub particles: usize,
+
+    /// Number of particles that were integrated.
+    pub integrated: usize,
+
+    /// Number of movable particles awake at the end of the step.
+    pub awake_particles: usize,
+
+    /// Number of particles skipped because they are asleep. They are still integrated
+    /// and collided, to tell whether anything moves them, but put back where they were
+    /// if nothing does.
+    pub skipped_asleep: usize,
+
+    /// Number of force registrations applied.
+    pub forces_applied: usize,
+
+    /// Number of particles skipped because they are immovable.
+    pub skipped_immovable: usize,
+
+    /// Highest speed of any integrated particle at the end of the step.
+    pub max_speed: f64,
+
+    /// Number of contacts found by the contact generators, including the dropped ones.
+    pub contacts_generated: usize,
+
+    /// Number of contacts dropped because they didn't fit in `max_contacts`.
+    pub contacts_dropped: usize,
+
+    /// Number of iterations the contact resolver used, or of sweeps the contact batch used.
+    pub iterations_used: usize,
+
+    /// Deepest penetration of the contacts found, before they were resolved.
+    pub max_penetration: f64,
+
+    /// Time spent in each phase, if collecting timings is enabled.
+    pub timings: Option<PhaseTimings>,
+}
+
+/// Controls how much contact work a `ParticleWorld` does every frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParticleWorldSettings {
+    /// Holds the maximum number of contacts resolved in a frame.
+    /// When more are generated, the deepest ones are kept.
+    pub max_contacts: usize,
+
+    /// Holds the number of resolver iterations, used when `calculate_iterations` is false.
+    pub resolver_iterations: Option<usize>,
+
+    /// Holds whether to use twice the number of contacts as the resolver iterations,
+    /// as suggested by Cyclone. Also used when `resolver_iterations` is `None`.
+    pub calculate_iterations: bool,
+
+    /// Holds whether `run_physics` measures the time spent in each phase.
+    pub collect_timings: bool,
+}
+
+impl Default for ParticleWorldSettings {
+    fn default() -> Self {
+        Self {
+            max_contacts: ParticleWorld::DEFAULT_MAX_CONTACTS,
+            resolver_iterations: None,
+            calculate_iterations: true,
+            collect_timings: true,
+        }
+    }
+}
+
+impl ParticleWorldSettings {
+    /// Returns the number of resolver iterations to use for the given number of contacts.
+    pub fn iterations_for(&self, contacts: usize) -> usize {
+        match self.resolver_iterations {
+            Some(iterations) if !self.calculate_iterations => iterations,
+            _ => contacts * 2,
+        }
+    }
+}
+
+/// Controls when the particles of a `ParticleWorld` fall asleep.
+///
+/// A particle slower than `linear_threshold`, whose velocity changed over the last step
+/// too little to speed it up past it within a second, is at rest. The change is measured
+/// after the contacts are resolved, so a particle resting on the ground is at rest whether
+/// its weight comes from the world gravity or from a force generator. Once at rest for
+/// `steps` consecutive steps it falls asleep, and stays put until a force, an impulse or
+/// a contact moves it again.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ParticleSleep {
+    /// Holds the speed below which a particle is at rest.
+    pub linear_threshold: f64,
+
+    /// Holds the number of consecutive steps at rest before a particle falls asleep.
+    pub steps: u32,
+}
+
+impl Default for ParticleSleep {
+    fn default() -> Self {
+        Self {
+            linear_threshold: 0.05,
+            steps: 30,
+        }
+    }
+}
+
+/// Keeps track of a set of particles and provides the means to update them all.
+///
+/// Force registrations and contact generators refer to particles by handle.
+/// Removing a particle removes its force registrations, and contact generators
+/// skip the particles that are gone.
+///
+/// With the `serde` feature the world can be saved and loaded. The particles keep
+/// their handles, along with the gravity, the grid, the settings, the collision
+/// restitution, the sleeping, the pause and the time scale. Force generators, contact generators,
+/// the contact listener and the contact batch hold user code or caches, so they are
+/// left out and have to be added again after loading.
+pub struct ParticleWorld {
+    /// Holds the particles simulated by the world.
+    particles: ParticleSet,
+
+    /// Holds the gravity applied to every particle with finite mass during `step`.
+    gravity: Vector3,
+
+    /// Optional broad-phase used to accelerate neighbor queries.
+    /// When `None`, queries fall back to a linear scan of all the particles.
+    grid: Option<UniformGrid>,
+
+    /// Holds whether particles were added or removed since the grid was last rebuilt.
+    /// Queries scan every particle until it is rebuilt.
+    grid_dirty: bool,
+
+    /// Holds the force generators applied to the particles during `run_physics`.
+    registry: ParticleForceRegistry,
+
+    /// Holds the contact generators run during `run_physics`.
+    contact_generators: Vec<Box<dyn ParticleContactGenerator>>,
+
+    /// Holds the resolver for the contacts.
+    resolver: ParticleContactResolver,
+
+    /// Holds the batch resolver used instead of `resolver`, if any.
+    batch: Option<ContactBatch>,
+
+    /// Holds the contact budget and the resolver iterations.
+    settings: ParticleWorldSettings,
+
+    /// Holds the contacts generated during the last `run_physics`.
+    contacts: Vec<ParticleContact>,
+
+    /// Holds the number of contacts found by each contact generator during the last `run_physics`.
+    contacts_per_generator: Vec<usize>,
+
+    /// Holds the diagnostics of the last `run_physics`.
+    last_stats: WorldStats,
+
+    /// Holds the restitution of the contacts made by `generate_contacts`.
+    collision_restitution: f64,
+
+    /// Holds the listener told about the resolved contacts, if any.
+    contact_listener: Option<Box<dyn ParticleContactListener>>,
+
+    /// Holds when particles fall asleep, or `None` to keep them all awake.
+    sleep: Option<ParticleSleep>,
+
+    /// Holds the position and velocity of every particle at the start of the current step,
+    /// in the order of the particles, used to tell the resting ones while sleeping is enabled.
+    step_start: Vec<(Vector3, Vector3)>,
+
+    /// Holds whether the world is frozen, ignoring everything but `step_once`.
+    paused: bool,
+
+    /// Holds the factor applied to every duration the world is stepped by.
+    time_scale: f64,
+}
+
+impl Default for ParticleWorld {
+    fn default() -> Self {
+        Self {
+            particles: ParticleSet::new(),
+            gravity: Vector3::new(0., 0., 0.),
+            grid: None,
+            grid_dirty: false,
+            registry: ParticleForceRegistry::new(),
+            contact_generators: Vec::new(),
+            resolver: ParticleContactResolver::new(0),
+            batch: None,
+            settings: ParticleWorldSettings::default(),
+            contacts: Vec::new(),
+            contacts_per_generator: Vec::new(),
+            last_stats: WorldStats::default(),
+            collision_restitution: 0.5,
+            contact_listener: None,
+            sleep: None,
+            step_start: Vec::new(),
+            paused: false,
+            time_scale: 1.,
+        }
+    }
+}
+
+impl fmt::Debug for ParticleWorld {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ParticleWorld")
+            .field("particles", &self.particles)
+            .field("gravity", &self.gravity)
+            .field("grid", &self.grid)
+            .field("grid_dirty", &self.grid_dirty)
+            .field("force_registrations", &self.registry.len())
+            .field("contact_generators", &self.contact_generators.len())
+            .field("resolver", &self.resolver)
+            .field("batch", &self.batch)
+            .field("settings", &self.settings)
+            .field("contacts", &self.contacts)
+            .field("contacts_per_generator", &self.contacts_per_generator)
+            .field("last_stats", &self.last_stats)
+            .field("collision_restitution", &self.collision_restitution)
+            .field("contact_listener", &self.contact_listener.is_some())
+            .field("sleep", &self.sleep)
+            .field("paused", &self.paused)
+            .field("time_scale", &self.time_scale)
+            .finish()
+    }
+}
+
+impl ParticleWorld {
+    /// The maximum number of contacts resolved in a frame, unless set otherwise.
+    pub const DEFAULT_MAX_CONTACTS: usize = 256;
+
+    /// Constructor
+    /// The world starts without gravity, force generators or contact generators.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Constructs an empty world using the given contact settings.
+    pub fn with_settings(settings: ParticleWorldSettings) -> Self {
+        Self {
+            settings,
+            ..Self::default()
+        }
+    }
+
+    /// Returns the gravity applied to every particle with finite mass.
+    pub fn get_gravity(&self) -> Vector3 {
+        self.gravity
+    }
+
+    /// Sets the gravity applied to every particle with finite mass during `step`.
+    pub fn set_gravity(&mut self, gravity: Vector3) {
+        self.gravity = gravity;
+    }
+
+    /// Adds a particle to the world and returns its handle.
+    /// The grid, if enabled, picks the particle up when it is next rebuilt, at the end
+    /// of the next step; until then queries scan every particle.
+    pub fn add_particle(&mut self, particle: Particle) -> ParticleHandle {
+        let handle = self.particles.insert(particle);
+        self.grid_dirty = true;
+        handle
+    }
+
+    /// Removes the given particle from the world, along with the force registrations
+    /// applying to it or depending on it. Returns false if the handle is stale.
+    pub fn remove_particle(&mut self, handle: ParticleHandle) -> bool {
+        if self.particles.remove(handle).is_none() {
+            return false;
+        }
+        self.registry.remove_all_for_particle(handle);
+        self.grid_dirty = true;
+        true
+    }
+
+    /// Adds a particle to the world, reusing the slot of the last one despawned.
+    /// Same as `add_particle`, named for emitters and other pooled uses.
+    pub fn spawn(&mut self, particle: Particle) -> ParticleId {
+        self.add_particle(particle)
+    }
+
+    /// Removes the given particle from the world, leaving its slot for the next spawn.
+    /// Same as `remove_particle`. Returns false if the handle is stale.
+    pub fn despawn(&mut self, id: ParticleId) -> bool {
+        self.remove_particle(id)
+    }
+
+    /// Returns true if the handle refers to a particle of the world.
+    pub fn contains(&self, handle: ParticleHandle) -> bool {
+        self.particles.contains(handle)
+    }
+
+    /// Returns the given particle, or `None` if the handle is stale.
+    pub fn get(&self, handle: ParticleHandle) -> Option<&Particle> {
+        self.particles.get(handle)
+    }
+
+    /// Returns the given particle for modification, or `None` if the handle is stale.
+    /// If a grid is enabled and the position is changed, call `rebuild` before querying.
+    pub fn get_mut(&mut self, handle: ParticleHandle) -> Option<&mut Particle> {
+        self.particles.get_mut(handle)
+    }
+
+    /// Returns the position of the given particle in `get_particles`, or `None` if the handle is stale.
+    /// Positions change when particles are removed, so don't hold on to them.
+    pub fn index_of(&self, handle: ParticleHandle) -> Option<usize> {
+        self.particles.index_of(handle)
+    }
+
+    /// Returns the handle of the particle at the given position in `get_particles`.
+    pub fn handle_at(&self, index: usize) -> ParticleHandle {
+        self.particles.handle_at(index)
+    }
+
+    /// Returns the particles held by the world, packed together in no particular order.
+    pub fn get_particles(&self) -> &[Particle] {
+        self.particles.as_slice()
+    }
+
+    /// Returns the particles held by the world for modification.
+    /// If a grid is enabled and positions are changed, call `rebuild` before querying.
+    pub fn get_particles_mut(&mut self) -> &mut [Particle] {
+        self.particles.as_mut_slice()
+    }
+
+    /// Returns the particles held by the world, addressed by handle.
+    pub fn get_particle_set(&self) -> &ParticleSet {
+        &self.particles
+    }
+
+    /// Iterates over the handles and particles of the world.
+    pub fn iter(&self) -> impl Iterator<Item = (ParticleHandle, &Particle)> + '_ {
+        self.particles.iter()
+    }
+
+    /// Calls the given function with the handle of every particle and the particle
+    /// for modification. Queries scan every particle until the grid, if enabled, is
+    /// next rebuilt.
+    pub fn for_each_mut(&mut self, mut f: impl FnMut(ParticleId, &mut Particle)) {
+        for (handle, particle) in self.particles.iter_mut() {
+            f(handle, particle);
+        }
+        self.grid_dirty = true;
+    }
+
+    /// Returns the number of particles in the world.
+    pub fn len(&self) -> usize {
+        self.particles.len()
+    }
+
+    /// Returns true if the world holds no particles.
+    pub fn is_empty(&self) -> bool {
+        self.particles.is_empty()
+    }
+
+    /// Returns the number of particles the world can hold without reallocating.
+    pub fn capacity(&self) -> usize {
+        self.particles.capacity()
+    }
+
+    /// Makes room for at least the given number of particles on top of the ones held.
+    /// Removed particles leave their storage for the next ones added, so an emitter
+    /// churning within the capacity never allocates.
+    pub fn reserve(&mut self, additional: usize) {
+        self.particles.reserve(additional);
+    }
+
+    /// Enables the uniform grid broad-phase with the given cell size.
+    /// See the `grid` module for choosing a cell size.
+    pub fn enable_grid(&mut self, cell_size: f64) {
+        let mut grid = UniformGrid::new(cell_size);
+        grid.rebuild(self.particles.as_slice());
+        self.grid = Some(grid);
+        self.grid_dirty = false;
+    }
+
+    /// Disables the broad-phase, making queries scan every particle.
+    pub fn disable_grid(&mut self) {
+        self.grid = None;
+    }
+
+    /// Returns the broad-phase grid, if enabled.
+    /// It is stale if particles were added or removed since it was last rebuilt.
+    pub fn get_grid(&self) -> Option<&UniformGrid> {
+        self.grid.as_ref()
+    }
+
+    /// Returns true if particles were added or removed since the grid was last rebuilt,
+    /// so queries scan every particle instead of using it.
+    pub fn is_grid_dirty(&self) -> bool {
+        self.grid.is_some() && self.grid_dirty
+    }
+
+    /// Rebuilds the broad-phase grid from the current particle positions.
+    /// Called automatically at the end of each `step`, so adding or removing many
+    /// particles between steps only costs a single rebuild.
+    pub fn rebuild(&mut self) {
+        if let Some(grid) = &mut self.grid {
+            grid.rebuild(self.particles.as_slice());
+        }
+        self.grid_dirty = false;
+    }
+
+    /// Returns the grid, if enabled and up to date with the particles of the world.
+    fn current_grid(&self) -> Option<&UniformGrid> {
+        if self.grid_dirty {
+            return None;
+        }
+        self.grid.as_ref()
+    }
+
+    /// Returns the force registry applied during `run_physics`.
+    pub fn get_force_registry(&self) -> &ParticleForceRegistry {
+        &self.registry
+    }
+
+    /// Returns the force registry applied during `run_physics`, to add or remove registrations.
+    pub fn get_force_registry_mut(&mut self) -> &mut ParticleForceRegistry {
+        &mut self.registry
+    }
+
+    /// Adds a contact generator, run every frame during `run_physics`.
+    pub fn add_contact_generator(&mut self, generator: Box<dyn ParticleContactGenerator>) {
+        self.contact_generators.push(generator);
+    }
+
+    /// Returns the contact generators run during `run_physics`.
+    pub fn get_contact_generators(&self) -> &[Box<dyn ParticleContactGenerator>] {
+        &self.contact_generators
+    }
+
+    /// Returns the contact settings.
+    pub fn get_settings(&self) -> ParticleWorldSettings {
+        self.settings
+    }
+
+    /// Sets the contact settings, taking effect on the next `run_physics`.
+    pub fn set_settings(&mut self, settings: ParticleWorldSettings) {
+        self.settings = settings;
+    }
+
+    /// Returns the maximum number of contacts resolved in a frame.
+    pub fn get_max_contacts(&self) -> usize {
+        self.settings.max_contacts
+    }
+
+    /// Sets the maximum number of contacts resolved in a frame.
+    pub fn set_max_contacts(&mut self, max_contacts: usize) {
+        self.settings.max_contacts = max_contacts;
+    }
+
+    /// Returns the number of resolver iterations set, or `None` if twice the
+    /// number of contacts is used.
+    pub fn get_resolver_iterations(&self) -> Option<usize> {
+        self.settings.resolver_iterations
+    }
+
+    /// Sets the number of resolver iterations used every frame.
+    /// With `None`, each frame uses twice the number of contacts it resolves.
+    /// Also turns `calculate_iterations` off for `Some`, and on for `None`.
+    pub fn set_resolver_iterations(&mut self, iterations: Option<usize>) {
+        self.settings.resolver_iterations = iterations;
+        self.settings.calculate_iterations = iterations.is_none();
+    }
+
+    /// Returns whether the resolver iterations are twice the number of contacts.
+    pub fn get_calculate_iterations(&self) -> bool {
+        self.settings.calculate_iterations
+    }
+
+    /// Sets whether the resolver iterations are twice the number of contacts,
+    /// rather than the fixed `resolver_iterations`.
+    pub fn set_calculate_iterations(&mut self, calculate_iterations: bool) {
+        self.settings.calculate_iterations = calculate_iterations;
+    }
+
+    /// Returns the contact resolver, which knows how many iterations the last frame used.
+    pub fn get_resolver(&self) -> &ParticleContactResolver {
+        &self.resolver
+    }
+
+    /// Returns the batch resolver used instead of the contact resolver, if any.
+    pub fn get_contact_batch(&self) -> Option<&ContactBatch> {
+        self.batch.as_ref()
+    }
+
+    /// Sets a batch resolver to use instead of the contact resolver, or `None` to go back
+    /// to the contact resolver. The batch uses its own iterations, not `resolver_iterations`.
+    pub fn set_contact_batch(&mut self, batch: Option<ContactBatch>) {
+        self.batch = batch;
+    }
+
+    /// Returns the diagnostics of the last `run_physics` that ran.
+    pub fn get_last_stats(&self) -> WorldStats {
+        self.last_stats
+    }
+
+    /// Returns the number of contacts found by each contact generator during the last
+    /// `run_physics`, in the order the generators were added, including the dropped ones.
+    pub fn get_contacts_per_generator(&self) -> &[usize] {
+        &self.contacts_per_generator
+    }
+
+    /// Returns the contacts resolved during the last `run_physics`.
+    pub fn get_contacts(&self) -> &[ParticleContact] {
+        &self.contacts
+    }
+
+    /// Returns the restitution of the contacts made by `generate_contacts`.
+    pub fn get_collision_restitution(&self) -> f64 {
+        self.collision_restitution
+    }
+
+    /// Sets the restitution of the contacts made by `generate_contacts`. Defaults to 0.5.
+    pub fn set_collision_restitution(&mut self, restitution: f64) {
+        self.collision_restitution = restitution;
+    }
+
+    /// Sets the listener told about the contacts resolved by `run_physics`, replacing any other.
+    pub fn set_contact_listener(&mut self, listener: Box<dyn ParticleContactListener>) {
+        self.contact_listener = Some(listener);
+    }
+
+    /// Removes the contact listener and returns it, if any.
+    pub fn clear_contact_listener(&mut self) -> Option<Box<dyn ParticleContactListener>> {
+        self.contact_listener.take()
+    }
+
+    /// Returns when particles fall asleep, or `None` if they never do.
+    pub fn get_sleep(&self) -> Option<ParticleSleep> {
+        self.sleep
+    }
+
+    /// Sets when particles fall asleep. `None` disables sleeping and wakes every particle.
+    pub fn set_sleep(&mut self, sleep: Option<ParticleSleep>) {
+        self.sleep = sleep;
+        if sleep.is_none() {
+            for particle in self.particles.as_mut_slice() {
+                particle.set_awake(true);
+            }
+        }
+    }
+
+    /// Returns the number of movable particles that are awake.
+    pub fn awake_count(&self) -> usize {
+        self.particles
+            .as_slice()
+            .iter()
+            .filter(|particle| particle.has_finite_mass() && particle.is_awake())
+            .count()
+    }
+
+    /// Returns the mass-weighted average position of the movable particles,
+    /// or `None` if there are none. Immovable particles carry no weight.
+    pub fn center_of_mass(&self) -> Option<Vector3> {
+        let mut total_mass = 0.;
+        let mut weighted = Vector3::new(0., 0., 0.);
+        for particle in self.particles.as_slice() {
+            if !particle.has_finite_mass() {
+                continue;
+            }
+            let mass = particle.get_mass();
+            weighted.add_scaled_vector(particle.position, mass);
+            total_mass += mass;
+        }
+        if total_mass == 0. {
+            return None;
+        }
+        Some(weighted * (1. / total_mass))
+    }
+
+    /// Returns whether the world is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Pauses or resumes the world. While paused, `run_physics` and the `step` functions do
+    /// nothing at all, forces added in the meantime included; only `step_once` advances it.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    /// Returns the factor applied to every duration the world is stepped by.
+    pub fn get_time_scale(&self) -> f64 {
+        self.time_scale
+    }
+
+    /// Sets the factor applied to every duration the world is stepped by, e.g. 0.5 for
+    /// slow motion. Negative values are clamped to zero, which freezes the world like pausing.
+    pub fn set_time_scale(&mut self, time_scale: f64) {
+        self.time_scale = time_scale.max(0.);
+    }
+
+    /// Returns the given duration scaled by the time scale, or `None` if the world
+    /// shouldn't move: it is paused, or the scaled duration is zero.
+    fn scaled(&self, duration: f64) -> Option<f64> {
+        let duration = duration * self.time_scale;
+        if self.paused || duration <= 0. {
+            None
+        } else {
+            Some(duration)
+        }
+    }
+
+    /// Runs exactly one `run_physics` step of the given duration, even while paused,
+    /// for stepping through a simulation frame by frame. The time scale isn't applied.
+    pub fn step_once(&mut self, fixed_duration: f64) -> WorldStats {
+        self.physics(fixed_duration)
+    }
+
+    /// Initializes the world for a simulation frame, clearing the forces of every particle.
+    /// Forces added after this call are applied by the next `run_physics`.
+    pub fn start_frame(&mut self) {
+        for particle in self.particles.as_mut_slice() {
+            particle.clear_accumulator();
+        }
+    }
+
+    /// Processes all the physics of the world for the given duration: applies the force
+    /// generators and gravity, integrates the particles, then generates the contacts
+    /// and resolves them.
+    /// When more than `max_contacts` contacts are generated, only the deepest are resolved.
+    /// The contacts go to the contact batch when one is set, and to the resolver otherwise.
+    /// The duration is scaled by the time scale; while paused nothing happens.
+    /// Returns the diagnostics of the frame, all zero if nothing happened.
+    pub fn run_physics(&mut self, duration: f64) -> WorldStats {
+        match self.scaled(duration) {
+            Some(duration) => self.physics(duration),
+            None => WorldStats::default(),
+        }
+    }
+
+    /// Runs `run_physics` for the given duration, ignoring the pause and time scale.
+    fn physics(&mut self, duration: f64) -> WorldStats {
+        let mut clock = PhaseClock::new(self.settings.collect_timings);
+
+        // First apply the force generators.
+        let forces_applied = self.registry.update_forces(&mut self.particles, duration);
+        let forces_time = clock.lap();
+
+        // Then integrate the objects.
+        let mut stats = self.integrate(duration);
+        stats.forces_applied = forces_applied;
+        let integrate_time = clock.lap();
+
+        // Generate contacts, all of them, so the deepest can be kept.
+        self.contacts.clear();
+        self.contacts_per_generator.clear();
+        for generator in &self.contact_generators {
+            let found = generator.add_contacts(&self.particles, &mut self.contacts, usize::MAX);
+            self.contacts_per_generator.push(found);
+        }
+        stats.contacts_generated = self.contacts.len();
+        stats.max_penetration = self
+            .contacts
+            .iter()
+            .map(|contact| contact.penetration)
+            .fold(0., f64::max);
+
+        // Drop the shallowest contacts past the budget.
+        let max_contacts = self.settings.max_contacts;
+        if self.contacts.len() > max_contacts {
+            if max_contacts > 0 {
+                self.contacts
+                    .select_nth_unstable_by(max_contacts - 1, |a, b| {
+                        b.penetration.total_cmp(&a.penetration)
+                    });
+            }
+            self.contacts.truncate(max_contacts);
+        }
+        stats.contacts_dropped = stats.contacts_generated - self.contacts.len();
+        let contacts_time = clock.lap();
+
+        // Remember the state of the contacts before resolution, for the listener.
+        let before: Vec<(f64, f64)> = match self.contact_listener {
+            Some(_) => self
+                .contacts
+                .iter()
+                .map(|contact| {
+                    (
+                        contact.penetration,
+                        contact.calculate_separating_velocity(&self.particles),
+                    )
+                })
+                .collect(),
+            None => Vec::new(),
+        };
+
+        // And process them.
+        if let Some(batch) = &mut self.batch {
+            batch.resolve_contacts(&mut self.contacts, &mut self.particles, duration);
+            stats.iterations_used = batch.get_iterations_used();
+        } else if !self.contacts.is_empty() {
+            let iterations = self.settings.iterations_for(self.contacts.len());
+            self.resolver.set_iterations(iterations);
+            self.resolver
+                .resolve_contacts(&mut self.contacts, &mut self.particles, duration);
+            stats.iterations_used = self.resolver.get_iterations_used();
+        }
+        if clock.is_running() {
+            stats.timings = Some(PhaseTimings {
+                forces: forces_time,
+                integrate: integrate_time,
+                contacts: contacts_time,
+                resolve: clock.lap(),
+            });
+        }
+
+        if let Some(listener) = &mut self.contact_listener {
+            for (contact, &(penetration, separating_velocity)) in self.contacts.iter().zip(&before)
+            {
+                let event = ContactEvent {
+                    handles: contact.particles,
+                    normal: contact.contact_normal,
+                    penetration,
+                    separating_velocity_before: separating_velocity,
+                    impulse_applied: contact.get_impulse(),
+                };
+                if listener.accepts(&event) {
+                    listener.on_contact(&event);
+                }
+            }
+        }
+
+        stats.awake_particles = self.update_sleep(duration);
+        self.rebuild();
+        self.last_stats = stats;
+        stats
+    }
+
+    /// Applies the world gravity and integrates every particle forward in time by the given amount.
+    /// Force and contact generators are left out; use `run_physics` to include them.
+    /// Like `run_physics`, the duration is scaled by the time scale and nothing happens while paused.
+    /// Returns the diagnostics gathered along the way.
+    pub fn step(&mut self, duration: f64) -> WorldStats {
+        match self.scaled(duration) {
+            Some(duration) => self.step_unscaled(duration),
+            None => WorldStats::default(),
+        }
+    }
+
+    /// Runs `step` for the given duration, ignoring the pause and time scale.
+    fn step_unscaled(&mut self, duration: f64) -> WorldStats {
+        let mut stats = self.integrate(duration);
+        stats.awake_particles = self.update_sleep(duration);
+        self.rebuild();
+        stats
+    }
+
+    /// Applies the world gravity and integrates every particle, without rebuilding the grid.
+    /// Sleeping particles are integrated too, so `update_sleep` can tell whether anything
+    /// moves them; those set moving since the last step wake up straight away.
+    fn integrate(&mut self, duration: f64) -> WorldStats {
+        self.step_start.clear();
+        if self.sleep.is_some() {
+            self.step_start.extend(
+                self.particles
+                    .as_slice()
+                    .iter()
+                    .map(|particle| (particle.position, particle.velocity)),
+            );
+        }
+
+        let mut stats = WorldStats::default();
+        for particle in self.particles.as_mut_slice() {
+            if !particle.has_finite_mass() {
+                stats.skipped_immovable += 1;
+                continue;
+            }
+            particle.add_force(self.gravity * particle.get_mass());
+            if !particle.is_awake() {
+                match self.sleep {
+                    Some(sleep) if particle.speed() < sleep.linear_threshold => {
+                        particle.integrate(duration);
+                        stats.skipped_asleep += 1;
+                        continue;
+                    }
+                    _ => particle.set_awake(true),
+                }
+            }
+            particle.integrate(duration);
+            stats.integrated += 1;
+            stats.max_speed = stats.max_speed.max(particle.velocity.magnitude());
+        }
+        stats.particles = self.particles.len();
+        stats
+    }
+
+    /// Puts to sleep the particles at rest for long enough, and wakes the sleeping ones
+    /// that forces or contacts set moving. The others are put back where they were at the
+    /// start of the step. Returns the number of movable particles left awake.
+    fn update_sleep(&mut self, duration: f64) -> usize {
+        let sleep = match self.sleep {
+            Some(sleep) => sleep,
+            None => return self.awake_count(),
+        };
+        let mut awake = 0;
+        for (particle, &(position, velocity)) in self
+            .particles
+            .as_mut_slice()
+            .iter_mut()
+            .zip(&self.step_start)
+        {
+            if !particle.has_finite_mass() {
+                continue;
+            }
+            if particle.is_awake() {
+                if particle.count_rest_step(sleep.linear_threshold, velocity, duration)
+                    >= sleep.steps
+                {
+                    particle.set_awake(false);
+                    continue;
+                }
+            } else if particle.at_rest(sleep.linear_threshold, velocity, duration) {
+                particle.position = position;
+                particle.velocity = velocity;
+                continue;
+            } else {
+                particle.set_awake(true);
+            }
+            awake += 1;
+        }
+        awake
+    }
+
+    /// Runs `run_physics` over the given duration in equal steps no longer than `max_substep`,
+    /// which keeps large frame times from destabilizing the simulation: every substep
+    /// applies the forces, integrates, and generates and resolves the contacts.
+    /// Forces added before the call only act during the first substep; the accumulators
+    /// are cleared before each of the following ones, like `start_frame` does.
+    /// The duration is scaled by the time scale first, so `max_substep` bounds the simulated steps.
+    /// The stats are those of the last substep, except `max_speed` which covers all of them.
+    pub fn step_substepped(&mut self, duration: f64, max_substep: f64) -> WorldStats {
+        assert!(max_substep > 0.);
+        let duration = match self.scaled(duration) {
+            Some(duration) => duration,
+            None => return WorldStats::default(),
+        };
+
+        // Allow for rounding errors, so 0.1s in substeps of 0.02s is five substeps and not six.
+        let substeps = ((duration / max_substep) - 1e-9).ceil().max(1.) as usize;
+        let substep = duration / substeps as f64;
+
+        let mut stats = WorldStats::default();
+        for i in 0..substeps {
+            if i > 0 {
+                self.start_frame();
+            }
+            let max_speed = stats.max_speed;
+            stats = self.physics(substep);
+            stats.max_speed = stats.max_speed.max(max_speed);
+        }
+        stats
+    }
+
+    /// Returns the handles of the particles within `radius` of `center`,
+    /// sorted in ascending order.
+    pub fn query_radius(&self, center: Vector3, radius: f64) -> Vec<ParticleHandle> {
+        let particles = self.particles.as_slice();
+        let mut result: Vec<ParticleHandle> = match self.current_grid() {
+            Some(grid) => grid
+                .query_radius(particles, center, radius)
+                .into_iter()
+                .map(|index| self.handle_at(index))
+                .collect(),
+            None => {
+                let radius_squared = radius * radius;
+                (0..particles.len())
+                    .filter(|&index| {
+                        (particles[index].position - center).square_magnitude() <= radius_squared
+                    })
+                    .map(|index| self.handle_at(index))
+                    .collect()
+            }
+        };
+        result.sort_unstable();
+        result
+    }
+
+    /// Returns every pair of particles closer than `radius` to each other.
+    /// Each pair is reported once as `(a, b)` with `a < b`, sorted in ascending order.
+    pub fn neighbor_pairs(&self, radius: f64) -> Vec<(ParticleHandle, ParticleHandle)> {
+        let mut result: Vec<(ParticleHandle, ParticleHandle)> = self
+            .index_pairs(radius)
+            .into_iter()
+            .map(|(i, j)| {
+                let (a, b) = (self.handle_at(i), self.handle_at(j));
+                if a < b {
+                    (a, b)
+                } else {
+                    (b, a)
+                }
+            })
+            .collect();
+        result.sort_unstable();
+        result
+    }
+
+    /// Returns a contact for every pair of overlapping particles, treating each particle
+    /// as a sphere of the given radius. Contacts use the collision restitution, and their
+    /// normal points from the second particle to the first.
+    /// Uses the grid if enabled and up to date, and checks every pair otherwise.
+    pub fn generate_contacts(&self, radius: f64) -> Vec<ParticleContact> {
+        let particles = self.particles.as_slice();
+        let reach = 2. * radius;
+        let mut contacts = Vec::new();
+        for (i, j) in self.index_pairs(reach) {
+            let offset = particles[i].position - particles[j].position;
+            let distance = offset.magnitude();
+            if distance >= reach {
+                continue;
+            }
+            let normal = if distance > 0. {
+                offset * (1. / distance)
+            } else {
+                ParticleCollisionGenerator::COINCIDENT_NORMAL
+            };
+            contacts.push(ParticleContact::new(
+                (self.handle_at(i), Some(self.handle_at(j))),
+                self.collision_restitution,
+                normal,
+                reach - distance,
+            ));
+        }
+        contacts
+    }
+
+    /// Returns every pair `(i, j)`, with `i < j`, of positions in `get_particles`
+    /// of particles closer than `radius` to each other.
+    fn index_pairs(&self, radius: f64) -> Vec<(usize, usize)> {
+        let particles = self.particles.as_slice();
+        match self.current_grid() {
+            Some(grid) => grid.neighbor_pairs(particles, radius),
+            None => {
+                let radius_squared = radius * radius;
+                let mut pairs = Vec::new();
+                for i in 0..particles.len() {
+                    for j in (i + 1)..particles.len() {
+                        if (particles[i].position - particles[j].position).square_magnitude()
+                            <= radius_squared
+                        {
+                            pairs.push((i, j));
+                        }
+                    }
+                }
+                pairs
+            }
+        }
+    }
+}
+
+/// The part of a `ParticleWorld` that is saved, holding the particles
+/// by reference when saving and by value when loading.
+#[cfg(feature = "serde")]
+#[derive(serde::Serialize, serde::Deserialize)]
+struct WorldState<P> {
+    /// Holds the particles, with their handles.
+    particles: P,
+
+    /// Holds the gravity of the world.
+    gravity: Vector3,
+
+    /// Holds the cell size of the grid, if enabled. The grid is rebuilt on load.
+    grid_cell_size: Option<f64>,
+
+    /// Holds the contact budget and the resolver iterations.
+    settings: ParticleWorldSettings,
+
+    /// Holds the restitution of the contacts made by `generate_contacts`.
+    collision_restitution: f64,
+
+    /// Holds when particles fall asleep, if ever.
+    sleep: Option<ParticleSleep>,
+
+    /// Holds whether the world is paused.
+    paused: bool,
+
+    /// Holds the time scale of the world.
+    time_scale: f64,
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for ParticleWorld {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        WorldState {
+            particles: &self.particles,
+            gravity: self.gravity,
+            grid_cell_size: self.grid.as_ref().map(UniformGrid::get_cell_size),
+            settings: self.settings,
+            collision_restitution: self.collision_restitution,
+            sleep: self.sleep,
+            paused: self.paused,
+            time_scale: self.time_scale,
+        }
+        .serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ParticleWorld {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let state = WorldState::<ParticleSet>::deserialize(deserializer)?;
+        let mut world = ParticleWorld {
+            particles: state.particles,
+            gravity: state.gravity,
+            settings: state.settings,
+            collision_restitution: state.collision_restitution,
+            sleep: state.sleep,
+            paused: state.paused,
+            time_scale: state.time_scale,
+            ..ParticleWorld::default()
+        };
+        if let Some(cell_size) = state.grid_cell_size {
+            world.enable_grid(cell_size);
+        }
+        Ok(world)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kellenth::contacts::GroundContactGenerator;
+    use crate::kellenth::noise::Random;
+
+    const ZERO: Vector3 = Vector3::new(0., 0., 0.);
+
+    /// Returns a particle with the given mass at rest at the given position.
+    fn particle_at(position: Vector3, mass: f64) -> Particle {
+        let mut particle = Particle::new(position, ZERO, ZERO, 1.);
+        particle.set_mass(mass);
+        particle
+    }
+
+    /// Returns a world holding the given number of particles scattered around the origin.
+    fn scattered_world(count: usize, seed: u64) -> ParticleWorld {
+        let mut rng = Random::new(seed);
+        let mut world = ParticleWorld::new();
+        for _ in 0..count {
+            world.add_particle(particle_at(rng.in_sphere(5.), 1.));
+        }
+        world
+    }
+
+    #[test]
+    fn grid_queries_match_brute_force() {
+        let mut world = scattered_world(300, 1);
+        let mut rng = Random::new(2);
+        let queries: Vec<(Vector3, f64)> = (0..30)
+            .map(|_| (rng.in_sphere(6.), rng.range(0.2, 2.)))
+            .collect();
+        let brute_queries: Vec<_> = queries
+            .iter()
+            .map(|&(c, r)| world.query_radius(c, r))
+            .collect();
+        let brute_pairs = world.neighbor_pairs(0.8);
+
+        world.enable_grid(1.);
+        let grid_queries: Vec<_> = queries
+            .iter()
+            .map(|&(c, r)| world.query_radius(c, r))
+            .collect();
+        assert_eq!(grid_queries, brute_queries);
+        assert_eq!(world.neighbor_pairs(0.8), brute_pairs);
+    }
+
+    #[test]
+    fn grid_is_rebuilt_once_after_adding_and_removing() {
+        let mut world = scattered_world(50, 3);
+        world.enable_grid(1.);
+        assert!(!world.is_grid_dirty());
+
+        let added = world.add_particle(particle_at(Vector3::new(20., 0., 0.), 1.));
+        let removed = world.handle_at(0);
+        world.remove_particle(removed);
+        assert!(world.is_grid_dirty());
+
+        // Queries stay correct while the grid is stale.
+        let found = world.query_radius(Vector3::new(20., 0., 0.), 0.5);
+        assert_eq!(found, vec![added]);
+
+        world.step(0.01);
+        assert!(!world.is_grid_dirty());
+        assert_eq!(
+            world.query_radius(Vector3::new(20., 0., 0.), 0.5),
+            vec![added]
+        );
+        assert!(!world.query_radius(ZERO, 10.).contains(&removed));
+    }
+
+    #[test]
+    fn despawned_slot_is_reused_by_the_next_spawn() {
+        let mut pool = ParticlePool::new();
+        let ids: Vec<ParticleId> = (0..4)
+            .map(|i| pool.spawn(particle_at(Vector3::new(i as f64, 0., 0.), 1.)))
+            .collect();
+
+        assert!(pool.despawn(ids[1]));
+        assert!(!pool.despawn(ids[1]));
+        let respawned = pool.spawn(particle_at(ZERO, 2.));
+        assert_eq!(respawned.slot(), ids[1].slot());
+        assert_ne!(respawned, ids[1]);
+        assert!(pool.get(ids[1]).is_none());
+        assert_eq!(pool[respawned].get_mass(), 2.);
+    }
+
+    #[test]
+    fn churning_within_capacity_does_not_grow_the_pool() {
+        let mut world = ParticleWorld::new();
+        world.reserve(64);
+        let capacity = world.capacity();
+        let mut rng = Random::new(3);
+        let mut live: Vec<ParticleId> = (0..32)
+            .map(|_| world.spawn(particle_at(rng.in_sphere(1.), 1.)))
+            .collect();
+
+        for _ in 0..10_000 {
+            let victim = (rng.next_f64() * live.len() as f64) as usize % live.len();
+            assert!(world.despawn(live.swap_remove(victim)));
+            while live.len() < 32 + (rng.next_f64() * 32.) as usize {
+                live.push(world.spawn(particle_at(rng.in_sphere(1.), 1.)));
+            }
+            assert!(world.len() <= 64);
+        }
+        assert_eq!(world.capacity(), capacity);
+        assert!(live.iter().all(|&id| world.contains(id)));
+    }
+
+    #[test]
+    fn step_reports_integrated_and_immovable_particles() {
+        let mut world = ParticleWorld::new();
+        world.add_particle(particle_at(ZERO, 1.));
+        let mut fast = particle_at(Vector3::new(5., 0., 0.), 2.);
+        fast.velocity = Vector3::new(0., 3., 4.);
+        world.add_particle(fast);
+        world.add_particle(Particle::new(Vector3::new(-5., 0., 0.), ZERO, ZERO, 1.));
+
+        let stats = world.step(0.1);
+        assert_eq!(stats.particles, 3);
+        assert_eq!(stats.integrated, 2);
+        assert_eq!(stats.skipped_immovable, 1);
+        assert_eq!(stats.skipped_asleep, 0);
+        assert_eq!(stats.awake_particles, 2);
+        assert_eq!(stats.max_speed, 5.);
+    }
+
+    #[test]
+    fn removing_a_particle_purges_its_force_registrations() {
+        let mut world = ParticleWorld::new();
+        let a = world.add_particle(particle_at(ZERO, 1.));
+        let b = world.add_particle(particle_at(Vector3::new(1., 0., 0.), 1.));
+        let registry = world.get_force_registry_mut();
+        registry.add_fn(a, |_, _| {});
+        registry.add_fn(b, |_, _| {});
+        registry.add_pair(
+            a,
+            b,
+            Box::new(crate::kellenth::forces::ParticleSpring::new(1., 1.)),
+        );
+
+        assert!(world.remove_particle(b));
+        let left: Vec<_> = world.get_force_registry().iter().collect();
+        assert_eq!(left.len(), 1);
+        assert_eq!((left[0].particle, left[0].other), (a, None));
+    }
+
+    #[test]
+    fn world_gravity_moves_only_movable_particles() {
+        let gravity = Vector3::new(0., -9.81, 0.);
+        let mut world = ParticleWorld::new();
+        world.set_gravity(gravity);
+        assert_eq!(world.get_gravity().y, -9.81);
+        let light = world.add_particle(particle_at(ZERO, 1.));
+        let heavy = world.add_particle(particle_at(Vector3::new(1., 0., 0.), 50.));
+        let fixed = world.add_particle(Particle::new(Vector3::new(2., 0., 0.), ZERO, ZERO, 1.));
+
+        for _ in 0..10 {
+            world.step(0.1);
+        }
+        for handle in [light, heavy] {
+            assert!((world.get(handle).unwrap().velocity.y + 9.81).abs() < 1e-9);
+            assert!(world.get(handle).unwrap().position.y < -4.);
+        }
+        let fixed = world.get(fixed).unwrap();
+        assert_eq!(fixed.velocity.magnitude(), 0.);
+        assert_eq!(fixed.position.y, 0.);
+    }
+
+    #[test]
+    fn ids_stay_valid_across_steps_and_go_stale_on_removal() {
+        let mut world = ParticleWorld::new();
+        world.set_gravity(Vector3::new(0., -1., 0.));
+        let ids: Vec<ParticleId> = (0..3)
+            .map(|i| world.add_particle(particle_at(Vector3::new(i as f64, 0., 0.), 1.)))
+            .collect();
+        for _ in 0..5 {
+            world.step(0.1);
+        }
+        for (i, &id) in ids.iter().enumerate() {
+            assert_eq!(world.get(id).unwrap().position.x, i as f64);
+        }
+        assert_eq!(
+            world.query_radius(Vector3::new(2., -0.15, 0.), 0.5),
+            [ids[2]]
+        );
+
+        assert!(world.remove_particle(ids[0]));
+        assert!(!world.contains(ids[0]));
+        assert!(world.get(ids[0]).is_none());
+        assert!(!world.remove_particle(ids[0]));
+        // A new particle reusing the storage doesn't bring the stale id back.
+        let new = world.add_particle(particle_at(ZERO, 1.));
+        assert!(world.get(ids[0]).is_none());
+        assert_ne!(new, ids[0]);
+    }
+
+    #[test]
+    fn removing_the_middle_particle_keeps_the_others() {
+        let mut world = ParticleWorld::new();
+        let ids: Vec<ParticleId> = (0..3)
+            .map(|i| world.add_particle(particle_at(Vector3::new(i as f64, 0., 0.), 1.)))
+            .collect();
+        assert!(world.remove_particle(ids[1]));
+
+        assert_eq!(world.get(ids[0]).unwrap().position.x, 0.);
+        assert_eq!(world.get(ids[2]).unwrap().position.x, 2.);
+        assert!(world.get(ids[1]).is_none());
+        assert_eq!(world.len(), 2);
+        // The last particle was moved into the gap of the packed slice.
+        assert_eq!(world.index_of(ids[2]), Some(1));
+        assert_eq!(world.handle_at(1), ids[2]);
+    }
+
+    #[test]
+    fn substepping_matches_manual_steps() {
+        let mut substepped = scattered_world(20, 4);
+        substepped.set_gravity(Vector3::new(0., -9.81, 0.));
+        for (i, particle) in substepped.get_particles_mut().iter_mut().enumerate() {
+            particle.velocity = Vector3::new(i as f64, 0., -1.);
+            particle.damping = 0.9;
+        }
+        let mut manual = ParticleWorld::new();
+        manual.set_gravity(substepped.get_gravity());
+        for &particle in substepped.get_particles() {
+            manual.add_particle(particle);
+        }
+
+        let stats = substepped.step_substepped(0.1, 0.02);
+        for _ in 0..5 {
+            manual.start_frame();
+            manual.run_physics(0.02);
+        }
+        for (a, b) in substepped
+            .get_particles()
+            .iter()
+            .zip(manual.get_particles())
+        {
+            assert_eq!(a.bit_key(), b.bit_key());
+        }
+        assert_eq!(stats.integrated, 20);
+
+        // Five substeps, not six: a lone particle ends where five semi-implicit steps of
+        // 0.02 take it, y = -g·dt²·(1 + 2 + ... + 5).
+        let mut single = ParticleWorld::new();
+        single.set_gravity(manual.get_gravity());
+        let handle = single.add_particle(particle_at(ZERO, 1.));
+        single.step_substepped(0.1, 0.02);
+        let expected = -9.81 * 0.02 * 0.02 * 15.;
+        assert!((single.get(handle).unwrap().position.y - expected).abs() < 1e-12);
+    }
+
+    #[test]
+    fn particles_fall_onto_the_ground_and_come_to_rest() {
+        let mut world = ParticleWorld::new();
+        world.set_gravity(Vector3::new(0., -9.81, 0.));
+        let mut ground =
+            GroundContactGenerator::new(Plane::new(Vector3::new(0., 1., 0.), 0.), 0.4, 0.5);
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                world.add_particle(particle_at(
+                    Vector3::new(i as f64 * 2., 1. + i as f64, 0.),
+                    1.,
+                ))
+            })
+            .collect();
+        for &handle in &handles {
+            ground.add_particle(handle);
+        }
+        world.add_contact_generator(Box::new(ground));
+
+        let mut stats = WorldStats::default();
+        for _ in 0..1000 {
+            world.start_frame();
+            stats = world.run_physics(0.01);
+            // Without explicit iterations the resolver gets twice the contact count.
+            assert!(stats.iterations_used <= 2 * stats.contacts_generated);
+        }
+
+        assert_eq!(stats.contacts_generated, handles.len());
+        for &handle in &handles {
+            let particle = world.get(handle).unwrap();
+            assert!(
+                (particle.position.y - 0.5).abs() < 0.01,
+                "{:?}",
+                particle.position
+            );
+            assert!(particle.velocity.magnitude() < 0.1);
+        }
+    }
+
+    #[test]
+    fn generate_contacts_finds_only_the_overlapping_pair() {
+        let mut world = ParticleWorld::new();
+        world.add_particle(particle_at(Vector3::new(5., 0., 0.), 1.));
+        let first = world.add_particle(particle_at(Vector3::new(0., 1.5, 0.), 1.));
+        let second = world.add_particle(particle_at(Vector3::new(0., 0., 0.), 1.));
+
+        let contacts = world.generate_contacts(1.);
+        assert_eq!(contacts.len(), 1);
+        let contact = &contacts[0];
+        assert_eq!(contact.particles, (first, Some(second)));
+        assert_eq!(
+            contact
+                .contact_normal
+                .manhattan_distance(&Vector3::new(0., 1., 0.)),
+            0.
+        );
+        assert_eq!(contact.penetration, 0.5);
+        assert_eq!(contact.restitution, world.get_collision_restitution());
+
+        // The same scene through the grid finds the same contact.
+        world.enable_grid(1.);
+        world.rebuild();
+        let grid_contacts = world.generate_contacts(1.);
+        assert_eq!(grid_contacts.len(), 1);
+        assert_eq!(grid_contacts[0].particles, contact.particles);
+    }
+
+    #[test]
+    fn stale_handles_do_not_alias_particles_reusing_their_slot() {
+        let mut world = ParticleWorld::new();
+        let a = world.add_particle(particle_at(Vector3::new(0., 0.2, 0.), 1.));
+        let b = world.add_particle(particle_at(Vector3::new(5., 0., 0.), 1.));
+        let mut ground =
+            GroundContactGenerator::new(Plane::new(Vector3::new(0., 1., 0.), 0.), 0., 0.5);
+        ground.add_particle(a);
+        world.add_contact_generator(Box::new(ground));
+        world.get_force_registry_mut().add_fn(a, |particle, _| {
+            particle.add_force(Vector3::new(100., 0., 0.))
+        });
+
+        // The next particle added takes the free slot of `a`.
+        assert!(world.remove_particle(a));
+        let c = world.add_particle(particle_at(Vector3::new(-3., -2., 0.), 1.));
+        assert_eq!(c.slot(), a.slot());
+        assert!(world.get(a).is_none());
+        assert!(world.get_mut(a).is_none());
+        assert!(!world.remove_particle(a));
+        let mut live: Vec<_> = world.iter().map(|(handle, _)| handle).collect();
+        live.sort_by_key(|handle| handle.slot());
+        assert_eq!(live, [c, b]);
+
+        // Neither the ground contact nor the force registered for `a` reach `c`.
+        world.start_frame();
+        let stats = world.run_physics(0.1);
+        assert_eq!(stats.contacts_generated, 0);
+        assert_eq!(stats.forces_applied, 0);
+        let moved = world.get(c).unwrap();
+        assert_eq!(
+            moved
+                .position
+                .manhattan_distance(&Vector3::new(-3., -2., 0.)),
+            0.
+        );
+        assert_eq!(world.get(b).unwrap().position.x, 5.);
+    }
+
+    #[test]
+    fn contact_budget_keeps_the_deepest_contacts() {
+        let mut world = ParticleWorld::new();
+        world.set_max_contacts(3);
+        let mut ground =
+            GroundContactGenerator::new(Plane::new(Vector3::new(0., 1., 0.), 0.), 0., 1.);
+        let depths = [0.1, 0.6, 0.3, 0.9, 0.2, 0.5];
+        let handles: Vec<_> = depths
+            .iter()
+            .enumerate()
+            .map(|(i, depth)| {
+                world.add_particle(particle_at(Vector3::new(i as f64 * 3., 1. - depth, 0.), 1.))
+            })
+            .collect();
+        for &handle in &handles {
+            ground.add_particle(handle);
+        }
+        world.add_contact_generator(Box::new(ground));
+
+        world.start_frame();
+        let stats = world.run_physics(0.01);
+        assert_eq!(stats.contacts_generated, 6);
+        assert_eq!(stats.contacts_dropped, 3);
+        assert_eq!(world.get_contacts().len(), 3);
+        assert!((stats.max_penetration - 0.9).abs() < 1e-12);
+        assert!(stats.iterations_used <= 6);
+        let mut kept: Vec<_> = world
+            .get_contacts()
+            .iter()
+            .map(|contact| contact.particles.0)
+            .collect();
+        kept.sort_by_key(|handle| handle.slot());
+        assert_eq!(kept, [handles[1], handles[3], handles[5]]);
+
+        // An explicit iteration count replaces the Cyclone heuristic.
+        world.set_settings(ParticleWorldSettings {
+            resolver_iterations: Some(1),
+            calculate_iterations: false,
+            ..world.get_settings()
+        });
+        world.start_frame();
+        assert_eq!(world.run_physics(0.01).iterations_used, 1);
+    }
+
+    #[test]
+    fn listener_hears_the_bounce_but_not_the_resting_contact() {
+        use crate::kellenth::contacts::ParticleContactListenerClosure;
+        use std::cell::RefCell;
+        use std::rc::Rc;
+
+        let mut world = ParticleWorld::new();
+        world.set_gravity(Vector3::new(0., -9.81, 0.));
+        let mut falling = particle_at(Vector3::new(0., 0.55, 0.), 2.);
+        falling.velocity = Vector3::new(0., -5., 0.);
+        let handle = world.add_particle(falling);
+        let mut ground =
+            GroundContactGenerator::new(Plane::new(Vector3::new(0., 1., 0.), 0.), 0., 0.5);
+        ground.add_particle(handle);
+        world.add_contact_generator(Box::new(ground));
+
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let heard = Rc::clone(&events);
+        world.set_contact_listener(Box::new(ParticleContactListenerClosure::new(
+            1.,
+            move |event: &ContactEvent| heard.borrow_mut().push(*event),
+        )));
+
+        for _ in 0..200 {
+            world.start_frame();
+            world.run_physics(0.01);
+        }
+        // The particle rests on the floor, resolved every frame with a tiny impulse.
+        assert!(!world.get_contacts().is_empty());
+        let events = events.borrow();
+        assert_eq!(events.len(), 1);
+        let bounce = events[0];
+        assert_eq!(bounce.handles, (handle, None));
+        assert_eq!(bounce.normal.y, 1.);
+        assert!(bounce.separating_velocity_before < -5.);
+        assert!((bounce.impulse_applied + 2. * bounce.separating_velocity_before).abs() < 1e-9);
+    }
+
+    #[test]
+    fn stepping_once_while_paused_matches_running() {
+        let gravity = Vector3::new(0., -9.81, 0.);
+        let mut paused = scattered_world(20, 4);
+        let mut running = scattered_world(20, 4);
+        paused.set_gravity(gravity);
+        running.set_gravity(gravity);
+        paused.set_paused(true);
+
+        let before: Vec<_> = paused
+            .get_particles()
+            .iter()
+            .map(Particle::bit_key)
+            .collect();
+        assert_eq!(paused.run_physics(0.1), WorldStats::default());
+        let after: Vec<_> = paused
+            .get_particles()
+            .iter()
+            .map(Particle::bit_key)
+            .collect();
+        assert_eq!(before, after);
+
+        for _ in 0..10 {
+            paused.step_once(0.02);
+            running.run_physics(0.02);
+        }
+        assert!(paused.is_paused());
+        let keys = |world: &ParticleWorld| {
+            world
+                .get_particles()
+                .iter()
+                .map(Particle::bit_key)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(keys(&paused), keys(&running));
+    }
+
+    #[test]
+    fn time_scale_multiplies_the_frame_duration() {
+        let mut slow = ParticleWorld::new();
+        let mut normal = ParticleWorld::new();
+        for world in [&mut slow, &mut normal] {
+            let mut particle = particle_at(ZERO, 1.);
+            particle.velocity = Vector3::new(3., 0., -1.);
+            world.add_particle(particle);
+        }
+        slow.set_time_scale(0.5);
+        slow.run_physics(0.1);
+        slow.run_physics(0.1);
+        normal.run_physics(0.1);
+        let moved = |world: &ParticleWorld| world.get_particles()[0].position;
+        assert!(moved(&slow).chebyshev_distance(&moved(&normal)) < 1e-12);
+        assert!((moved(&normal).x - 0.3).abs() < 1e-12);
+
+        // Negative scales freeze the world rather than running it backwards.
+        slow.set_time_scale(-1.);
+        assert_eq!(slow.get_time_scale(), 0.);
+        let frozen = moved(&slow);
+        slow.run_physics(0.1);
+        assert_eq!(moved(&slow).manhattan_distance(&frozen), 0.);
+    }
+
+    #[test]
+    fn stats_count_a_hand_checked_scene() {
+        let mut world = ParticleWorld::new();
+        let sunk = world.add_particle(particle_at(Vector3::new(0., 0.3, 0.), 1.));
+        let mut flying = particle_at(Vector3::new(5., 3., 0.), 2.);
+        flying.velocity = Vector3::new(4., 0., 3.);
+        let flying = world.add_particle(flying);
+        world.add_particle(Particle::new(Vector3::new(-5., 0., 0.), ZERO, ZERO, 1.));
+        world
+            .get_force_registry_mut()
+            .add_fn(flying, |particle, _| {
+                particle.add_force(Vector3::new(0., 1., 0.))
+            });
+        let mut ground =
+            GroundContactGenerator::new(Plane::new(Vector3::new(0., 1., 0.), 0.), 0., 0.5);
+        ground.add_particle(sunk);
+        ground.add_particle(flying);
+        world.add_contact_generator(Box::new(ground));
+        world.add_contact_generator(Box::new(GroundContactGenerator::new(
+            Plane::new(Vector3::new(0., 1., 0.), 0.),
+            0.,
+            0.5,
+        )));
+
+        world.start_frame();
+        let stats = world.run_physics(0.01);
+        assert_eq!(stats.particles, 3);
+        assert_eq!(stats.integrated, 2);
+        assert_eq!(stats.skipped_immovable, 1);
+        assert_eq!(stats.skipped_asleep, 0);
+        assert_eq!(stats.awake_particles, 2);
+        assert_eq!(stats.forces_applied, 1);
+        assert_eq!(stats.contacts_generated, 1);
+        assert_eq!(stats.contacts_dropped, 0);
+        assert_eq!(world.get_contacts_per_generator(), [1, 0]);
+        assert!((stats.max_penetration - 0.2).abs() < 1e-12);
+        // The force has sped the flying particle up by 0.005 upwards.
+        assert!((stats.max_speed - (25f64 + 0.005 * 0.005).sqrt()).abs() < 1e-12);
+        assert!(stats.timings.is_some());
+        assert_eq!(world.get_last_stats(), stats);
+
+        world.set_settings(ParticleWorldSettings {
+            collect_timings: false,
+            ..world.get_settings()
+        });
+        world.start_frame();
+        assert!(world.run_physics(0.01).timings.is_none());
+    }
+
+    #[test]
+    fn for_each_mut_visits_every_particle_with_its_id() {
+        let mut world = scattered_world(10, 6);
+        world.for_each_mut(|_, particle| particle.velocity = Vector3::new(1., 2., 3.));
+        assert!(world
+            .get_particles()
+            .iter()
+            .all(|particle| particle.speed() > 0.));
+
+        let mut visited = Vec::new();
+        world.for_each_mut(|id, particle| {
+            particle.velocity = ZERO;
+            visited.push(id);
+        });
+        assert!(world
+            .get_particles()
+            .iter()
+            .all(|particle| particle.speed() == 0.));
+        let ids: Vec<_> = world.iter().map(|(id, _)| id).collect();
+        assert_eq!(visited, ids);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn world_survives_a_json_round_trip() {
+        let mut world = scattered_world(12, 8);
+        let removed = world.handle_at(3);
+        world.remove_particle(removed);
+        world.set_gravity(Vector3::new(0., -3., 1.));
+        world.enable_grid(1.5);
+        world.set_max_contacts(17);
+        world.set_collision_restitution(0.3);
+        world.set_sleep(Some(ParticleSleep::default()));
+        world.set_time_scale(0.75);
+        world.for_each_mut(|_, particle| particle.add_force(Vector3::new(0.5, 0., 0.)));
+        world.step(0.1);
+
+        let json = serde_json::to_string(&world).unwrap();
+        let mut loaded: ParticleWorld = serde_json::from_str(&json).unwrap();
+        let keys = |world: &ParticleWorld| {
+            world
+                .iter()
+                .map(|(handle, particle)| (handle, particle.bit_key()))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(keys(&loaded), keys(&world));
+        assert!(loaded.get(removed).is_none());
+        assert_eq!(
+            loaded
+                .get_gravity()
+                .manhattan_distance(&world.get_gravity()),
+            0.
+        );
+        assert_eq!(loaded.get_grid().unwrap().get_cell_size(), 1.5);
+        assert_eq!(loaded.get_settings(), world.get_settings());
+        assert_eq!(loaded.get_collision_restitution(), 0.3);
+        assert_eq!(loaded.get_sleep(), world.get_sleep());
+        assert_eq!(loaded.get_time_scale(), 0.75);
+        assert_eq!(serde_json::to_string(&loaded).unwrap(), json);
+
+        // Both worlds go on the same way, down to the handle the next particle gets.
+        let next = particle_at(ZERO, 1.);
+        assert_eq!(loaded.add_particle(next), world.add_particle(next));
+        for _ in 0..10 {
+            loaded.step(0.05);
+            world.step(0.05);
+        }
+        assert_eq!(keys(&loaded), keys(&world));
+    }
+
+    #[test]
+    fn settled_particles_sleep_until_an_impulse_wakes_them() {
+        let mut world = ParticleWorld::new();
+        world.set_gravity(Vector3::new(0., -9.81, 0.));
+        world.set_sleep(Some(ParticleSleep::default()));
+        let handle = world.add_particle(particle_at(Vector3::new(0., 1., 0.), 1.));
+        let mut ground =
+            GroundContactGenerator::new(Plane::new(Vector3::new(0., 1., 0.), 0.), 0., 0.5);
+        ground.add_particle(handle);
+        world.add_contact_generator(Box::new(ground));
+        assert_eq!(world.awake_count(), 1);
+
+        let mut steps = 0;
+        while world.awake_count() > 0 {
+            world.start_frame();
+            world.run_physics(0.01);
+            steps += 1;
+            assert!(steps < 500, "never fell asleep");
+        }
+        assert!(!world.get(handle).unwrap().is_awake());
+        let resting = world.get(handle).unwrap().position;
+        world.start_frame();
+        let stats = world.run_physics(0.01);
+        assert_eq!(
+            (
+                stats.skipped_asleep,
+                stats.integrated,
+                stats.awake_particles
+            ),
+            (1, 0, 0)
+        );
+        assert_eq!(
+            world
+                .get(handle)
+                .unwrap()
+                .position
+                .manhattan_distance(&resting),
+            0.
+        );
+
+        world
+            .get_mut(handle)
+            .unwrap()
+            .apply_impulse(Vector3::new(0., 3., 0.));
+        world.start_frame();
+        let stats = world.run_physics(0.01);
+        assert_eq!((stats.skipped_asleep, stats.integrated), (0, 1));
+        assert_eq!(world.awake_count(), 1);
+        assert!(world.get(handle).unwrap().position.y > resting.y);
+    }
+
+    #[test]
+    fn center_of_mass_leans_towards_the_heavier_particle() {
+        let mut world = ParticleWorld::new();
+        assert!(world.center_of_mass().is_none());
+        // Immovable particles don't count.
+        world.add_particle(Particle::new(Vector3::new(100., 0., 0.), ZERO, ZERO, 1.));
+        assert!(world.center_of_mass().is_none());
+
+        world.add_particle(particle_at(Vector3::new(0., 0., 0.), 1.));
+        world.add_particle(particle_at(Vector3::new(4., 2., 0.), 3.));
+        let center = world.center_of_mass().unwrap();
+        assert!((center - Vector3::new(3., 1.5, 0.)).chebyshev_distance(&ZERO) < 1e-12);
+    }
+
+    #[test]
+    fn substeps_resolve_the_contacts_of_fast_particles() {
+        let mut world = ParticleWorld::new();
+        world.set_gravity(Vector3::new(0., -9.81, 0.));
+        let mut ground =
+            GroundContactGenerator::new(Plane::new(Vector3::new(0., 1., 0.), 0.), 0., 0.5);
+        let mut fast = particle_at(Vector3::new(0., 20., 0.), 1.);
+        fast.velocity = Vector3::new(0., -200., 0.);
+        let handle = world.add_particle(fast);
+        ground.add_particle(handle);
+        world.add_contact_generator(Box::new(ground));
+
+        for _ in 0..10 {
+            world.start_frame();
+            world.step_substepped(0.1, 0.005);
+            assert!(world.get(handle).unwrap().position.y > 0.5 - 1e-9);
+        }
+        assert_eq!(world.get_last_stats().contacts_generated, 1);
+        assert!(world.get(handle).unwrap().speed() < 0.1);
+
+        // A single long step jumps right over a small obstacle the substeps run into.
+        let run = |max_substep: f64| {
+            let mut world = ParticleWorld::new();
+            let mut boulder = ParticleCollisionGenerator::new(0.);
+            let rock = world.add_particle(Particle::new(ZERO, ZERO, ZERO, 1.));
+            let mut fast = particle_at(Vector3::new(0., 3., 0.), 1.);
+            fast.velocity = Vector3::new(0., -100., 0.);
+            let handle = world.add_particle(fast);
+            boulder.add_particle(rock, 1.);
+            boulder.add_particle(handle, 0.25);
+            world.add_contact_generator(Box::new(boulder));
+            world.step_substepped(0.1, max_substep);
+            world.get(handle).unwrap().position.y
+        };
+        assert!(run(0.1) < -5.);
+        assert!(run(0.005) > 1.25 - 1e-9);
+    }
+
+    #[test]
+    fn particles_held_up_by_the_ground_sleep_under_a_gravity_generator() {
+        use crate::kellenth::forces::ParticleGravity;
+
+        let mut world = ParticleWorld::new();
+        world.set_sleep(Some(ParticleSleep::default()));
+        let handle = world.add_particle(particle_at(Vector3::new(0., 1., 0.), 2.));
+        let gravity = ParticleGravity::new(Vector3::new(0., -9.81, 0.));
+        world
+            .get_force_registry_mut()
+            .add(handle, Box::new(gravity));
+        let mut ground =
+            GroundContactGenerator::new(Plane::new(Vector3::new(0., 1., 0.), 0.), 0., 0.5);
+        ground.add_particle(handle);
+        world.add_contact_generator(Box::new(ground));
+
+        let mut steps = 0;
+        while world.awake_count() > 0 {
+            world.start_frame();
+            world.run_physics(0.01);
+            steps += 1;
+            assert!(steps < 500, "never fell asleep");
+        }
+
+        // The weight still pulls every step, but the ground holds it still.
+        let resting = world.get(handle).unwrap().position;
+        for _ in 0..100 {
+            world.start_frame();
+            let stats = world.run_physics(0.01);
+            assert_eq!((stats.skipped_asleep, stats.awake_particles), (1, 0));
+        }
+        let particle = world.get(handle).unwrap();
+        assert_eq!(particle.position.manhattan_distance(&resting), 0.);
+        assert!((particle.position.y - 0.5).abs() < 0.01);
+
+        // Without the ground to hold it, the weight wakes it up.
+        let mut world = ParticleWorld::new();
+        world.set_sleep(Some(ParticleSleep::default()));
+        let mut particle = particle_at(resting, 2.);
+        particle.set_awake(false);
+        let handle = world.add_particle(particle);
+        world
+            .get_force_registry_mut()
+            .add(handle, Box::new(gravity));
+        world.start_frame();
+        let stats = world.run_physics(0.01);
+        assert_eq!(stats.awake_particles, 1);
+        assert!(world.get(handle).unwrap().position.y < resting.y);
+    }
+}
@@ -0,0 +1,747 @@
+//! Holds the builders that assemble common structures, like ropes, cloth and bridges, out of particles
+//! and links or springs, and register everything with a `ParticleWorld`.
+
+use crate::kellenth::core::*;
+use crate::kellenth::forces::ParticleDampedSpring;
+use crate::kellenth::links::{ParticleCable, ParticleCableConstraint, ParticleRod};
+use crate::kellenth::particle::Particle;
+
+use super::{ParticleHandle, ParticleWorld};
+
+/// The kind of link holding the segments of a rope together.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RopeConstraint {
+    /// Segments keep their length exactly, like a chain of rigid links.
+    Rod,
+
+    /// Segments can shrink freely but stretch by at most the given fraction
+    /// of their rest length, like a slack cable.
+    Cable {
+        /// Holds how much a segment can stretch, as a fraction of its rest length.
+        max_stretch: f64,
+    },
+}
+
+/// The particles making up a rope built by a `RopeBuilder`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RopeHandles {
+    /// Holds the particles of the rope, from the start to the end.
+    pub particles: Vec<ParticleHandle>,
+
+    /// Holds the rest length of every segment.
+    pub segment_length: f64,
+}
+
+impl RopeHandles {
+    /// Returns the particle at the start of the rope.
+    pub fn first(&self) -> ParticleHandle {
+        self.particles[0]
+    }
+
+    /// Returns the particle at the end of the rope.
+    pub fn last(&self) -> ParticleHandle {
+        self.particles[self.particles.len() - 1]
+    }
+}
+
+/// Builds a rope of particles laid out on a straight line, linked by rods or cables.
+///
+/// The resolver pulls a chain of rods straight one link at a time, so long rod ropes
+/// need many more iterations than the default, about the square of the segment count,
+/// and short steps when they swing fast. See `ParticleWorld::set_resolver_iterations`.
+#[derive(Debug, Clone, Copy)]
+pub struct RopeBuilder {
+    /// Holds the position of the first particle.
+    start: Vector3,
+
+    /// Holds the position of the last particle.
+    end: Vector3,
+
+    /// Holds the number of segments, one less than the number of particles.
+    segments: usize,
+
+    /// Holds the mass of the whole rope, split between the movable particles.
+    total_mass: f64,
+
+    /// Holds the damping of every particle.
+    damping: f64,
+
+    /// Holds the links between consecutive particles.
+    constraint: RopeConstraint,
+
+    /// Holds whether the first particle is immovable.
+    pin_start: bool,
+
+    /// Holds whether the last particle is immovable.
+    pin_end: bool,
+}
+
+impl RopeBuilder {
+    /// Constructor
+    /// The rope goes from `start` to `end` in the given number of segments. It weighs 1,
+    /// is linked by rods, and only its start is pinned, until configured otherwise.
+    pub fn new(start: Vector3, end: Vector3, segments: usize) -> Self {
+        assert!(segments > 0);
+        Self {
+            start,
+            end,
+            segments,
+            total_mass: 1.,
+            damping: 0.99,
+            constraint: RopeConstraint::Rod,
+            pin_start: true,
+            pin_end: false,
+        }
+    }
+
+    /// Constructs a rope hanging from the anchor, of the given length along the given direction.
+    pub fn from_anchor(anchor: Vector3, direction: Vector3, length: f64, segments: usize) -> Self {
+        Self::new(
+            anchor,
+            anchor + direction.get_normalized() * length,
+            segments,
+        )
+    }
+
+    /// Sets the mass of the whole rope, split evenly between the movable particles.
+    pub fn with_mass(mut self, total_mass: f64) -> Self {
+        assert!(total_mass > 0.);
+        self.total_mass = total_mass;
+        self
+    }
+
+    /// Sets the damping of every particle.
+    pub fn with_damping(mut self, damping: f64) -> Self {
+        self.damping = damping;
+        self
+    }
+
+    /// Sets the kind of link holding the segments together.
+    pub fn with_constraint(mut self, constraint: RopeConstraint) -> Self {
+        self.constraint = constraint;
+        self
+    }
+
+    /// Sets which ends of the rope are immovable.
+    pub fn with_pinned_ends(mut self, start: bool, end: bool) -> Self {
+        self.pin_start = start;
+        self.pin_end = end;
+        self
+    }
+
+    /// Adds the particles and links of the rope to the world.
+    /// Returns the particles, from the start to the end.
+    pub fn build(&self, world: &mut ParticleWorld) -> RopeHandles {
+        let count = self.segments + 1;
+        let pinned = (self.pin_start as usize) + (self.pin_end as usize);
+        let movable = count.saturating_sub(pinned).max(1);
+        let particle_mass = self.total_mass / movable as f64;
+
+        let zero = Vector3::new(0., 0., 0.);
+        let step = (self.end - self.start) * (1. / self.segments as f64);
+        let segment_length = step.magnitude();
+        let mut particles = Vec::with_capacity(count);
+        for i in 0..count {
+            let position = self.start + step * (i as f64);
+            let mut particle = Particle::new(position, zero, zero, self.damping);
+            if (i == 0 && self.pin_start) || (i == count - 1 && self.pin_end) {
+                particle.set_inverse_mass(0.);
+            } else {
+                particle.set_mass(particle_mass);
+            }
+            particles.push(world.add_particle(particle));
+        }
+
+        for pair in particles.windows(2) {
+            let pair = (pair[0], pair[1]);
+            match self.constraint {
+                RopeConstraint::Rod => {
+                    world.add_contact_generator(Box::new(ParticleRod::new(pair, segment_length)));
+                }
+                RopeConstraint::Cable { max_stretch } => {
+                    let max_length = segment_length * (1. + max_stretch);
+                    world.add_contact_generator(Box::new(ParticleCable::new(pair, max_length, 0.)));
+                }
+            }
+        }
+
+        RopeHandles {
+            particles,
+            segment_length,
+        }
+    }
+}
+
+/// The particles making up a bridge built by a `BridgeBuilder`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BridgeHandles {
+    /// Holds the particles along the left side of the deck, from the start to the end.
+    pub left: Vec<ParticleHandle>,
+
+    /// Holds the particles along the right side of the deck, from the start to the end.
+    pub right: Vec<ParticleHandle>,
+}
+
+impl BridgeHandles {
+    /// Returns the number of pairs of deck particles across the bridge.
+    pub fn len(&self) -> usize {
+        self.left.len()
+    }
+
+    /// Returns true if the bridge has no deck particles.
+    pub fn is_empty(&self) -> bool {
+        self.left.is_empty()
+    }
+
+    /// Returns the left and right particles of the given pair across the deck.
+    pub fn pair(&self, index: usize) -> (ParticleHandle, ParticleHandle) {
+        (self.left[index], self.right[index])
+    }
+
+    /// Returns the index of the pair whose middle is closest to the given point,
+    /// e.g. to put the weight of something standing on the deck on that pair.
+    /// Returns `None` if every deck particle is gone from the world.
+    pub fn nearest_pair(&self, world: &ParticleWorld, point: Vector3) -> Option<usize> {
+        let mut nearest = None;
+        let mut nearest_distance = f64::MAX;
+        for index in 0..self.len() {
+            let (left, right) = match (world.get(self.left[index]), world.get(self.right[index])) {
+                (Some(left), Some(right)) => (left.position, right.position),
+                _ => continue,
+            };
+            let distance = ((left + right) * 0.5 - point).square_magnitude();
+            if distance < nearest_distance {
+                nearest_distance = distance;
+                nearest = Some(index);
+            }
+        }
+        nearest
+    }
+}
+
+/// Builds a rope bridge, like the classic Cyclone demo.
+///
+/// The deck is two parallel rows of particles, each pair across the deck held apart by a rod.
+/// Cables run along each side between consecutive particles, and the four particles at the
+/// ends hang from fixed support points above them by cables, so the deck sags between them.
+///
+/// The default resolver passes the load along the cables one link at a time, so a deck
+/// carrying particles much heavier than its own keeps stretching; resolving the contacts
+/// with a `ContactBatch` holds it within the batch slop.
+#[derive(Debug, Clone, Copy)]
+pub struct BridgeBuilder {
+    /// Holds the middle of the deck at its start.
+    start: Vector3,
+
+    /// Holds the middle of the deck at its end.
+    end: Vector3,
+
+    /// Holds the number of segments along each side, one less than the number of pairs.
+    segments: usize,
+
+    /// Holds the distance between the two sides of the deck.
+    width: f64,
+
+    /// Holds the mass of the whole deck, split between its particles.
+    deck_mass: f64,
+
+    /// Holds how much the side cables can stretch, as a fraction of the segment length.
+    slack: f64,
+
+    /// Holds the height of the support points above the end particles.
+    support_height: f64,
+
+    /// Holds the damping of every particle.
+    damping: f64,
+}
+
+impl BridgeBuilder {
+    /// Constructor
+    /// The deck goes from `start` to `end` in the given number of segments. It is 2 wide,
+    /// weighs 1, has cables with 5% of slack, and hangs from supports 1 above the ends,
+    /// until configured otherwise. The span must not be vertical.
+    pub fn new(start: Vector3, end: Vector3, segments: usize) -> Self {
+        assert!(segments > 0);
+        Self {
+            start,
+            end,
+            segments,
+            width: 2.,
+            deck_mass: 1.,
+            slack: 0.05,
+            support_height: 1.,
+            damping: 0.99,
+        }
+    }
+
+    /// Sets the distance between the two sides of the deck.
+    pub fn with_width(mut self, width: f64) -> Self {
+        self.width = width;
+        self
+    }
+
+    /// Sets the mass of the whole deck, split evenly between its particles.
+    pub fn with_deck_mass(mut self, deck_mass: f64) -> Self {
+        assert!(deck_mass > 0.);
+        self.deck_mass = deck_mass;
+        self
+    }
+
+    /// Sets how much the side cables can stretch, as a fraction of the segment length.
+    pub fn with_slack(mut self, slack: f64) -> Self {
+        self.slack = slack;
+        self
+    }
+
+    /// Sets the height of the support points above the end particles.
+    pub fn with_support_height(mut self, support_height: f64) -> Self {
+        self.support_height = support_height;
+        self
+    }
+
+    /// Sets the damping of every particle.
+    pub fn with_damping(mut self, damping: f64) -> Self {
+        self.damping = damping;
+        self
+    }
+
+    /// Adds the particles, rods and cables of the bridge to the world.
+    /// Returns the deck particles, side by side.
+    pub fn build(&self, world: &mut ParticleWorld) -> BridgeHandles {
+        let up = Vector3::new(0., 1., 0.);
+        let span = self.end - self.start;
+        let across = (span % up).get_normalized() * (self.width * 0.5);
+        assert!(across.square_magnitude() > 0.);
+
+        let count = self.segments + 1;
+        let particle_mass = self.deck_mass / (2 * count) as f64;
+        let step = span * (1. / self.segments as f64);
+
+        let zero = Vector3::new(0., 0., 0.);
+        let mut add = |position: Vector3| {
+            let mut particle = Particle::new(position, zero, zero, self.damping);
+            particle.set_mass(particle_mass);
+            world.add_particle(particle)
+        };
+        let mut left = Vec::with_capacity(count);
+        let mut right = Vec::with_capacity(count);
+        for i in 0..count {
+            let middle = self.start + step * (i as f64);
+            left.push(add(middle - across));
+            right.push(add(middle + across));
+        }
+
+        // Rods hold the sides apart.
+        for i in 0..count {
+            world
+                .add_contact_generator(Box::new(ParticleRod::new((left[i], right[i]), self.width)));
+        }
+
+        // Cables run along the sides.
+        let max_length = step.magnitude() * (1. + self.slack);
+        for side in [&left, &right] {
+            for pair in side.windows(2) {
+                let cable = ParticleCable::new((pair[0], pair[1]), max_length, 0.);
+                world.add_contact_generator(Box::new(cable));
+            }
+        }
+
+        // The ends hang from the supports.
+        let ends = [
+            (left[0], self.start - across),
+            (right[0], self.start + across),
+            (left[count - 1], self.end - across),
+            (right[count - 1], self.end + across),
+        ];
+        for (handle, position) in ends {
+            let anchor = position + up * self.support_height;
+            let support = ParticleCableConstraint::new(handle, anchor, self.support_height, 0.);
+            world.add_contact_generator(Box::new(support));
+        }
+
+        BridgeHandles { left, right }
+    }
+}
+
+/// The stiffness and damping of one kind of cloth spring.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClothSpring {
+    /// Holds the spring constant.
+    pub stiffness: f64,
+
+    /// Holds the damping coefficient along the spring axis.
+    pub damping: f64,
+}
+
+impl ClothSpring {
+    /// Constructor
+    pub fn new(stiffness: f64, damping: f64) -> Self {
+        Self { stiffness, damping }
+    }
+}
+
+/// The particles making up a cloth built by a `ClothBuilder`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClothHandles {
+    /// Holds the particles of the cloth, row by row.
+    pub particles: Vec<ParticleHandle>,
+
+    /// Holds the number of particles in every row.
+    pub columns: usize,
+
+    /// Holds the number of rows.
+    pub rows: usize,
+}
+
+impl ClothHandles {
+    /// Returns the particle at the given grid point.
+    pub fn handle(&self, column: usize, row: usize) -> ParticleHandle {
+        assert!(column < self.columns && row < self.rows);
+        self.particles[row * self.columns + column]
+    }
+}
+
+/// Builds a rectangular cloth of particles held together by damped springs.
+///
+/// Structural springs join the horizontal and vertical neighbors, shear springs
+/// the diagonal ones, and bend springs the particles two apart, which keeps the
+/// cloth from folding too sharply.
+///
+/// Springs are explicit forces, so they blow up when a step is too long for the
+/// stiffness and damping pulling on each particle. The default springs hang a 10 × 10
+/// cloth steadily with steps of 1/240 s; run several shorter steps per frame, or use
+/// softer springs, rather than stepping them at 1/60 s.
+#[derive(Debug, Clone)]
+pub struct ClothBuilder {
+    /// Holds the position of the particle at the grid point (0, 0).
+    origin: Vector3,
+
+    /// Holds the offset between neighboring columns.
+    across: Vector3,
+
+    /// Holds the offset between neighboring rows.
+    down: Vector3,
+
+    /// Holds the number of particles in every row.
+    columns: usize,
+
+    /// Holds the number of rows.
+    rows: usize,
+
+    /// Holds the mass of the whole cloth, split between the movable particles.
+    total_mass: f64,
+
+    /// Holds the damping of every particle.
+    damping: f64,
+
+    /// Holds the springs between horizontal and vertical neighbors, if any.
+    structural: Option<ClothSpring>,
+
+    /// Holds the springs between diagonal neighbors, if any.
+    shear: Option<ClothSpring>,
+
+    /// Holds the springs between particles two apart, if any.
+    bend: Option<ClothSpring>,
+
+    /// Holds the grid points of the immovable particles.
+    pinned: Vec<(usize, usize)>,
+}
+
+impl ClothBuilder {
+    /// Constructor
+    /// The cloth has `columns` by `rows` particles `spacing` apart, with columns along the
+    /// positive x axis and rows going down the negative y axis from `origin`, so it hangs
+    /// from its first row. It weighs 1, has structural and shear springs, and nothing is
+    /// pinned, until configured otherwise.
+    pub fn new(origin: Vector3, columns: usize, rows: usize, spacing: f64) -> Self {
+        assert!(columns > 0 && rows > 0);
+        Self {
+            origin,
+            across: Vector3::new(spacing, 0., 0.),
+            down: Vector3::new(0., -spacing, 0.),
+            columns,
+            rows,
+            total_mass: 1.,
+            damping: 0.99,
+            structural: Some(ClothSpring::new(100., 0.5)),
+            shear: Some(ClothSpring::new(50., 0.25)),
+            bend: None,
+            pinned: Vec::new(),
+        }
+    }
+
+    /// Sets the offsets between neighboring columns and rows, to lay the cloth out
+    /// in another plane, e.g. flat on the ground.
+    pub fn with_axes(mut self, across: Vector3, down: Vector3) -> Self {
+        self.across = across;
+        self.down = down;
+        self
+    }
+
+    /// Sets the mass of the whole cloth, split evenly between the movable particles.
+    pub fn with_mass(mut self, total_mass: f64) -> Self {
+        assert!(total_mass > 0.);
+        self.total_mass = total_mass;
+        self
+    }
+
+    /// Sets the damping of every particle.
+    pub fn with_damping(mut self, damping: f64) -> Self {
+        self.damping = damping;
+        self
+    }
+
+    /// Sets the springs between horizontal and vertical neighbors, or `None` for none.
+    pub fn with_structural(mut self, spring: Option<ClothSpring>) -> Self {
+        self.structural = spring;
+        self
+    }
+
+    /// Sets the springs between diagonal neighbors, or `None` for none.
+    pub fn with_shear(mut self, spring: Option<ClothSpring>) -> Self {
+        self.shear = spring;
+        self
+    }
+
+    /// Sets the springs between particles two apart, or `None` for none.
+    pub fn with_bend(mut self, spring: Option<ClothSpring>) -> Self {
+        self.bend = spring;
+        self
+    }
+
+    /// Makes the particle at the given grid point immovable.
+    pub fn with_pinned(mut self, column: usize, row: usize) -> Self {
+        assert!(column < self.columns && row < self.rows);
+        self.pinned.push((column, row));
+        self
+    }
+
+    /// Makes every particle of the first row immovable, like a curtain on its rail.
+    pub fn with_pinned_top_row(mut self) -> Self {
+        for column in 0..self.columns {
+            self.pinned.push((column, 0));
+        }
+        self
+    }
+
+    /// Adds the particles and springs of the cloth to the world.
+    /// Returns the particles, row by row.
+    pub fn build(&self, world: &mut ParticleWorld) -> ClothHandles {
+        let is_pinned = |column: usize, row: usize| self.pinned.contains(&(column, row));
+        let count = self.columns * self.rows;
+        let pinned = (0..count)
+            .filter(|index| is_pinned(index % self.columns, index / self.columns))
+            .count();
+        let movable = count.saturating_sub(pinned).max(1);
+        let particle_mass = self.total_mass / movable as f64;
+
+        let zero = Vector3::new(0., 0., 0.);
+        let mut particles = Vec::with_capacity(count);
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let position =
+                    self.origin + self.across * (column as f64) + self.down * (row as f64);
+                let mut particle = Particle::new(position, zero, zero, self.damping);
+                if is_pinned(column, row) {
+                    particle.set_inverse_mass(0.);
+                } else {
+                    particle.set_mass(particle_mass);
+                }
+                particles.push(world.add_particle(particle));
+            }
+        }
+
+        let cloth = ClothHandles {
+            particles,
+            columns: self.columns,
+            rows: self.rows,
+        };
+
+        // Every spring is listed once, from the grid point to a neighbor further along.
+        let mut links: Vec<((isize, isize), ClothSpring)> = Vec::new();
+        if let Some(spring) = self.structural {
+            links.extend([((1, 0), spring), ((0, 1), spring)]);
+        }
+        if let Some(spring) = self.shear {
+            links.extend([((1, 1), spring), ((-1, 1), spring)]);
+        }
+        if let Some(spring) = self.bend {
+            links.extend([((2, 0), spring), ((0, 2), spring)]);
+        }
+
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                for &((dc, dr), spring) in &links {
+                    let other_column = column as isize + dc;
+                    let other_row = row + dr as usize;
+                    if other_column < 0
+                        || other_column as usize >= self.columns
+                        || other_row >= self.rows
+                    {
+                        continue;
+                    }
+                    let a = cloth.handle(column, row);
+                    let b = cloth.handle(other_column as usize, other_row);
+                    let rest_length =
+                        (self.across * (dc as f64) + self.down * (dr as f64)).magnitude();
+                    connect(world, (a, b), spring, rest_length);
+                }
+            }
+        }
+
+        cloth
+    }
+}
+
+/// Registers a damped spring between the two particles, acting on both ends.
+fn connect(
+    world: &mut ParticleWorld,
+    (a, b): (ParticleHandle, ParticleHandle),
+    spring: ClothSpring,
+    rest_length: f64,
+) {
+    let spring = ParticleDampedSpring::new(spring.stiffness, rest_length, spring.damping);
+    let registry = world.get_force_registry_mut();
+    registry.add_pair(a, b, Box::new(spring));
+    registry.add_pair(b, a, Box::new(spring));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hanging_rope_settles_into_a_vertical_chain() {
+        let mut world = ParticleWorld::new();
+        world.set_gravity(Vector3::new(0., -9.81, 0.));
+        world.set_resolver_iterations(Some(100));
+        world.set_calculate_iterations(false);
+        let rope =
+            RopeBuilder::from_anchor(Vector3::new(0., 5., 0.), Vector3::new(1., -1., 0.), 2., 5)
+                .with_mass(2.5)
+                .with_damping(0.2)
+                .build(&mut world);
+        assert_eq!(rope.particles.len(), 6);
+        assert!((rope.segment_length - 0.4).abs() < 1e-12);
+        assert!(!world.get(rope.first()).unwrap().has_finite_mass());
+        assert!((world.get(rope.last()).unwrap().get_mass() - 0.5).abs() < 1e-12);
+
+        for _ in 0..3000 {
+            world.start_frame();
+            world.run_physics(0.01);
+        }
+
+        for (i, pair) in rope.particles.windows(2).enumerate() {
+            let upper = world.get(pair[0]).unwrap().position;
+            let lower = world.get(pair[1]).unwrap().position;
+            assert!(((upper - lower).magnitude() - rope.segment_length).abs() < 1e-3);
+            assert!(
+                lower.x.abs() < 0.01 && lower.z.abs() < 1e-9,
+                "segment {i} at {lower:?}"
+            );
+            assert!(lower.y < upper.y);
+            // What is left is less than the speed gravity adds in a single frame.
+            assert!(world.get(pair[1]).unwrap().speed() < 0.05);
+        }
+        let anchor = world.get(rope.first()).unwrap().position;
+        assert_eq!(anchor.manhattan_distance(&Vector3::new(0., 5., 0.)), 0.);
+    }
+
+    #[test]
+    fn pinned_cloth_drapes_without_overstretching() {
+        let mut world = ParticleWorld::new();
+        world.set_gravity(Vector3::new(0., -9.81, 0.));
+        let spacing = 0.1;
+        let cloth = ClothBuilder::new(Vector3::new(0., 2., 0.), 10, 10, spacing)
+            .with_bend(Some(ClothSpring::new(20., 0.1)))
+            .with_pinned_top_row()
+            .build(&mut world);
+        assert_eq!(cloth.particles.len(), 100);
+        // Structural, shear and bend springs, each registered on both ends.
+        assert_eq!(world.get_force_registry().len(), 2 * (180 + 162 + 160));
+        let corner = world.get(cloth.handle(9, 9)).unwrap().position;
+        assert_eq!(corner.manhattan_distance(&Vector3::new(0.9, 1.1, 0.)), 0.);
+
+        for _ in 0..4800 {
+            world.start_frame();
+            world.run_physics(1. / 240.);
+        }
+        assert!(world.get_last_stats().max_speed < 0.01);
+
+        for row in 0..10 {
+            for column in 0..10 {
+                let here = world.get(cloth.handle(column, row)).unwrap().position;
+                assert!(here.z.abs() < 1e-9);
+                for (right, down) in [(column + 1, row), (column, row + 1)] {
+                    if right < 10 && down < 10 {
+                        let there = world.get(cloth.handle(right, down)).unwrap().position;
+                        assert!((here - there).magnitude() < spacing * 1.5);
+                    }
+                }
+                if row > 0 {
+                    // Everything below the rail has sagged.
+                    assert!(here.y < 2. - spacing * row as f64);
+                }
+            }
+        }
+        assert_eq!(world.get(cloth.handle(4, 0)).unwrap().position.y, 2.);
+    }
+
+    #[test]
+    fn loaded_bridge_sags_within_its_cable_lengths() {
+        use crate::kellenth::contacts::ContactBatch;
+
+        let mut world = ParticleWorld::new();
+        world.set_gravity(Vector3::new(0., -9.81, 0.));
+        world.set_contact_batch(Some(ContactBatch::new(50, 0.01, 0.2)));
+        let bridge = BridgeBuilder::new(Vector3::new(0., 0., 0.), Vector3::new(10., 0., 0.), 10)
+            .with_deck_mass(2.)
+            .build(&mut world);
+        assert_eq!(bridge.len(), 11);
+
+        // A player weighing five times the deck stands on the pair nearest the middle.
+        let middle = bridge
+            .nearest_pair(&world, Vector3::new(5.2, 0., 0.3))
+            .unwrap();
+        assert_eq!(middle, 5);
+        let (left, right) = bridge.pair(middle);
+        for handle in [left, right] {
+            world
+                .get_force_registry_mut()
+                .add_fn(handle, |particle, _| {
+                    particle.add_force(Vector3::new(0., -5. * 9.81, 0.))
+                });
+        }
+
+        for _ in 0..2000 {
+            world.start_frame();
+            world.run_physics(0.01);
+        }
+
+        let position = |handle| world.get(handle).unwrap().position;
+        let tolerance = 0.02;
+        let max_length = 1.05;
+        for side in [&bridge.left, &bridge.right] {
+            for pair in side.windows(2) {
+                assert!(
+                    (position(pair[0]) - position(pair[1])).magnitude() < max_length + tolerance
+                );
+            }
+        }
+        for i in 0..bridge.len() {
+            let (left, right) = bridge.pair(i);
+            assert!(((position(left) - position(right)).magnitude() - 2.).abs() < tolerance);
+        }
+        for (handle, anchor) in [
+            (bridge.left[0], Vector3::new(0., 1., -1.)),
+            (bridge.right[10], Vector3::new(10., 1., 1.)),
+        ] {
+            assert!((position(handle) - anchor).magnitude() < 1. + tolerance);
+        }
+
+        // The load pulls the middle of the deck down below its ends.
+        let sag = position(bridge.left[0]).y - position(left).y;
+        assert!(sag > 1., "{sag}");
+        assert!(position(left).y < position(bridge.left[3]).y);
+    }
+}
@@ -0,0 +1,366 @@
+//! Holds the particle emitter, which spawns particles into a `ParticleWorld` over time
+//! and removes them again when their lifetime runs out.
+
+use std::fmt;
+
+use crate::kellenth::core::*;
+use crate::kellenth::noise::Random;
+use crate::kellenth::particle::Particle;
+
+use super::{ParticleHandle, ParticleWorld};
+
+/// The region new particles are spawned in, around the position of the emitter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum EmitterShape {
+    /// Every particle starts at the position of the emitter.
+    Point,
+
+    /// Particles start anywhere inside the sphere of the given radius.
+    Sphere {
+        /// Holds the radius of the sphere.
+        radius: f64,
+    },
+
+    /// Particles start anywhere inside the cone with its apex at the emitter, opening
+    /// along the emitter direction by the given half-angle, up to the given length.
+    Cone {
+        /// Holds the half-angle of the cone, in radians.
+        angle: f64,
+
+        /// Holds how far from the apex particles can start.
+        length: f64,
+    },
+}
+
+/// A particle spawned by an emitter, with the time it has left to live.
+#[derive(Debug, Clone, Copy)]
+struct LiveParticle {
+    /// Holds the particle in the world.
+    handle: ParticleHandle,
+
+    /// Holds the time left before the particle is removed.
+    remaining: f64,
+}
+
+/// Spawns particles over time with randomized initial conditions, for fireworks,
+/// smoke or debris.
+///
+/// Particles are spawned at `rate` per second, carrying the fraction left over from
+/// one update to the next, and every one lives for a random time within the lifetime
+/// range, after which `update` removes it from the world. The emitter never keeps more
+/// than `max_live` particles alive; spawns that would go over the cap are skipped.
+pub struct ParticleEmitter {
+    /// Holds the position of the emitter.
+    position: Vector3,
+
+    /// Holds the direction particles are launched in, normalized.
+    direction: Vector3,
+
+    /// Holds the region new particles are spawned in.
+    shape: EmitterShape,
+
+    /// Holds the number of particles spawned per second.
+    rate: f64,
+
+    /// Holds the fraction of a particle carried over to the next update.
+    accumulator: f64,
+
+    /// Holds the half-angle, in radians, of the cone of launch directions.
+    spread: f64,
+
+    /// Holds the range of launch speeds.
+    speed: (f64, f64),
+
+    /// Holds the range of masses.
+    mass: (f64, f64),
+
+    /// Holds the range of lifetimes, in seconds.
+    lifetime: (f64, f64),
+
+    /// Holds the damping of every particle.
+    damping: f64,
+
+    /// Holds the maximum number of particles alive at once.
+    max_live: usize,
+
+    /// Holds the particles spawned and still alive.
+    live: Vec<LiveParticle>,
+
+    /// Holds the callback given every new particle before it is added to the world, if any.
+    on_spawn: Option<SpawnCallback>,
+}
+
+/// The callback given every new particle before it is added to the world.
+type SpawnCallback = Box<dyn FnMut(&mut Particle)>;
+
+impl fmt::Debug for ParticleEmitter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ParticleEmitter")
+            .field("position", &self.position)
+            .field("direction", &self.direction)
+            .field("shape", &self.shape)
+            .field("rate", &self.rate)
+            .field("accumulator", &self.accumulator)
+            .field("spread", &self.spread)
+            .field("speed", &self.speed)
+            .field("mass", &self.mass)
+            .field("lifetime", &self.lifetime)
+            .field("damping", &self.damping)
+            .field("max_live", &self.max_live)
+            .field("live", &self.live.len())
+            .field("on_spawn", &self.on_spawn.is_some())
+            .finish()
+    }
+}
+
+impl ParticleEmitter {
+    /// Constructor
+    /// The emitter spawns the given number of particles per second from a point, straight
+    /// up at speed 1. They weigh 1 and live for 1 second, and at most 1000 are alive at
+    /// once, until configured otherwise.
+    pub fn new(position: Vector3, rate: f64) -> Self {
+        Self {
+            position,
+            direction: Vector3::new(0., 1., 0.),
+            shape: EmitterShape::Point,
+            rate,
+            accumulator: 0.,
+            spread: 0.,
+            speed: (1., 1.),
+            mass: (1., 1.),
+            lifetime: (1., 1.),
+            damping: 0.99,
+            max_live: 1000,
+            live: Vec::new(),
+            on_spawn: None,
+        }
+    }
+
+    /// Sets the region new particles are spawned in.
+    pub fn with_shape(mut self, shape: EmitterShape) -> Self {
+        self.shape = shape;
+        self
+    }
+
+    /// Sets the direction particles are launched in, and the half-angle in radians
+    /// of the cone of directions around it.
+    pub fn with_direction(mut self, direction: Vector3, spread: f64) -> Self {
+        self.direction = direction.get_normalized();
+        self.spread = spread;
+        self
+    }
+
+    /// Sets the range of launch speeds.
+    pub fn with_speed(mut self, min: f64, max: f64) -> Self {
+        self.speed = (min, max);
+        self
+    }
+
+    /// Sets the range of masses.
+    pub fn with_mass(mut self, min: f64, max: f64) -> Self {
+        assert!(min > 0.);
+        self.mass = (min, max);
+        self
+    }
+
+    /// Sets the range of lifetimes, in seconds.
+    pub fn with_lifetime(mut self, min: f64, max: f64) -> Self {
+        self.lifetime = (min, max);
+        self
+    }
+
+    /// Sets the damping of every particle.
+    pub fn with_damping(mut self, damping: f64) -> Self {
+        self.damping = damping;
+        self
+    }
+
+    /// Sets the maximum number of particles alive at once.
+    pub fn with_max_live(mut self, max_live: usize) -> Self {
+        self.max_live = max_live;
+        self
+    }
+
+    /// Sets a callback given every new particle before it is added to the world,
+    /// to customize it further, e.g. with a material.
+    pub fn with_on_spawn<F>(mut self, on_spawn: F) -> Self
+    where
+        F: FnMut(&mut Particle) + 'static,
+    {
+        self.on_spawn = Some(Box::new(on_spawn));
+        self
+    }
+
+    /// Returns the position of the emitter.
+    pub fn get_position(&self) -> Vector3 {
+        self.position
+    }
+
+    /// Moves the emitter, e.g. to follow a rocket. Particles already spawned stay where they are.
+    pub fn set_position(&mut self, position: Vector3) {
+        self.position = position;
+    }
+
+    /// Returns the number of particles spawned per second.
+    pub fn get_rate(&self) -> f64 {
+        self.rate
+    }
+
+    /// Sets the number of particles spawned per second; zero stops the emitter.
+    pub fn set_rate(&mut self, rate: f64) {
+        self.rate = rate;
+    }
+
+    /// Returns the number of particles spawned by the emitter and still alive.
+    pub fn get_live_count(&self) -> usize {
+        self.live.len()
+    }
+
+    /// Returns the particles spawned by the emitter and still alive.
+    pub fn get_live_particles(&self) -> impl Iterator<Item = ParticleHandle> + '_ {
+        self.live.iter().map(|live| live.handle)
+    }
+
+    /// Removes the expired particles from the world, then spawns the particles due in
+    /// the given duration, drawing their initial conditions from `rng`.
+    /// Particles removed from the world by something else are forgotten.
+    /// Returns the number of particles spawned.
+    pub fn update(&mut self, world: &mut ParticleWorld, duration: f64, rng: &mut Random) -> usize {
+        // Age the live particles, removing the ones whose time is up.
+        self.live.retain_mut(|live| {
+            live.remaining -= duration;
+            if live.remaining <= 0. {
+                world.remove_particle(live.handle);
+                return false;
+            }
+            world.contains(live.handle)
+        });
+
+        // Work out how many particles are due, keeping the fraction for next time.
+        self.accumulator += self.rate * duration;
+        let due = self.accumulator.floor();
+        self.accumulator -= due;
+        let count = (due as usize).min(self.max_live.saturating_sub(self.live.len()));
+
+        let zero = Vector3::new(0., 0., 0.);
+        for _ in 0..count {
+            let offset = match self.shape {
+                EmitterShape::Point => zero,
+                EmitterShape::Sphere { radius } => rng.in_sphere(radius),
+                EmitterShape::Cone { angle, length } => {
+                    cone_direction(self.direction, angle, rng) * (length * rng.next_f64().cbrt())
+                }
+            };
+            let velocity = cone_direction(self.direction, self.spread, rng)
+                * rng.range(self.speed.0, self.speed.1);
+
+            let mut particle = Particle::new(self.position + offset, velocity, zero, self.damping);
+            particle.set_mass(rng.range(self.mass.0, self.mass.1));
+            if let Some(on_spawn) = &mut self.on_spawn {
+                on_spawn(&mut particle);
+            }
+
+            self.live.push(LiveParticle {
+                handle: world.add_particle(particle),
+                remaining: rng.range(self.lifetime.0, self.lifetime.1),
+            });
+        }
+        count
+    }
+}
+
+/// Returns a random unit vector within the given half-angle of the axis,
+/// uniformly distributed over that cap of the sphere.
+fn cone_direction(axis: Vector3, angle: f64, rng: &mut Random) -> Vector3 {
+    // Pick the angle from the axis so every part of the cap is equally likely.
+    let cos_angle = 1. - rng.next_f64() * (1. - angle.cos());
+    let sin_angle = (1. - cos_angle * cos_angle).max(0.).sqrt();
+    let around = rng.range(0., std::f64::consts::TAU);
+
+    // Build two unit vectors perpendicular to the axis.
+    let helper = if axis.x.abs() < 0.9 {
+        Vector3::new(1., 0., 0.)
+    } else {
+        Vector3::new(0., 1., 0.)
+    };
+    let first = (axis % helper).get_normalized();
+    let second = axis % first;
+
+    axis * cos_angle + (first * around.cos() + second * around.sin()) * sin_angle
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Returns an emitter spraying particles upwards from a small sphere.
+    fn fountain(rate: f64) -> ParticleEmitter {
+        ParticleEmitter::new(Vector3::new(0., 1., 0.), rate)
+            .with_shape(EmitterShape::Sphere { radius: 0.5 })
+            .with_direction(Vector3::new(0., 2., 0.), 0.3)
+            .with_speed(2., 4.)
+            .with_mass(0.5, 1.5)
+            .with_lifetime(0.5, 1.5)
+    }
+
+    /// Runs the emitter for the given number of updates, returning the world and
+    /// the number of particles spawned by each update.
+    fn run(
+        emitter: &mut ParticleEmitter,
+        updates: usize,
+        seed: u64,
+    ) -> (ParticleWorld, Vec<usize>) {
+        let mut world = ParticleWorld::new();
+        let mut rng = Random::new(seed);
+        let spawned = (0..updates)
+            .map(|_| emitter.update(&mut world, 0.1, &mut rng))
+            .collect();
+        (world, spawned)
+    }
+
+    #[test]
+    fn spawning_is_deterministic_for_a_seed() {
+        let (first, spawned) = run(&mut fountain(25.), 4, 7);
+        // The half particle left over every update is carried to the next one.
+        assert_eq!(spawned, [2, 3, 2, 3]);
+        let (second, _) = run(&mut fountain(25.), 4, 7);
+        let keys = |world: &ParticleWorld| {
+            world
+                .get_particles()
+                .iter()
+                .map(Particle::bit_key)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(keys(&first), keys(&second));
+
+        let up = Vector3::new(0., 1., 0.);
+        for particle in first.get_particles() {
+            assert!((particle.position - Vector3::new(0., 1., 0.)).magnitude() <= 0.5);
+            assert!(particle.velocity.angle_between(&up) <= 0.3 + 1e-12);
+            assert!(particle.speed() >= 2. && particle.speed() < 4.);
+            assert!(particle.get_mass() >= 0.5 && particle.get_mass() < 1.5);
+        }
+    }
+
+    #[test]
+    fn live_particles_stay_under_the_cap_and_expire() {
+        let mut emitter = fountain(1000.).with_max_live(30);
+        let mut world = ParticleWorld::new();
+        let mut rng = Random::new(1);
+        let mut total = 0;
+        for _ in 0..20 {
+            total += emitter.update(&mut world, 0.1, &mut rng);
+            assert!(emitter.get_live_count() <= 30);
+            assert_eq!(world.len(), emitter.get_live_count());
+        }
+        // Expired particles make room for new ones.
+        assert!(total > 30);
+
+        emitter.set_rate(0.);
+        for _ in 0..15 {
+            emitter.update(&mut world, 0.1, &mut rng);
+        }
+        assert_eq!(emitter.get_live_count(), 0);
+        assert!(world.is_empty());
+    }
+}
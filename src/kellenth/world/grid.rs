@@ -0,0 +1,209 @@
+//! Holds the uniform grid broad-phase used to accelerate neighbor queries.
+//!
+//! The grid hashes every particle into a cubic cell of side `cell_size`.
+//! A radius query then only has to look at the cells overlapping the query
+//! sphere instead of scanning every particle in the world.
+//!
+//! # Choosing a cell size
+//! The recommended cell size is equal to (or slightly larger than) the query
+//! radius you use most often. With `cell_size == radius` a query touches a
+//! 3x3x3 block of cells. Much smaller cells make each query visit many empty
+//! cells, while much larger cells put too many particles in every cell and
+//! the grid degenerates back into a linear scan.
+
+use std::collections::HashMap;
+
+use crate::kellenth::core::*;
+use crate::kellenth::particle::Particle;
+
+/// Integer coordinates of a grid cell.
+pub type CellKey = (i64, i64, i64);
+
+/// Spatial hash of particle indices keyed by cell coordinates.
+#[derive(Debug, Clone)]
+pub struct UniformGrid {
+    /// Side length of a single cubic cell.
+    cell_size: f64,
+
+    /// Particle indices stored in each occupied cell.
+    cells: HashMap<CellKey, Vec<usize>>,
+}
+
+impl UniformGrid {
+    /// Constructor
+    /// The cell size must be strictly positive.
+    pub fn new(cell_size: f64) -> Self {
+        assert!(cell_size > 0.);
+        Self {
+            cell_size,
+            cells: HashMap::new(),
+        }
+    }
+
+    /// Returns the side length of a cell.
+    pub fn get_cell_size(&self) -> f64 {
+        self.cell_size
+    }
+
+    /// Returns the coordinates of the cell containing the given position.
+    pub fn cell_of(&self, position: Vector3) -> CellKey {
+        (
+            (position.x / self.cell_size).floor() as i64,
+            (position.y / self.cell_size).floor() as i64,
+            (position.z / self.cell_size).floor() as i64,
+        )
+    }
+
+    /// Removes every particle from the grid.
+    pub fn clear(&mut self) {
+        self.cells.clear();
+    }
+
+    /// Inserts the particle with the given index at the given position.
+    pub fn insert(&mut self, index: usize, position: Vector3) {
+        let key = self.cell_of(position);
+        self.cells.entry(key).or_default().push(index);
+    }
+
+    /// Rebuilds the grid from scratch using the current particle positions.
+    /// Must be called whenever the particles have moved, usually after each step.
+    pub fn rebuild(&mut self, particles: &[Particle]) {
+        self.clear();
+        for (index, particle) in particles.iter().enumerate() {
+            self.insert(index, particle.position);
+        }
+    }
+
+    /// Returns the indices of all the particles within `radius` of `center`,
+    /// sorted in ascending order.
+    pub fn query_radius(&self, particles: &[Particle], center: Vector3, radius: f64) -> Vec<usize> {
+        let offset = Vector3 {
+            x: radius,
+            y: radius,
+            z: radius,
+        };
+        let min = self.cell_of(center - offset);
+        let max = self.cell_of(center + offset);
+        let radius_squared = radius * radius;
+
+        let mut result = Vec::new();
+        for x in min.0..=max.0 {
+            for y in min.1..=max.1 {
+                for z in min.2..=max.2 {
+                    if let Some(cell) = self.cells.get(&(x, y, z)) {
+                        result.extend(cell.iter().copied().filter(|&index| {
+                            (particles[index].position - center).square_magnitude()
+                                <= radius_squared
+                        }));
+                    }
+                }
+            }
+        }
+        result.sort_unstable();
+        result
+    }
+
+    /// Returns every pair of particles closer than `radius` to each other.
+    /// Each pair is reported once as `(i, j)` with `i < j`, sorted in ascending order.
+    pub fn neighbor_pairs(&self, particles: &[Particle], radius: f64) -> Vec<(usize, usize)> {
+        let reach = (radius / self.cell_size).ceil() as i64;
+        let radius_squared = radius * radius;
+
+        let mut result = Vec::new();
+        for (key, cell) in &self.cells {
+            for dx in -reach..=reach {
+                for dy in -reach..=reach {
+                    for dz in -reach..=reach {
+                        let other = match self.cells.get(&(key.0 + dx, key.1 + dy, key.2 + dz)) {
+                            Some(other) => other,
+                            None => continue,
+                        };
+                        for &i in cell {
+                            for &j in other.iter().filter(|&&j| j > i) {
+                                if (particles[i].position - particles[j].position)
+                                    .square_magnitude()
+                                    <= radius_squared
+                                {
+                                    result.push((i, j));
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        result.sort_unstable();
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::kellenth::noise::Random;
+
+    fn scattered_particles(count: usize, seed: u64) -> Vec<Particle> {
+        let mut rng = Random::new(seed);
+        let zero = Vector3::new(0., 0., 0.);
+        (0..count)
+            .map(|_| Particle::new(rng.in_sphere(10.), zero, zero, 1.))
+            .collect()
+    }
+
+    fn brute_force_query(particles: &[Particle], center: Vector3, radius: f64) -> Vec<usize> {
+        (0..particles.len())
+            .filter(|&i| (particles[i].position - center).square_magnitude() <= radius * radius)
+            .collect()
+    }
+
+    fn brute_force_pairs(particles: &[Particle], radius: f64) -> Vec<(usize, usize)> {
+        let mut pairs = Vec::new();
+        for i in 0..particles.len() {
+            for j in (i + 1)..particles.len() {
+                if (particles[i].position - particles[j].position).square_magnitude()
+                    <= radius * radius
+                {
+                    pairs.push((i, j));
+                }
+            }
+        }
+        pairs
+    }
+
+    #[test]
+    fn query_radius_matches_brute_force() {
+        let particles = scattered_particles(500, 1);
+        let mut rng = Random::new(2);
+        for cell_size in [0.5, 1.5, 4.] {
+            let mut grid = UniformGrid::new(cell_size);
+            grid.rebuild(&particles);
+            for _ in 0..50 {
+                let center = rng.in_sphere(12.);
+                let radius = rng.range(0.1, 3.);
+                assert_eq!(
+                    grid.query_radius(&particles, center, radius),
+                    brute_force_query(&particles, center, radius)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn neighbor_pairs_match_brute_force() {
+        let particles = scattered_particles(400, 3);
+        for (cell_size, radius) in [(1., 1.), (0.5, 1.2), (3., 0.8)] {
+            let mut grid = UniformGrid::new(cell_size);
+            grid.rebuild(&particles);
+            assert_eq!(
+                grid.neighbor_pairs(&particles, radius),
+                brute_force_pairs(&particles, radius)
+            );
+        }
+    }
+
+    #[test]
+    fn negative_coordinates_fall_in_their_own_cells() {
+        let grid = UniformGrid::new(1.);
+        assert_eq!(grid.cell_of(Vector3::new(-0.5, 0.5, -1.)), (-1, 0, -1));
+    }
+}